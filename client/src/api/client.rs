@@ -1,17 +1,34 @@
 use reqwest::header::HeaderMap;
 use serde_json::json;
 
+use crate::api::clock::ServerClock;
+use crate::api::health::{HealthMonitor, ServerState};
 use crate::api::models::*;
+use crate::api::stream::JsonArrayDecoder;
+use crate::api::tls::{self, TlsError};
+use crate::api::traffic::{Priority, Slot, TrafficShaper};
+use crate::config::cache::CachePolicy;
+use crate::config::connection::{ConnectionPolicy, Http2Mode};
+use crate::config::proxy::ProxyPolicy;
+use crate::config::retry::RetryPolicy;
 use crate::config::settings::Config;
-use crate::util::auth::{ExtraItem, UserKeys};
+use crate::pin::store::PinStore;
+use crate::util::auth::{ExtraItem, UserKeys, STREAMED_BODY};
+use crate::util::compression::{self, ContentEncoding};
 use crate::util::date::format_datetime;
 use crate::util::fs::format_permissions;
-use crate::util::path::{get_file_name, get_parent_path};
-use std::time::Duration;
+use crate::util::path::{encode_path, get_file_name, get_parent_path, join_path};
+use futures::{StreamExt, TryStreamExt};
+use sha2::{Digest, Sha256};
+use std::time::{Duration, Instant};
 
 use moka::sync::Cache as MokaCache;
+use moka::Expiry;
 
 use parking_lot::Mutex;
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::OsStrExt;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 #[derive(Debug, thiserror::Error)]
@@ -33,23 +50,180 @@ pub enum ClientError {
 
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
+
+    #[error("Server unreachable, waiting for it to come back")]
+    Unavailable,
+
+    #[error("No answer from the server within {0:?}")]
+    Timeout(Duration),
+
+    #[error("Listing cursor expired")]
+    CursorExpired,
+
+    #[error("Response integrity check failed: {0}")]
+    Integrity(String),
+
+    #[error("TLS configuration error: {0}")]
+    Tls(#[from] TlsError),
+
+    #[error("Proxy configuration error: {0}")]
+    Proxy(String),
 }
 
-const READ_ALIGN: u64 = 4096;
-const READ_PREFETCH: u64 = 2 * 1024 * 1024;
+const LIST_PAGE_SIZE: usize = 1000;
+const SIGNED_BODY_LIMIT: usize = 1024 * 1024; // larger answers are streamed by the server
 
 fn align_down(v: u64, a: u64) -> u64 {
     v - (v % a)
 }
 
+// Cuts the ranges in pieces of about `chunk` bytes (a multiple of `align`), to be
+// requested in parallel
+fn split_ranges(ranges: &[Extent], chunk: u64, align: u64) -> Vec<Extent> {
+    let piece = chunk.div_ceil(align).max(1) * align;
+    let mut pieces = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        let end = range.offset + range.length;
+        let mut offset = range.offset;
+        while offset < end {
+            let length = piece.min(end - offset);
+            pieces.push(Extent { offset, length });
+            offset += length;
+        }
+    }
+    pieces
+}
+
 pub struct RemoteClient {
     base_url: String,
     http_client: reqwest::Client,
     user_keys: UserKeys,
     timeout: Duration,
     pub path_mounting: String,
-    cache_metadata: MokaCache<String, DirectoryListing>,
-    read_buf: MokaCache<String, Arc<Mutex<BitmapReadBuf>>>,
+    cache_metadata: MokaCache<OsString, DirectoryListing>,
+    read_buf: MokaCache<OsString, Arc<Mutex<BitmapReadBuf>>>,
+    negative_lookup: MokaCache<OsString, ()>, // paths known not to exist
+    extents: MokaCache<OsString, Arc<FileExtents>>, // data ranges of sparse files
+    cache_policy: Arc<CachePolicy>,
+    pin_store: Option<Arc<PinStore>>, // pinned files, served locally and when offline
+    retry: RetryPolicy,
+    health: HealthMonitor,
+    clock: ServerClock,
+    strict_integrity: bool,
+    upload_encoding: Mutex<Option<ContentEncoding>>, // as advertised by the server
+    server_signs: AtomicBool, // an answer came signed: the server is not an old one
+    traffic: Arc<TrafficShaper>,
+    connection: ConnectionPolicy,
+}
+
+// Whether a request may be sent again after an attempt whose outcome is unknown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Replay {
+    Safe,   // idempotent: replayed after any transient failure
+    Unsafe, // replayed only when the server surely did not act on it
+}
+
+// Why an attempt got no answer
+enum AttemptError {
+    Http(reqwest::Error),
+    NoAnswer, // nothing within the attempt timeout
+}
+
+impl AttemptError {
+    fn is_timeout(&self) -> bool {
+        match self {
+            AttemptError::Http(e) => e.is_timeout(),
+            AttemptError::NoAnswer => true,
+        }
+    }
+}
+
+impl std::fmt::Display for AttemptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AttemptError::Http(e) => e.fmt(f),
+            AttemptError::NoAnswer => f.write_str("no answer"),
+        }
+    }
+}
+
+// Wait asked by a 429 or 503, in seconds or as an HTTP date
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    let value = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?;
+    if let Ok(secs) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+        .to_std()
+        .ok()
+        .or(Some(Duration::ZERO))
+}
+
+// Whether a 401 is for the timestamp, which the server marks with its own time.
+// The header is not authenticated: it is only a hint to measure the clock again
+fn timestamp_rejected(response: &reqwest::Response) -> bool {
+    response.status() == reqwest::StatusCode::UNAUTHORIZED
+        && response.headers().contains_key("X-Server-Time")
+}
+
+// Bytes in a `bytes start-end/size` Content-Range header
+fn content_range_length(value: &str) -> Option<usize> {
+    let (range, _) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let (start, end) = range.split_once('-')?;
+    let start: usize = start.trim().parse().ok()?;
+    let end: usize = end.trim().parse().ok()?;
+    end.checked_sub(start).map(|span| span + 1)
+}
+
+// Lifetime of the entries of a cache keyed by path: a matching cache rule replaces
+// both `ttl` and `tti`
+struct PolicyExpiry {
+    policy: Arc<CachePolicy>,
+    ttl: Duration,
+    tti: Option<Duration>,
+}
+
+impl<V> Expiry<OsString, V> for PolicyExpiry {
+    fn expire_after_create(
+        &self,
+        key: &OsString,
+        _value: &V,
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        Some(self.policy.rule_ttl(key).unwrap_or(self.ttl))
+    }
+
+    fn expire_after_update(
+        &self,
+        key: &OsString,
+        value: &V,
+        updated_at: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        self.expire_after_create(key, value, updated_at)
+    }
+
+    fn expire_after_read(
+        &self,
+        key: &OsString,
+        _value: &V,
+        read_at: Instant,
+        duration_until_expiry: Option<Duration>,
+        last_modified_at: Instant,
+    ) -> Option<Duration> {
+        match (self.policy.rule_ttl(key), self.tti) {
+            (None, Some(tti)) => {
+                let lived = read_at.saturating_duration_since(last_modified_at);
+                Some(tti.min(self.ttl.saturating_sub(lived)))
+            }
+            _ => duration_until_expiry,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -58,15 +232,17 @@ struct BitmapReadBuf {
     filled: u64,
     data: Vec<u8>,
     bitmap: Vec<u64>,
+    checksums: Option<Arc<FileChecksums>>, // blocks are checked against them on download
 }
 
-fn new_bitmap_buf(size: u64) -> BitmapReadBuf {
-    let bits = ((size + 63) / 64) as usize;
+fn new_bitmap_buf(size: u64, checksums: Option<Arc<FileChecksums>>) -> BitmapReadBuf {
+    let bits = size.div_ceil(64) as usize;
     BitmapReadBuf {
         size,
         filled: 0,
         data: vec![0u8; size as usize],
         bitmap: vec![0u64; bits],
+        checksums,
     }
 }
 
@@ -106,27 +282,93 @@ fn range_present(bm: &BitmapReadBuf, start: u64, len: u64) -> bool {
     true
 }
 
-fn mark_bytes(bm: &mut BitmapReadBuf, start: u64, bytes: &[u8]) {
-    let mut idx = start;
-    for &b in bytes {
-        if idx >= bm.size {
-            break;
+fn clear_bytes(bm: &mut BitmapReadBuf, start: u64, len: u64) {
+    let end = (start + len).min(bm.size);
+    for idx in start..end {
+        let word = (idx / 64) as usize;
+        let mask = 1u64 << (idx % 64);
+        if (bm.bitmap[word] & mask) != 0 {
+            bm.bitmap[word] &= !mask;
+            bm.filled -= 1;
         }
+    }
+}
+
+fn mark_zeroes(bm: &mut BitmapReadBuf, start: u64, len: u64) {
+    let end = (start + len).min(bm.size);
+    for idx in start..end {
+        bm.data[idx as usize] = 0;
+        set_bit(bm, idx);
+    }
+}
 
+fn mark_bytes(bm: &mut BitmapReadBuf, start: u64, bytes: &[u8]) {
+    for (idx, &b) in (start..bm.size).zip(bytes) {
         bm.data[idx as usize] = b;
         set_bit(bm, idx);
-        idx += 1;
     }
 }
 
+// Routes the requests through the configured proxy. Without one the HTTP client
+// follows HTTPS_PROXY, HTTP_PROXY and NO_PROXY, unless told to ignore them. The
+// proxy only relays the requests: their signature is the same either way
+fn with_proxy(
+    builder: reqwest::ClientBuilder,
+    policy: &ProxyPolicy,
+) -> Result<reqwest::ClientBuilder, ClientError> {
+    let Some(url) = &policy.url else {
+        return Ok(if policy.use_env {
+            builder
+        } else {
+            builder.no_proxy()
+        });
+    };
+
+    let mut proxy = reqwest::Proxy::all(url.as_str())
+        .map_err(|e| ClientError::Proxy(format!("`{}`: {}", url, e)))?;
+    if let Some(username) = &policy.username {
+        proxy = proxy.basic_auth(username, policy.password.as_deref().unwrap_or(""));
+    }
+    let no_proxy = if !policy.no_proxy.is_empty() {
+        reqwest::NoProxy::from_string(&policy.no_proxy.join(","))
+    } else if policy.use_env {
+        reqwest::NoProxy::from_env()
+    } else {
+        None
+    };
+    Ok(builder.proxy(proxy.no_proxy(no_proxy)))
+}
+
 impl RemoteClient {
-    pub fn new(config: &Config, user_keys: Option<UserKeys>) -> Self {
-        let http_client = reqwest::Client::builder()
+    pub fn new(config: &Config, user_keys: Option<UserKeys>) -> Result<Self, ClientError> {
+        let connection = &config.connection;
+        // a zero duration turns the corresponding timer off
+        let enabled = |d: Duration| (!d.is_zero()).then_some(d);
+        let mut builder = reqwest::Client::builder()
             .timeout(config.timeout)
-            .build()
-            .expect("Failed to create HTTP client");
+            .connect_timeout(config.health.connect_timeout)
+            .pool_max_idle_per_host(connection.pool_max_idle_per_host)
+            .pool_idle_timeout(enabled(connection.pool_idle_timeout))
+            .tcp_keepalive(enabled(connection.tcp_keepalive))
+            .tcp_nodelay(connection.tcp_nodelay)
+            .http2_keep_alive_interval(enabled(connection.http2_keepalive))
+            .http2_keep_alive_while_idle(true)
+            .http2_adaptive_window(true);
+        builder = match connection.http2 {
+            Http2Mode::Negotiate => builder,
+            Http2Mode::PriorKnowledge => builder.http2_prior_knowledge(),
+            Http2Mode::Off => builder.http1_only(),
+        };
+        builder = with_proxy(builder, &config.proxy)?;
+        if config.tls.is_custom() {
+            builder =
+                builder.use_preconfigured_tls(tls::client_config(&config.tls, connection.http2)?);
+        }
+        let http_client = builder.build()?;
 
-        Self {
+        let cache_policy = Arc::new(config.cache.clone());
+
+        Ok(Self {
             base_url: config.server_full_url(),
             http_client,
             user_keys: user_keys.unwrap_or(UserKeys {
@@ -136,20 +378,66 @@ impl RemoteClient {
             timeout: config.timeout,
             path_mounting: config.mount_point.to_string_lossy().to_string(),
             cache_metadata: MokaCache::builder()
-                .time_to_live(Duration::from_secs(3 * 60))
-                .time_to_idle(Duration::from_secs(3 * 60))
+                .expire_after(PolicyExpiry {
+                    policy: cache_policy.clone(),
+                    ttl: cache_policy.metadata_ttl,
+                    tti: Some(cache_policy.metadata_tti),
+                })
                 .build(),
             read_buf: MokaCache::builder()
-                .time_to_live(Duration::from_secs(3 * 60))
-                .max_capacity(512)
+                .expire_after(PolicyExpiry {
+                    policy: cache_policy.clone(),
+                    ttl: cache_policy.metadata_ttl,
+                    tti: None,
+                })
+                .max_capacity(cache_policy.read_buffer_entries)
                 .build(),
-        }
+            negative_lookup: MokaCache::builder()
+                .expire_after(PolicyExpiry {
+                    policy: cache_policy.clone(),
+                    ttl: cache_policy.negative_lookup_ttl,
+                    tti: None,
+                })
+                .max_capacity(16 * 1024)
+                .support_invalidation_closures()
+                .build(),
+            extents: MokaCache::builder()
+                .expire_after(PolicyExpiry {
+                    policy: cache_policy.clone(),
+                    ttl: cache_policy.metadata_ttl,
+                    tti: None,
+                })
+                .max_capacity(1024)
+                .build(),
+            cache_policy,
+            pin_store: None,
+            retry: config.retry.clone(),
+            health: HealthMonitor::new(config.health.clone()),
+            clock: ServerClock::new(),
+            strict_integrity: config.strict_integrity,
+            upload_encoding: Mutex::new(None),
+            server_signs: AtomicBool::new(false),
+            traffic: Arc::new(TrafficShaper::new(&config.traffic)),
+            connection: config.connection.clone(),
+        })
     }
 
-    fn build_path(&self, base: &str, extra: Option<&str>) -> String {
+    pub fn with_pin_store(mut self, pin_store: Arc<PinStore>) -> Self {
+        self.pin_store = Some(pin_store);
+        self
+    }
+
+    pub fn cache_policy(&self) -> &CachePolicy {
+        &self.cache_policy
+    }
+
+    // The path goes in its text form (see encode_path), percent-encoded once more
+    // for the URL: the server decodes both layers back to the exact bytes
+    fn build_path(&self, base: &str, extra: Option<&OsStr>) -> String {
         match extra {
             Some(p) if !p.is_empty() => {
-                let encoded = urlencoding::encode(p.trim_start_matches('/'));
+                let text = encode_path(p);
+                let encoded = urlencoding::encode(text.trim_start_matches('/'));
                 format!("{}/{}", base.trim_end_matches('/'), encoded)
             }
             _ => base.trim_end_matches('/').to_string(),
@@ -184,14 +472,391 @@ impl RemoteClient {
         }
     }
 
+    // Drops the negative entries of every child of `parent`, so that names created
+    // there become visible immediately instead of after the TTL
+    fn invalidate_negative_lookups(&self, parent: &OsStr) {
+        let parent = parent.to_os_string();
+        let _ = self
+            .negative_lookup
+            .invalidate_entries_if(move |path, _| get_parent_path(path) == parent);
+    }
+
+    pub fn server_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn server_state(&self) -> ServerState {
+        self.health.state()
+    }
+
+    // Sends the request made by `build` until an attempt gets an answer worth returning.
+    // Each attempt is built again, so it is signed again with a fresh nonce. While the
+    // server is offline nothing is sent and the request fails at once
+    async fn execute<F>(
+        &self,
+        tag: &str,
+        replay: Replay,
+        build: F,
+    ) -> Result<reqwest::Response, ClientError>
+    where
+        F: Fn() -> reqwest::RequestBuilder,
+    {
+        self.execute_within(tag, replay, None, build).await
+    }
+
+    // As execute, for a request that is part of an `operation` already admitted as a
+    // whole, in which case it takes no place of its own in the schedule
+    async fn execute_within<F>(
+        &self,
+        tag: &str,
+        replay: Replay,
+        operation: Option<&Slot>,
+        build: F,
+    ) -> Result<reqwest::Response, ClientError>
+    where
+        F: Fn() -> reqwest::RequestBuilder,
+    {
+        let attempt_timeout = self.retry.attempt_timeout.min(self.timeout);
+        let mut attempt = 1;
+        let mut resynced = false;
+        loop {
+            if self.health.state() == ServerState::Offline {
+                return Err(ClientError::Unavailable);
+            }
+            if self.clock.claim_sync() {
+                self.sync_clock().await;
+            }
+
+            // the nonce the answer has to be signed with, and the place of the request
+            // among the others, kept until its answer is read
+            let (nonce, slot, outcome) = match build().build() {
+                Ok(request) => {
+                    let nonce = request
+                        .headers()
+                        .get("X-Nonce")
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string);
+                    let slot = match operation {
+                        Some(_) => None,
+                        None => Some(self.traffic.admit(&request).await),
+                    };
+                    // a hung server is given up on well before the whole timeout
+                    let outcome = match tokio::time::timeout(
+                        attempt_timeout,
+                        self.http_client.execute(request),
+                    )
+                    .await
+                    {
+                        Ok(outcome) => outcome.map_err(AttemptError::Http),
+                        Err(_) => Err(AttemptError::NoAnswer),
+                    };
+                    (nonce, slot, outcome)
+                }
+                Err(e) => (None, None, Err(AttemptError::Http(e))),
+            };
+            let last = attempt >= self.retry.max_attempts;
+
+            // rejected for the timestamp: the offset is measured again through
+            // GET /time and the request signed again, once
+            if let Ok(response) = &outcome {
+                if !resynced && timestamp_rejected(response) {
+                    log::warn!(
+                        "⚠️ [{}] Timestamp rejected, retrying after a clock sync",
+                        tag
+                    );
+                    self.sync_clock().await;
+                    resynced = true;
+                    continue;
+                }
+            }
+
+            let wait = match &outcome {
+                _ if last => None,
+                // the server turned the request away: safe to send again either way
+                Ok(response) if matches!(response.status().as_u16(), 429 | 503) => {
+                    match retry_after(response) {
+                        Some(wait) if wait > self.retry.max_delay => None,
+                        Some(wait) => Some(wait),
+                        None => Some(self.retry.backoff(attempt)),
+                    }
+                }
+                Ok(response) if matches!(response.status().as_u16(), 502 | 504) => {
+                    (replay == Replay::Safe).then(|| self.retry.backoff(attempt))
+                }
+                Ok(_) => None,
+                // never connected: the request cannot have been acted on
+                Err(AttemptError::Http(e)) if e.is_connect() => Some(self.retry.backoff(attempt)),
+                // a server that keeps timing out is not waited for again and again:
+                // once unanswered requests pile up the breaker takes over
+                Err(e) if e.is_timeout() => (replay == Replay::Safe && !self.health.failing())
+                    .then(|| self.retry.backoff(attempt)),
+                Err(AttemptError::Http(e)) if e.is_request() => {
+                    (replay == Replay::Safe).then(|| self.retry.backoff(attempt))
+                }
+                Err(_) => None,
+            };
+
+            match wait {
+                None => {
+                    // a gateway error means the server behind it did not answer either
+                    match &outcome {
+                        Ok(response) if !matches!(response.status().as_u16(), 502..=504) => {
+                            self.health.record_success()
+                        }
+                        _ => self
+                            .health
+                            .record_failure(&self.http_client, self.build_url("/")),
+                    }
+                    return match (outcome, nonce) {
+                        (Ok(response), Some(nonce)) => {
+                            self.verify_response(tag, response, &nonce, slot).await
+                        }
+                        (Ok(response), None) => Ok(response),
+                        (Err(AttemptError::Http(e)), _) => Err(ClientError::Http(e)),
+                        (Err(AttemptError::NoAnswer), _) => {
+                            Err(ClientError::Timeout(attempt_timeout))
+                        }
+                    };
+                }
+                Some(wait) => {
+                    // every attempt left without answer counts, not just the last one
+                    if matches!(&outcome, Err(e) if e.is_timeout()) {
+                        self.health
+                            .record_failure(&self.http_client, self.build_url("/"));
+                    }
+                    match &outcome {
+                        Ok(response) => log::warn!(
+                            "⚠️ [{}] Attempt {} answered {}, retrying in {:?}",
+                            tag,
+                            attempt,
+                            response.status(),
+                            wait
+                        ),
+                        Err(e) => log::warn!(
+                            "⚠️ [{}] Attempt {} failed: {}, retrying in {:?}",
+                            tag,
+                            attempt,
+                            e,
+                            wait
+                        ),
+                    }
+                    drop(slot);
+                    tokio::time::sleep(wait).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    // Checks the signature of the answer to a signed request, before anything in it
+    // gets cached or returned. Bodies the server signs whole are read, their
+    // Content-Encoding undone and their digest checked; streamed ones, whose content
+    // is covered only by the checksums of the file when it has them, are handed on as
+    // they arrive, and refused in strict mode. A wrong signature is always rejected, a
+    // missing one in strict mode and, once the server has been seen signing, on any
+    // answer but a 401 (servers older than response signing sign nothing, failed
+    // authentications neither)
+    async fn verify_response(
+        &self,
+        tag: &str,
+        response: reqwest::Response,
+        nonce: &str,
+        slot: Option<Slot>,
+    ) -> Result<reqwest::Response, ClientError> {
+        let status = response.status();
+        let version = response.version();
+        let mut headers = response.headers().clone();
+
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+        let Some(signature) = header("X-Response-Signature").map(str::to_string) else {
+            if self.strict_integrity
+                || (status != reqwest::StatusCode::UNAUTHORIZED
+                    && self.server_signs.load(Ordering::Acquire))
+            {
+                eprintln!(
+                    "❌ [{}] Unsigned response ({}), answer discarded",
+                    tag, status
+                );
+                return Err(ClientError::Integrity("unsigned response".to_string()));
+            }
+            log::debug!("[{}] Unsigned response ({})", tag, status);
+            return Ok(self.pass_through(response, slot));
+        };
+        let next_cursor = header("X-Next-Cursor").map(str::to_string);
+        let content_sha256 = header("X-Content-Sha256").map(str::to_string);
+        let written_sha256 = header("X-Written-Sha256").map(str::to_string);
+        let accepted = header("Accept-Encoding").map(str::to_string);
+        let signed_headers = [
+            next_cursor.as_deref(),
+            content_sha256.as_deref(),
+            written_sha256.as_deref(),
+            accepted.as_deref(),
+        ];
+
+        if header("X-Response-Body") == Some(STREAMED_BODY) {
+            if self.strict_integrity {
+                eprintln!(
+                    "❌ [{}] Streamed response in strict mode, answer discarded",
+                    tag
+                );
+                return Err(ClientError::Integrity("streamed response".to_string()));
+            }
+            self.check_signature(tag, status, nonce, None, &signed_headers, &signature)?;
+            self.negotiate_upload_encoding(accepted.as_deref());
+            return Ok(self.pass_through(response, slot));
+        }
+
+        // the server holds back no more than this, so neither is anything more read
+        let mut body = Vec::new();
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            self.traffic.downloaded(chunk.len() as u64);
+            if body.len() + chunk.len() > SIGNED_BODY_LIMIT {
+                eprintln!("❌ [{}] Signed response too large, answer discarded", tag);
+                return Err(ClientError::Integrity(
+                    "signed response too large".to_string(),
+                ));
+            }
+            body.extend_from_slice(&chunk);
+        }
+        drop(slot);
+
+        // the signature is over the body as it was before the compression, which is
+        // no larger than what the server signs whole nor than the range sent
+        if let Some(value) = header("Content-Encoding") {
+            let max_len = header("Content-Range")
+                .and_then(content_range_length)
+                .map_or(SIGNED_BODY_LIMIT, |len| len.min(SIGNED_BODY_LIMIT));
+            let decoded = match ContentEncoding::parse(value) {
+                Some(encoding) => compression::decompress(encoding, &body, max_len),
+                None => Err(std::io::Error::other(format!("unknown encoding {}", value))),
+            };
+            match decoded {
+                Ok(decoded) => body = decoded,
+                Err(e) => {
+                    eprintln!("❌ [{}] Cannot decode the response: {}", tag, e);
+                    return Err(ClientError::Integrity(format!(
+                        "undecodable response: {}",
+                        e
+                    )));
+                }
+            }
+            headers.remove(reqwest::header::CONTENT_ENCODING);
+            headers.insert(reqwest::header::CONTENT_LENGTH, body.len().into());
+        }
+        self.check_signature(tag, status, nonce, Some(&body), &signed_headers, &signature)?;
+        self.negotiate_upload_encoding(accepted.as_deref());
+
+        let mut verified = http::Response::new(body);
+        *verified.status_mut() = status;
+        *verified.version_mut() = version;
+        *verified.headers_mut() = headers;
+        Ok(reqwest::Response::from(verified))
+    }
+
+    fn check_signature(
+        &self,
+        tag: &str,
+        status: reqwest::StatusCode,
+        nonce: &str,
+        body: Option<&[u8]>,
+        signed_headers: &[Option<&str>],
+        signature: &str,
+    ) -> Result<(), ClientError> {
+        if !self
+            .user_keys
+            .verify_response(status.as_u16(), nonce, body, signed_headers, signature)
+        {
+            eprintln!("❌ [{}] Response signature mismatch, answer discarded", tag);
+            return Err(ClientError::Integrity(
+                "response signature mismatch".to_string(),
+            ));
+        }
+        self.server_signs.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    // The encodings the server can decode, as listed in a signed answer
+    fn negotiate_upload_encoding(&self, accepted: Option<&str>) {
+        if let Some(accepted) = accepted {
+            *self.upload_encoding.lock() = ContentEncoding::negotiate(accepted);
+        }
+    }
+
+    // Largest range asked for at once: in strict mode answers have to be signed
+    // whole, which the server does up to SIGNED_BODY_LIMIT
+    fn max_range(&self, span: u64) -> u64 {
+        if self.strict_integrity {
+            span.min(SIGNED_BODY_LIMIT as u64)
+        } else {
+            span
+        }
+    }
+
+    // The answer as it arrives, counted against the download limit as it is read and
+    // keeping its place in the schedule until then
+    fn pass_through(&self, response: reqwest::Response, slot: Option<Slot>) -> reqwest::Response {
+        let status = response.status();
+        let version = response.version();
+        let headers = response.headers().clone();
+        let traffic = self.traffic.clone();
+        let body = response.bytes_stream().inspect(move |chunk| {
+            let _slot = &slot;
+            if let Ok(chunk) = chunk {
+                traffic.downloaded(chunk.len() as u64);
+            }
+        });
+
+        let mut passed = http::Response::new(reqwest::Body::wrap_stream(body));
+        *passed.status_mut() = status;
+        *passed.version_mut() = version;
+        *passed.headers_mut() = headers;
+        reqwest::Response::from(passed)
+    }
+
+    // Measures the offset of the server clock through GET /time, or the Date header
+    // of its answer (second precision) for servers without it
+    async fn sync_clock(&self) {
+        let sent_ms = UserKeys::generate_timestamp();
+        let response = match self.http_client.get(self.build_url("/time")).send().await {
+            Ok(response) => response,
+            Err(e) => {
+                log::debug!("[CLOCK] Sync failed: {}", e);
+                return;
+            }
+        };
+        let received_ms = UserKeys::generate_timestamp();
+
+        let date_ms = response
+            .headers()
+            .get(reqwest::header::DATE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| chrono::DateTime::parse_from_rfc2822(v).ok())
+            .map(|date| date.timestamp_millis());
+        let server_ms = match response.json::<ServerTime>().await {
+            Ok(body) => Some(body.time),
+            Err(_) => date_ms,
+        };
+
+        if let Some(server_ms) = server_ms {
+            self.clock.record(server_ms, sent_ms, received_ms);
+        }
+    }
+
+    // Headers of a signed request. The Range and Idempotency-Key headers, when given,
+    // are set here as well: they are signed after the timestamp and the nonce, in this
+    // order, as the server rebuilds the message
     fn get_headers(
         &self,
         method: &str,
         route_path: &str,
-        extra_header: Option<&str>,
+        range: Option<&str>,
+        idempotency_key: Option<&str>,
         extra_to_be_hashed: Option<Vec<ExtraItem>>,
     ) -> HeaderMap {
-        let timestamp = UserKeys::generate_timestamp().to_string();
+        let timestamp = self.clock.timestamp().to_string();
         let nonce = UserKeys::generate_nonce();
 
         let hmac_message = self.user_keys.build_hmac_message(
@@ -199,25 +864,41 @@ impl RemoteClient {
             route_path,
             {
                 let mut v: Vec<&str> = vec![&timestamp, &nonce];
-                if let Some(extra) = extra_header {
-                    v.push(extra);
-                }
+                v.extend(range);
+                v.extend(idempotency_key);
                 v
             },
             extra_to_be_hashed,
         );
 
-        let final_headers: HeaderMap =
+        let mut final_headers: HeaderMap =
             self.user_keys
                 .get_auth_headers(&hmac_message, &timestamp.to_string(), &nonce);
+        if let Some(range) = range {
+            final_headers.insert(
+                reqwest::header::RANGE,
+                range.parse().expect("Invalid Range header"),
+            );
+        }
+        if let Some(key) = idempotency_key {
+            final_headers.insert(
+                "Idempotency-Key",
+                key.parse().expect("Invalid idempotency key"),
+            );
+        }
+        // signed answers are compressed on request, and decoded in verify_response
+        final_headers.insert(
+            reqwest::header::ACCEPT_ENCODING,
+            reqwest::header::HeaderValue::from_static(compression::ACCEPT_ENCODING),
+        );
         final_headers
     }
 
-    pub async fn get_file_metadata(&self, path: &str) -> Result<MetaFile, ClientError> {
+    pub async fn get_file_metadata(&self, path: &OsStr) -> Result<MetaFile, ClientError> {
         if path == "/" {
             let now_iso = chrono::Utc::now().to_rfc3339();
             return Ok(MetaFile {
-                name: "/".to_string(),
+                name: OsString::from("/"),
                 size: 4096,
                 atime: now_iso.clone(),
                 mtime: now_iso.clone(),
@@ -227,50 +908,201 @@ impl RemoteClient {
                 perm: "755".to_string(),
                 nlink: 2,
                 ref_path: None,
+                flags: 0,
+                rdev: 0,
+                sha256: None,
+                has_checksums: false,
             });
         }
 
-        let parent_path = get_parent_path(path);
-        let file_name = get_file_name(path);
+        // paths excluded from caching always get a fresh parent listing
+        let cached = self.cache_policy.is_cached(path);
 
-        let parent_listing = self.list_directory(&parent_path).await?;
+        if cached && self.negative_lookup.contains_key(path) {
+            return Err(ClientError::NotFound {
+                path: path.to_string_lossy().into_owned(),
+            });
+        }
 
-        if let Some(found_file) = parent_listing.files.iter().find(|f| f.name == file_name) {
-            let mut result = found_file.clone();
-            result.name = path.to_string();
-            return Ok(result);
+        // a parent listed already answers for its children, anything else is asked
+        // for alone instead of listing the whole parent
+        if cached {
+            let parent_path = get_parent_path(path);
+            if let Some(parent_listing) = self.cache_metadata.get(&parent_path) {
+                let file_name = get_file_name(path);
+                if let Some(found_file) = parent_listing.files.iter().find(|f| f.name == file_name)
+                {
+                    let mut result = found_file.clone();
+                    result.name = path.to_os_string();
+                    return Ok(result);
+                }
+                self.negative_lookup.insert(path.to_os_string(), ());
+                return Err(ClientError::NotFound {
+                    path: path.to_string_lossy().into_owned(),
+                });
+            }
         }
 
-        Err(ClientError::NotFound {
-            path: path.to_string(),
-        })
+        match self.stat_entry(path).await {
+            Ok(mut result) => {
+                result.name = path.to_os_string();
+                Ok(result)
+            }
+            Err(e @ ClientError::NotFound { .. }) => {
+                if cached {
+                    self.negative_lookup.insert(path.to_os_string(), ());
+                }
+                Err(e)
+            }
+            Err(
+                e @ (ClientError::Http(_) | ClientError::Timeout(_) | ClientError::Unavailable),
+            ) => {
+                // server unreachable: pinned entries are still known
+                match self.pin_store.as_ref().and_then(|pins| pins.metadata(path)) {
+                    Some(metadata) => Ok(metadata),
+                    None => Err(e),
+                }
+            }
+            Err(e) => Err(e),
+        }
     }
 
-    pub async fn list_directory(&self, path: &str) -> Result<DirectoryListing, ClientError> {
-        let route_path = self.build_path("/list", Some(path));
+    // Metadata of the entry at `path` alone, without listing its parent
+    pub async fn stat_entry(&self, path: &OsStr) -> Result<MetaFile, ClientError> {
+        let route_path = self.build_path("/stat", Some(path));
         let url = self.build_url(&route_path);
 
-        let headers = self.get_headers("GET", &route_path, None, None);
+        let response = self
+            .execute("STAT", Replay::Safe, || {
+                self.http_client
+                    .get(&url)
+                    .headers(self.get_headers("GET", &route_path, None, None, None))
+                    .timeout(self.timeout)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let message = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(match status {
+                404 => ClientError::NotFound {
+                    path: path.to_string_lossy().into_owned(),
+                },
+                _ => self.map_http_error(status, message),
+            });
+        }
+
+        Ok(response.json::<MetaFile>().await?)
+    }
+
+    pub async fn list_directory(&self, path: &OsStr) -> Result<DirectoryListing, ClientError> {
+        if let Some(cached_response) = self.cache_metadata.get(path) {
+            return Ok(cached_response.clone());
+        }
+
+        let directory_listing = match self.fetch_directory(path).await {
+            Ok(listing) => listing,
+            Err(
+                e @ (ClientError::Http(_) | ClientError::Timeout(_) | ClientError::Unavailable),
+            ) => {
+                return match self.pin_store.as_ref().and_then(|pins| pins.listing(path)) {
+                    Some(listing) => Ok(listing),
+                    None => Err(e),
+                };
+            }
+            Err(e) => return Err(e),
+        };
+
+        if self.cache_policy.is_cached(path) {
+            self.cache_metadata
+                .insert(path.to_os_string(), directory_listing.clone());
+        }
+
+        Ok(directory_listing)
+    }
+
+    // Whole listing of `path` straight from the server, one page after the other
+    pub async fn fetch_directory(&self, path: &OsStr) -> Result<DirectoryListing, ClientError> {
+        let mut files = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let page = match self
+                .list_directory_page(path, cursor.as_deref(), LIST_PAGE_SIZE)
+                .await
+            {
+                Ok(page) => page,
+                // the server closed the listing (left unused, or for newer ones of
+                // the same user): it starts over
+                Err(ClientError::CursorExpired) if cursor.is_some() => {
+                    log::warn!(
+                        "⚠️ [LIST_DIR] Cursor expired, listing {} again",
+                        path.to_string_lossy()
+                    );
+                    files.clear();
+                    cursor = None;
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            files.extend(page.files);
 
-        match self.cache_metadata.get(path) {
-            Some(cached_response) => {
-                return Ok(cached_response.clone());
+            match page.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
             }
-            None => {}
         }
 
+        Ok(DirectoryListing { files })
+    }
+
+    // Listings of many directories at once, fetched in parallel and returned in the
+    // order of `paths`
+    pub async fn fetch_directories(
+        &self,
+        paths: &[OsString],
+    ) -> Vec<Result<DirectoryListing, ClientError>> {
+        let requests: Vec<_> = paths
+            .iter()
+            .map(|path| self.fetch_directory(path))
+            .collect();
+        futures::stream::iter(requests)
+            .buffered(self.connection.parallel_requests)
+            .collect()
+            .await
+    }
+
+    // Fetches the entries of `path` that follow `cursor` (in the order the server
+    // reads them), decoding them while the body is still being received
+    pub async fn list_directory_page(
+        &self,
+        path: &OsStr,
+        cursor: Option<&str>,
+        limit: usize,
+    ) -> Result<ListingPage, ClientError> {
+        // the query string is part of the signed path; cursors are base64url, no encoding needed
+        let mut route_path = format!("{}?limit={}", self.build_path("/list", Some(path)), limit);
+        if let Some(cursor) = cursor {
+            route_path.push_str(&format!("&cursor={}", cursor));
+        }
+        let url = self.build_url(&route_path);
+
         let response = match self
-            .http_client
-            .get(&url)
-            .headers(headers)
-            .timeout(self.timeout)
-            .send()
+            .execute("LIST_DIR", Replay::Safe, || {
+                self.http_client
+                    .get(&url)
+                    .headers(self.get_headers("GET", &route_path, None, None, None))
+                    .timeout(self.timeout)
+            })
             .await
         {
             Ok(r) => r,
             Err(e) => {
                 eprintln!("❌ [LIST_DIR] Error on sending request: {}", e);
-                return Err(ClientError::Http(e));
+                return Err(e);
             }
         };
 
@@ -283,8 +1115,9 @@ impl RemoteClient {
 
             return Err(match status_code {
                 404 => ClientError::NotFound {
-                    path: path.to_string(),
+                    path: path.to_string_lossy().into_owned(),
                 },
+                410 => ClientError::CursorExpired,
                 403 | 401 => ClientError::PermissionDenied(message),
                 _ => ClientError::Server {
                     status: status_code,
@@ -293,29 +1126,47 @@ impl RemoteClient {
             });
         }
 
-        let files: Vec<MetaFile> = match response.json::<Vec<MetaFile>>().await {
-            Ok(f) => f,
-            Err(e) => {
-                return Err(ClientError::Http(e));
-            }
-        };
-
-        let directory_listing = DirectoryListing { files };
-
-        self.cache_metadata
-            .insert(path.to_string(), directory_listing.clone());
+        let next_cursor = response
+            .headers()
+            .get("X-Next-Cursor")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+
+        let mut decoder = JsonArrayDecoder::new();
+        let mut files: Vec<MetaFile> = Vec::new();
+        let mut body = response.bytes_stream();
+
+        while let Some(chunk) = body.next().await {
+            let chunk = match chunk {
+                Ok(c) => c,
+                Err(e) => {
+                    eprintln!("❌ [LIST_DIR] Error on receiving listing: {}", e);
+                    return Err(ClientError::Http(e));
+                }
+            };
+            files.extend(decoder.push::<MetaFile>(&chunk)?);
+        }
+        decoder.finish()?;
 
-        Ok(directory_listing)
+        Ok(ListingPage { files, next_cursor })
     }
 
     pub async fn read_file(
         &self,
-        path: &str,
+        path: &OsStr,
         offset: Option<u64>,
         size: Option<u64>,
     ) -> Result<FileContent, ClientError> {
         let off = offset.unwrap_or(0);
-        let want = size.unwrap_or(READ_PREFETCH);
+        let want = size.unwrap_or(self.cache_policy.read_prefetch);
+
+        if let Some(data) = self
+            .pin_store
+            .as_ref()
+            .and_then(|pins| pins.read(path, off, want))
+        {
+            return Ok(FileContent { data });
+        }
 
         let arc_buf = match self.read_buf.get(path) {
             Some(b) => b,
@@ -324,53 +1175,113 @@ impl RemoteClient {
                 if off >= meta.size {
                     return Ok(FileContent { data: Vec::new() });
                 }
-                let nb = Arc::new(Mutex::new(new_bitmap_buf(meta.size)));
-                self.read_buf.insert(path.to_string(), nb.clone());
+                // blocks are checked whenever the server keeps their hashes, also
+                // after a partial write left the digest of the whole file unknown
+                let checksums = if meta.sha256.is_some() || meta.has_checksums {
+                    Some(self.get_checksums(path, true).await?)
+                        .filter(|checksums| checksums.size == meta.size)
+                } else {
+                    None
+                };
+                let nb = Arc::new(Mutex::new(new_bitmap_buf(meta.size, checksums)));
+                if self.cache_policy.is_cached(path) {
+                    self.read_buf.insert(path.to_os_string(), nb.clone());
+                }
                 nb
             }
         };
 
-        // Primo lock
-        let buf = arc_buf.lock();
+        // Primo lock, rilasciato prima dell'I/O
+        let (effective_want, checksums, base, span, data_ranges, align) = {
+            let buf = arc_buf.lock();
 
-        if off >= buf.size {
-            return Ok(FileContent { data: Vec::new() });
-        }
-        let effective_want = want.min(buf.size - off);
-
-        // Tutto già presente
-        if range_present(&buf, off, effective_want) {
-            let end = off + effective_want;
-            return Ok(FileContent {
-                data: buf.data[off as usize..end as usize].to_vec(),
-            });
-        }
+            if off >= buf.size {
+                return Ok(FileContent { data: Vec::new() });
+            }
+            let effective_want = want.min(buf.size - off);
+
+            // Tutto già presente
+            if range_present(&buf, off, effective_want) {
+                let end = off + effective_want;
+                return Ok(FileContent {
+                    data: buf.data[off as usize..end as usize].to_vec(),
+                });
+            }
 
-        // Trova primo buco
-        let mut first_missing = off;
-        let end_req = off + effective_want;
-        while first_missing < end_req {
-            if !bit_is_set(&buf, first_missing) {
-                break;
+            // Trova primo buco
+            let mut first_missing = off;
+            let end_req = off + effective_want;
+            while first_missing < end_req {
+                if !bit_is_set(&buf, first_missing) {
+                    break;
+                }
+                first_missing += 1;
             }
-            first_missing += 1;
-        }
 
-        // Calcola fetch
-        let base = align_down(first_missing, READ_ALIGN);
-        let target_span = std::cmp::max(READ_PREFETCH, effective_want);
-        let prefetch_to = (first_missing + target_span).min(buf.size);
-        let span = prefetch_to - base;
+            // Calcola fetch: con i checksum si scaricano blocchi interi, per verificarli
+            let checksums = buf.checksums.clone();
+            let align = match &checksums {
+                Some(checksums) => checksums.block_size.max(self.cache_policy.read_align),
+                None => self.cache_policy.read_align,
+            };
+            let base = align_down(first_missing, align);
+            let target_span = std::cmp::max(self.cache_policy.read_prefetch, effective_want);
+            let mut prefetch_to = first_missing + target_span;
+            if checksums.is_some() {
+                prefetch_to = align_down(prefetch_to + align - 1, align);
+            }
+            let prefetch_to = prefetch_to.min(buf.size);
+            let span = prefetch_to - base;
+
+            // Buchi noti: letti come zeri senza richiederli al server
+            let data_ranges = match self.extents.get(path) {
+                Some(extents) if extents.size == buf.size => extents.data_within(base, span),
+                _ => vec![Extent {
+                    offset: base,
+                    length: span,
+                }],
+            };
+
+            (effective_want, checksums, base, span, data_ranges, align)
+        };
 
-        // Rilascia lock prima dell'I/O
-        drop(buf);
-        let chunk = self.http_read_range(path, base, span).await?;
+        let piece = self.max_range(self.connection.parallel_chunk);
+        let data_ranges = split_ranges(&data_ranges, piece, align);
+        // what the caller asked for sets the priority, not the prefetch added to it
+        let priority = self.traffic.classify(effective_want);
+        let chunks = self.fetch_ranges(path, &data_ranges, priority).await?;
+
+        // Verifica dei blocchi prima che entrino nella cache di lettura
+        if let Some(checksums) = &checksums {
+            let mut fetched = vec![0u8; span as usize];
+            for (range, chunk) in data_ranges.iter().zip(&chunks) {
+                let start = (range.offset - base) as usize;
+                fetched[start..start + chunk.len()].copy_from_slice(chunk);
+            }
+            if let Some(block) = checksums.mismatch(base, &fetched) {
+                eprintln!(
+                    "❌ [READ] Checksum errato per il blocco {} di {:?}, cache scartata",
+                    block, path
+                );
+                self.read_buf.invalidate(path);
+                return Err(ClientError::Integrity(format!(
+                    "checksum mismatch in block {}",
+                    block
+                )));
+            }
+        }
 
         // Rilock e marca
         let mut buf = arc_buf.lock();
-        if !chunk.is_empty() {
-            mark_bytes(&mut buf, base, &chunk);
+        let mut pos = base;
+        for (range, chunk) in data_ranges.iter().zip(&chunks) {
+            mark_zeroes(&mut buf, pos, range.offset - pos);
+            if !chunk.is_empty() {
+                mark_bytes(&mut buf, range.offset, chunk);
+            }
+            pos = range.offset + range.length;
         }
+        mark_zeroes(&mut buf, pos, (base + span).saturating_sub(pos));
 
         // Ricostruisci risposta contigua disponibile
         let mut avail = 0u64;
@@ -387,27 +1298,56 @@ impl RemoteClient {
         })
     }
 
-    async fn http_read_range(
+    // Downloads the ranges with up to `parallel_requests` of them in flight, which
+    // share the connection under HTTP/2; the chunks come back in the order asked.
+    // They are scheduled together, with the priority of the read they serve
+    pub async fn fetch_ranges(
+        &self,
+        path: &OsStr,
+        ranges: &[Extent],
+        priority: Priority,
+    ) -> Result<Vec<Vec<u8>>, ClientError> {
+        let operation = self.traffic.admit_as(priority, 0).await;
+        let requests: Vec<_> = ranges
+            .iter()
+            .map(|range| self.read_range(path, range.offset, range.length, Some(&operation)))
+            .collect();
+        futures::stream::iter(requests)
+            .buffered(self.connection.parallel_requests)
+            .try_collect()
+            .await
+    }
+
+    pub async fn http_read_range(
         &self,
-        path: &str,
+        path: &OsStr,
         base: u64,
         span: u64,
+    ) -> Result<Vec<u8>, ClientError> {
+        self.read_range(path, base, self.max_range(span), None)
+            .await
+    }
+
+    async fn read_range(
+        &self,
+        path: &OsStr,
+        base: u64,
+        span: u64,
+        operation: Option<&Slot>,
     ) -> Result<Vec<u8>, ClientError> {
         let route_path = self.build_path("/files", Some(path));
         let url = self.build_url(&route_path);
 
         let range_value = format!("bytes={}-{}", base, base + span.saturating_sub(1));
-        let mut headers = self.get_headers("GET", &route_path, Some(&range_value), None);
-        headers.insert("Range", range_value.parse().expect("Invalid Range header"));
 
         let response = self
-            .http_client
-            .get(&url)
-            .headers(headers)
-            .timeout(self.timeout)
-            .send()
-            .await
-            .map_err(ClientError::Http)?;
+            .execute_within("READ_RANGE", Replay::Safe, operation, || {
+                self.http_client
+                    .get(&url)
+                    .headers(self.get_headers("GET", &route_path, Some(&range_value), None, None))
+                    .timeout(self.timeout)
+            })
+            .await?;
 
         let status = response.status().as_u16();
         if status == 206 || status == 200 {
@@ -424,25 +1364,108 @@ impl RemoteClient {
         }
     }
 
+    // Data ranges of the file at `path`, as scanned by the server
+    pub async fn get_extents(&self, path: &OsStr) -> Result<Arc<FileExtents>, ClientError> {
+        if let Some(extents) = self.extents.get(path) {
+            return Ok(extents);
+        }
+
+        let route_path = self.build_path("/extents", Some(path));
+        let url = self.build_url(&route_path);
+
+        let response = self
+            .execute("EXTENTS", Replay::Safe, || {
+                self.http_client
+                    .get(&url)
+                    .headers(self.get_headers("GET", &route_path, None, None, None))
+                    .timeout(self.timeout)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let message = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(self.map_http_error(status, message));
+        }
+
+        let extents = Arc::new(response.json::<FileExtents>().await?);
+        if self.cache_policy.is_cached(path) {
+            self.extents.insert(path.to_os_string(), extents.clone());
+        }
+        Ok(extents)
+    }
+
+    // Checksums of the file at `path`, computed by the server for what it does not
+    // know yet
+    // With `blocks_only` the server does not hash the whole file just for its digest,
+    // which is then None when not known already
+    pub async fn get_checksums(
+        &self,
+        path: &OsStr,
+        blocks_only: bool,
+    ) -> Result<Arc<FileChecksums>, ClientError> {
+        let mut route_path = self.build_path("/checksums", Some(path));
+        if blocks_only {
+            route_path.push_str("?blocksOnly=true");
+        }
+        let url = self.build_url(&route_path);
+
+        let response = self
+            .execute("CHECKSUMS", Replay::Safe, || {
+                self.http_client
+                    .get(&url)
+                    .headers(self.get_headers("GET", &route_path, None, None, None))
+                    .timeout(self.timeout)
+            })
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let message = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(self.map_http_error(status, message));
+        }
+
+        Ok(Arc::new(response.json::<FileChecksums>().await?))
+    }
+
     pub async fn write_file(&self, write_request: &WriteRequest) -> Result<(), ClientError> {
         self.cache_metadata
             .invalidate(&get_parent_path(&write_request.path));
         self.read_buf.invalidate(&write_request.path);
+        self.extents.invalidate(&write_request.path);
+        self.invalidate_negative_lookups(&get_parent_path(&write_request.path));
+        if let Some(pins) = &self.pin_store {
+            pins.invalidate(&write_request.path);
+        }
+        if let Some(ref new_path) = write_request.new_path {
+            self.cache_metadata.invalidate(&get_parent_path(new_path));
+            self.read_buf.invalidate(new_path);
+            self.extents.invalidate(new_path);
+            self.invalidate_negative_lookups(&get_parent_path(new_path));
+            // a moved directory brings its whole subtree into existence
+            let prefix = join_path(new_path, OsStr::new(""));
+            let _ = self.negative_lookup.invalidate_entries_if(move |path, _| {
+                path.as_bytes().starts_with(prefix.as_bytes())
+            });
+        }
 
         let route_path = self.build_path("/files", Some(&write_request.path));
         let url = self.build_url(&route_path);
 
-        match write_request.kind {
-            FileKind::Symlink | FileKind::Hardlink => {
-                if write_request.ref_path.is_none() {
-                    eprintln!("❌ [WRITE_FILE] refPath mancante per link");
-                    return Err(ClientError::Server {
-                        status: 400,
-                        message: "refPath required for link types".into(),
-                    });
-                }
-            }
-            _ => {}
+        if matches!(write_request.kind, FileKind::Symlink | FileKind::Hardlink)
+            && write_request.ref_path.is_none()
+        {
+            eprintln!("❌ [WRITE_FILE] refPath mancante per link");
+            return Err(ClientError::Server {
+                status: 400,
+                message: "refPath required for link types".into(),
+            });
         }
 
         let has_content = write_request
@@ -451,7 +1474,7 @@ impl RemoteClient {
             .map(|d| !d.is_empty())
             .unwrap_or(false);
         match write_request.mode {
-            Mode::Write => {
+            Mode::Write | Mode::Create => {
                 if has_content
                     && (write_request.size as usize) != write_request.data.as_ref().unwrap().len()
                 {
@@ -506,7 +1529,7 @@ impl RemoteClient {
 
         let effective_size: u64 = match write_request.mode {
             Mode::Truncate => write_request.size, // final requested size
-            Mode::Write | Mode::Append | Mode::WriteAt => {
+            Mode::Write | Mode::Append | Mode::WriteAt | Mode::Create => {
                 if has_content {
                     write_request.data.as_ref().unwrap().len() as u64
                 } else {
@@ -516,10 +1539,7 @@ impl RemoteClient {
         };
 
         let send_data: Vec<u8> = match write_request.kind {
-            FileKind::Symlink | FileKind::Hardlink => {
-                if has_content {}
-                Vec::new()
-            }
+            FileKind::Symlink | FileKind::Hardlink => Vec::new(),
             _ => write_request.data.clone().unwrap_or_default(),
         };
 
@@ -549,10 +1569,10 @@ impl RemoteClient {
         metadata_map.insert("mode".to_string(), json!(write_request.mode.to_string()));
 
         if let Some(ref new_path) = write_request.new_path {
-            metadata_map.insert("newPath".to_string(), json!(new_path));
+            metadata_map.insert("newPath".to_string(), json!(encode_path(new_path)));
         }
         if let Some(ref ref_path) = write_request.ref_path {
-            metadata_map.insert("refPath".to_string(), json!(ref_path));
+            metadata_map.insert("refPath".to_string(), json!(encode_path(ref_path)));
         }
         if let Some(ref offset) = write_request.offset {
             if matches!(write_request.mode, Mode::WriteAt) {
@@ -567,36 +1587,73 @@ impl RemoteClient {
 
         let include_content = !send_data.is_empty();
 
-        let extra_items = if include_content {
-            Some(vec![
-                ExtraItem::Text(&metadata_str),
-                ExtraItem::Bytes(&send_data),
-            ])
-        } else {
-            Some(vec![ExtraItem::Text(&metadata_str)])
+        // an append replayed with the same key is applied by the server only once;
+        // renames, links and exclusive creations would fail when replayed
+        let idempotency_key = match write_request.mode {
+            Mode::Append => Some(UserKeys::generate_nonce()),
+            _ => None,
+        };
+        let replay = match (write_request.kind, &write_request.mode) {
+            _ if write_request.new_path.is_some() => Replay::Unsafe,
+            (FileKind::Symlink | FileKind::Hardlink, _) | (_, Mode::Create) => Replay::Unsafe,
+            _ => Replay::Safe,
+        };
+        // the content travels compressed when the server takes it and it shrinks; the
+        // signature stays over the original bytes
+        let encoding = *self.upload_encoding.lock();
+        let compressed = match encoding {
+            Some(encoding)
+                if include_content && !compression::is_compressed_media(&write_request.path) =>
+            {
+                compression::compress(encoding, &send_data).map(|data| (encoding, data))
+            }
+            _ => None,
         };
-        let mut headers = self.get_headers("PUT", &route_path, None, extra_items);
-        headers.remove(reqwest::header::CONTENT_TYPE);
-
-        let mut form = reqwest::multipart::Form::new().text("metadata", metadata_str.clone());
-        if include_content {
-            form = form.part(
-                "content",
-                reqwest::multipart::Part::bytes(send_data)
-                    .file_name("file")
-                    .mime_str("application/octet-stream")
-                    .map_err(ClientError::Http)?,
-            );
-        }
 
         let response = self
-            .http_client
-            .put(&url)
-            .headers(headers)
-            .multipart(form)
-            .send()
-            .await
-            .map_err(ClientError::Http)?;
+            .execute("WRITE_FILE", replay, || {
+                let extra_items = if include_content {
+                    Some(vec![
+                        ExtraItem::Text(&metadata_str),
+                        ExtraItem::Bytes(&send_data),
+                    ])
+                } else {
+                    Some(vec![ExtraItem::Text(&metadata_str)])
+                };
+                let mut headers = self.get_headers(
+                    "PUT",
+                    &route_path,
+                    None,
+                    idempotency_key.as_deref(),
+                    extra_items,
+                );
+                headers.remove(reqwest::header::CONTENT_TYPE);
+
+                let mut form =
+                    reqwest::multipart::Form::new().text("metadata", metadata_str.clone());
+                if include_content {
+                    let part = match &compressed {
+                        Some((encoding, data)) => {
+                            let mut part_headers = HeaderMap::new();
+                            part_headers.insert(
+                                reqwest::header::CONTENT_ENCODING,
+                                reqwest::header::HeaderValue::from_static(encoding.as_str()),
+                            );
+                            reqwest::multipart::Part::bytes(data.clone()).headers(part_headers)
+                        }
+                        None => reqwest::multipart::Part::bytes(send_data.clone()),
+                    };
+                    form = form.part(
+                        "content",
+                        part.file_name("file")
+                            .mime_str("application/octet-stream")
+                            .expect("Invalid content type"),
+                    );
+                }
+
+                self.http_client.put(&url).headers(headers).multipart(form)
+            })
+            .await?;
 
         let status_code = response.status().as_u16();
 
@@ -611,20 +1668,13 @@ impl RemoteClient {
                 status_code, error_body
             );
 
-            if status_code == 400 || status_code == 404 {
-                if let Some(data) = &write_request.data {
-                    if data.len() <= 100 {}
-                } else {
-                }
-            }
-
             return Err(match status_code {
                 400 => ClientError::Server {
                     status: status_code,
                     message: format!("Bad Request: {}", error_body),
                 },
                 404 => ClientError::NotFound {
-                    path: write_request.path.clone(),
+                    path: write_request.path.to_string_lossy().into_owned(),
                 },
                 401 | 403 => ClientError::PermissionDenied(error_body),
                 409 => ClientError::Server {
@@ -638,37 +1688,272 @@ impl RemoteClient {
             });
         }
 
+        // the digest the server computed on what it stored has to match what was sent:
+        // that of the whole file when the request carried all of it, that of the bytes
+        // where it wrote for writes in place and appends
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        let stored_sha256 = match write_request.mode {
+            Mode::Write | Mode::Create => header("X-Content-Sha256"),
+            Mode::WriteAt | Mode::Append => header("X-Written-Sha256"),
+            Mode::Truncate => None,
+        };
+        if let Some(stored) = stored_sha256 {
+            if stored != format!("{:x}", Sha256::digest(&send_data)) {
+                eprintln!(
+                    "❌ [WRITE_FILE] Il server ha memorizzato un contenuto diverso da quello inviato"
+                );
+                return Err(ClientError::Integrity(
+                    "stored content does not match the written one".to_string(),
+                ));
+            }
+        }
+
         self.handle_empty_response(response).await
     }
 
-    pub async fn create_directory(&self, path: &str) -> Result<(), ClientError> {
+    pub async fn create_directory(
+        &self,
+        create_request: &CreateDirectoryRequest,
+    ) -> Result<(), ClientError> {
+        let path = create_request.path.as_os_str();
         let route_path = self.build_path("/mkdir", Some(path));
         let url = self.build_url(&route_path);
 
-        self.cache_metadata.invalidate(&get_parent_path(&path)); //invalidate the father entries
+        self.cache_metadata.invalidate(&get_parent_path(path)); //invalidate the father entries
         self.read_buf.invalidate(path);
+        self.invalidate_negative_lookups(&get_parent_path(path));
 
-        let headers = self.get_headers("POST", &route_path, None, None);
+        let body = serde_json::to_string(create_request)?;
 
-        let response = self.http_client.post(&url).headers(headers).send().await?;
+        let response = self
+            .execute("MKDIR", Replay::Unsafe, || {
+                self.http_client
+                    .post(&url)
+                    .headers(self.get_headers(
+                        "POST",
+                        &route_path,
+                        None,
+                        None,
+                        Some(vec![ExtraItem::Text(&body)]),
+                    ))
+                    .header("Content-Type", "application/json")
+                    .body(body.clone())
+            })
+            .await?;
 
         self.handle_empty_response(response).await
     }
 
-    pub async fn delete(&self, path: &str) -> Result<(), ClientError> {
-        let route_path = self.build_path("/files", Some(path));
+    pub async fn create_node(&self, create_request: &CreateNodeRequest) -> Result<(), ClientError> {
+        let path = create_request.path.as_os_str();
+        let route_path = self.build_path("/mknod", Some(path));
         let url = self.build_url(&route_path);
 
-        self.cache_metadata.invalidate(&get_parent_path(&path));
+        self.cache_metadata.invalidate(&get_parent_path(path));
         self.read_buf.invalidate(path);
+        self.invalidate_negative_lookups(&get_parent_path(path));
+
+        let body = serde_json::to_string(create_request)?;
+
+        let response = self
+            .execute("MKNOD", Replay::Unsafe, || {
+                self.http_client
+                    .post(&url)
+                    .headers(self.get_headers(
+                        "POST",
+                        &route_path,
+                        None,
+                        None,
+                        Some(vec![ExtraItem::Text(&body)]),
+                    ))
+                    .header("Content-Type", "application/json")
+                    .body(body.clone())
+            })
+            .await?;
+
+        self.handle_empty_response(response).await
+    }
+
+    // Copies `length` bytes (or up to the end) of `source`, starting at `source_offset`,
+    // into `dest` at `dest_offset` entirely on the server; returns the bytes copied
+    pub async fn copy_range(
+        &self,
+        source: &OsStr,
+        dest: &OsStr,
+        source_offset: u64,
+        dest_offset: u64,
+        length: Option<u64>,
+    ) -> Result<u64, ClientError> {
+        let route_path = self.build_path("/copy", Some(dest));
+        let url = self.build_url(&route_path);
+
+        self.cache_metadata.invalidate(&get_parent_path(dest));
+        self.read_buf.invalidate(dest);
+        self.extents.invalidate(dest);
+        self.invalidate_negative_lookups(&get_parent_path(dest));
+        if let Some(pins) = &self.pin_store {
+            pins.invalidate(dest);
+        }
+
+        let body = serde_json::to_string(&CopyRequest {
+            source: source.to_os_string(),
+            source_offset,
+            dest_offset,
+            length,
+        })?;
+
+        // copying the same bytes to the same place again changes nothing, unless the
+        // copy is within one file: the source may then have been overwritten by it
+        let replay = if source == dest {
+            Replay::Unsafe
+        } else {
+            Replay::Safe
+        };
+        let response = self
+            .execute("COPY", replay, || {
+                self.http_client
+                    .post(&url)
+                    .headers(self.get_headers(
+                        "POST",
+                        &route_path,
+                        None,
+                        None,
+                        Some(vec![ExtraItem::Text(&body)]),
+                    ))
+                    .header("Content-Type", "application/json")
+                    .body(body.clone())
+                    .timeout(self.timeout)
+            })
+            .await?;
 
-        let headers = self.get_headers("DELETE", &route_path, None, None);
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let message = response
+                .text()
+                .await
+                .unwrap_or_else(|_| "Unknown error".to_string());
+            return Err(self.map_http_error(status, message));
+        }
+
+        let copy_response = response.json::<CopyResponse>().await?;
+        Ok(copy_response.copied)
+    }
+
+    pub async fn fallocate(
+        &self,
+        path: &OsStr,
+        fallocate_request: &FallocateRequest,
+    ) -> Result<(), ClientError> {
+        let route_path = self.build_path("/fallocate", Some(path));
+        let url = self.build_url(&route_path);
+
+        let body = serde_json::to_string(fallocate_request)?;
+
+        // allocating, zeroing or punching the same range twice gives the same file
+        let response = self
+            .execute("FALLOCATE", Replay::Safe, || {
+                self.http_client
+                    .post(&url)
+                    .headers(self.get_headers(
+                        "POST",
+                        &route_path,
+                        None,
+                        None,
+                        Some(vec![ExtraItem::Text(&body)]),
+                    ))
+                    .header("Content-Type", "application/json")
+                    .body(body.clone())
+                    .timeout(self.timeout)
+            })
+            .await?;
+        // a refused request changed nothing: the caches stay as they are
+        self.handle_empty_response(response).await?;
+
+        self.cache_metadata.invalidate(&get_parent_path(path));
+        self.extents.invalidate(path);
+        if let Some(pins) = &self.pin_store {
+            pins.invalidate(path);
+        }
+        // a size change makes the whole buffer stale, otherwise only the range is
+        if fallocate_request.keep_size {
+            if let Some(buf) = self.read_buf.get(path) {
+                clear_bytes(
+                    &mut buf.lock(),
+                    fallocate_request.offset,
+                    fallocate_request.length,
+                );
+            }
+        } else {
+            self.read_buf.invalidate(path);
+        }
+        Ok(())
+    }
+
+    // Replaces the immutable/append-only flags of a regular file, removing the ones
+    // already set only if `clear` is given
+    pub async fn set_flags(
+        &self,
+        path: &OsStr,
+        flags: u32,
+        clear: bool,
+    ) -> Result<(), ClientError> {
+        let route_path = self.build_path("/flags", Some(path));
+        let url = self.build_url(&route_path);
+
+        let body = serde_json::to_string(&FlagsRequest { flags, clear })?;
+
+        let response = self
+            .execute("FLAGS", Replay::Safe, || {
+                self.http_client
+                    .put(&url)
+                    .headers(self.get_headers(
+                        "PUT",
+                        &route_path,
+                        None,
+                        None,
+                        Some(vec![ExtraItem::Text(&body)]),
+                    ))
+                    .header("Content-Type", "application/json")
+                    .body(body.clone())
+                    .timeout(self.timeout)
+            })
+            .await?;
+
+        self.cache_metadata.invalidate(&get_parent_path(path));
+        if let Some(pins) = &self.pin_store {
+            pins.invalidate(path);
+        }
+
+        self.handle_empty_response(response).await
+    }
+
+    pub async fn delete(&self, path: &OsStr) -> Result<(), ClientError> {
+        let route_path = self.build_path("/files", Some(path));
+        let url = self.build_url(&route_path);
+
+        self.cache_metadata.invalidate(&get_parent_path(path));
+        self.read_buf.invalidate(path);
+        self.extents.invalidate(path);
+        if let Some(pins) = &self.pin_store {
+            pins.invalidate(path);
+        }
 
         let response = self
-            .http_client
-            .delete(&url)
-            .headers(headers)
-            .send()
+            .execute("DELETE", Replay::Safe, || {
+                self.http_client.delete(&url).headers(self.get_headers(
+                    "DELETE",
+                    &route_path,
+                    None,
+                    None,
+                    None,
+                ))
+            })
             .await?;
 
         self.handle_empty_response(response).await
@@ -681,11 +1966,12 @@ impl RemoteClient {
         let request_body = RegisterRequest { username };
 
         let response = self
-            .http_client
-            .post(&url)
-            .json(&request_body)
-            .timeout(self.timeout)
-            .send()
+            .execute("REGISTER", Replay::Unsafe, || {
+                self.http_client
+                    .post(&url)
+                    .json(&request_body)
+                    .timeout(self.timeout)
+            })
             .await?;
 
         if response.status().is_success() {
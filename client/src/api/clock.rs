@@ -0,0 +1,63 @@
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::util::auth::UserKeys;
+
+// How often the offset is measured again while the client is in use
+pub const CLOCK_RESYNC_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+// Offset between the clock of the server and the local one, added to the timestamps
+// of the signed requests: the server rejects those more than 5 minutes away from
+// its own time, which a drifting clock or a VM resumed from suspend easily is
+pub struct ServerClock {
+    offset_ms: AtomicI64,
+    synced_at: Mutex<Option<Instant>>,
+}
+
+impl Default for ServerClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ServerClock {
+    pub fn new() -> Self {
+        ServerClock {
+            offset_ms: AtomicI64::new(0),
+            synced_at: Mutex::new(None),
+        }
+    }
+
+    // Local time corrected by the last measured offset, in milliseconds
+    pub fn timestamp(&self) -> i64 {
+        UserKeys::generate_timestamp() + self.offset_ms.load(Ordering::Relaxed)
+    }
+
+    // True once per interval, for the caller that is going to measure the offset
+    pub fn claim_sync(&self) -> bool {
+        let mut synced_at = self.synced_at.lock();
+        match *synced_at {
+            Some(at) if at.elapsed() < CLOCK_RESYNC_INTERVAL => false,
+            _ => {
+                *synced_at = Some(Instant::now());
+                true
+            }
+        }
+    }
+
+    // `server_ms` was read by the server somewhere between `sent_ms` and
+    // `received_ms`, both local: the middle of the round trip is the best guess
+    pub fn record(&self, server_ms: i64, sent_ms: i64, received_ms: i64) {
+        let offset = server_ms - (sent_ms + received_ms) / 2;
+        let previous = self.offset_ms.swap(offset, Ordering::Relaxed);
+        *self.synced_at.lock() = Some(Instant::now());
+        if (offset - previous).abs() >= 1000 {
+            log::info!(
+                "🕒 [CLOCK] Server clock is {} ms {} the local one",
+                offset.abs(),
+                if offset >= 0 { "ahead of" } else { "behind" }
+            );
+        }
+    }
+}
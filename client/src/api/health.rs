@@ -0,0 +1,92 @@
+use parking_lot::Mutex;
+use std::sync::Arc;
+
+use crate::config::health::HealthPolicy;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerState {
+    Online,
+    Offline, // requests fail fast while a probe waits for the server
+}
+
+struct Circuit {
+    state: ServerState,
+    failures: u32, // requests in a row left without answer
+}
+
+// Circuit breaker over the connection to the server: after `failure_threshold`
+// requests in a row get no answer the server is considered offline, and requests
+// fail at once until a background probe reaches it again
+#[derive(Clone)]
+pub struct HealthMonitor {
+    circuit: Arc<Mutex<Circuit>>,
+    policy: HealthPolicy,
+}
+
+impl HealthMonitor {
+    pub fn new(policy: HealthPolicy) -> Self {
+        HealthMonitor {
+            circuit: Arc::new(Mutex::new(Circuit {
+                state: ServerState::Online,
+                failures: 0,
+            })),
+            policy,
+        }
+    }
+
+    pub fn state(&self) -> ServerState {
+        self.circuit.lock().state
+    }
+
+    // Whether the last requests went unanswered, without taking the server offline yet
+    pub fn failing(&self) -> bool {
+        self.circuit.lock().failures > 0
+    }
+
+    pub fn record_success(&self) {
+        let mut circuit = self.circuit.lock();
+        circuit.failures = 0;
+        if circuit.state == ServerState::Offline {
+            circuit.state = ServerState::Online;
+            log::info!("✅ [HEALTH] Server reachable again, back online");
+        }
+    }
+
+    // `probe_url` is polled from now on if this failure takes the server offline
+    pub fn record_failure(&self, http_client: &reqwest::Client, probe_url: String) {
+        let mut circuit = self.circuit.lock();
+        circuit.failures += 1;
+        if circuit.state == ServerState::Online && circuit.failures >= self.policy.failure_threshold
+        {
+            circuit.state = ServerState::Offline;
+            log::warn!(
+                "⚠️ [HEALTH] {} requests in a row without answer, server considered offline",
+                circuit.failures
+            );
+            drop(circuit);
+            self.spawn_probe(http_client.clone(), probe_url);
+        }
+    }
+
+    fn spawn_probe(&self, http_client: reqwest::Client, probe_url: String) {
+        let monitor = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(monitor.policy.probe_interval).await;
+                // any answer, an error status included, means the server is back
+                match http_client
+                    .get(&probe_url)
+                    .timeout(monitor.policy.probe_interval)
+                    .send()
+                    .await
+                {
+                    Ok(_) => {
+                        monitor.record_success();
+                        return;
+                    }
+                    Err(e) => log::debug!("[HEALTH] Probe failed: {}", e),
+                }
+            }
+        });
+    }
+}
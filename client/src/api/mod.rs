@@ -1,2 +1,7 @@
 pub mod client;
+pub mod clock;
+pub mod health;
 pub mod models;
+pub mod stream;
+pub mod tls;
+pub mod traffic;
@@ -1,4 +1,10 @@
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::ffi::OsString;
+use std::fmt;
+
+use crate::util::path::{serde_opt_path, serde_path};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthRequest {
     pub username: String,
@@ -22,8 +28,8 @@ pub struct SessionInfo {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MetaFile {
-    #[serde(rename = "name")]
-    pub name: String,
+    #[serde(rename = "name", with = "serde_path")]
+    pub name: OsString, // raw bytes, whatever the encoding of the name
     #[serde(rename = "size")]
     pub size: u64,
 
@@ -48,10 +54,26 @@ pub struct MetaFile {
     #[serde(rename = "nlink")]
     pub nlink: u32,
 
-    #[serde(rename = "refPath")]
-    pub ref_path: Option<String>,
+    #[serde(rename = "refPath", with = "serde_opt_path", default)]
+    pub ref_path: Option<OsString>,
+
+    #[serde(rename = "flags", default)]
+    pub flags: u32, // FS_IMMUTABLE_FL | FS_APPEND_FL, 0 when the server omits it
+
+    #[serde(rename = "rdev", default)]
+    pub rdev: u32, // device number of character and block devices, 0 otherwise
+
+    #[serde(rename = "sha256", default)]
+    pub sha256: Option<String>, // digest of the content of regular files, when known
+
+    #[serde(rename = "checksums", default)]
+    pub has_checksums: bool, // block hashes kept by the server, the digest may be unknown
 }
 
+// Inode flags kept by the server, with the values of linux/fs.h
+pub const FS_IMMUTABLE_FL: u32 = 0x10;
+pub const FS_APPEND_FL: u32 = 0x20;
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Copy)]
 #[serde(rename_all = "snake_case")]
 pub enum FileKind {
@@ -63,12 +85,26 @@ pub enum FileKind {
     Symlink,
     #[serde(rename = "hard_link")]
     Hardlink,
+    #[serde(rename = "fifo")]
+    Fifo,
+    #[serde(rename = "socket")]
+    Socket,
+    #[serde(rename = "char_device")]
+    CharDevice,
+    #[serde(rename = "block_device")]
+    BlockDevice,
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DirectoryListing {
     pub files: Vec<MetaFile>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListingPage {
+    pub files: Vec<MetaFile>,
+    pub next_cursor: Option<String>, // None on the last page
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileContent {
     pub data: Vec<u8>,
@@ -76,29 +112,36 @@ pub struct FileContent {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WriteRequest {
-    pub path: String,
-    pub new_path: Option<String>,
+    #[serde(with = "serde_path")]
+    pub path: OsString,
+    #[serde(with = "serde_opt_path")]
+    pub new_path: Option<OsString>,
     pub size: u64,
     pub atime: String,
     pub mtime: String,
     pub ctime: String,
     pub crtime: String,
     pub kind: FileKind,
-    pub ref_path: Option<String>,
+    #[serde(with = "serde_opt_path")]
+    pub ref_path: Option<OsString>,
     pub perm: String,
     pub mode: Mode,
     pub data: Option<Vec<u8>>,
     pub offset: Option<u64>,
 }
 
-impl FileKind {
-    pub fn to_string(&self) -> String {
-        match self {
-            FileKind::RegularFile => "regular_file".to_string(),
-            FileKind::Directory => "directory".to_string(),
-            FileKind::Symlink => "soft_link".to_string(),
-            FileKind::Hardlink => "hard_link".to_string(),
-        }
+impl fmt::Display for FileKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FileKind::RegularFile => "regular_file",
+            FileKind::Directory => "directory",
+            FileKind::Symlink => "soft_link",
+            FileKind::Hardlink => "hard_link",
+            FileKind::Fifo => "fifo",
+            FileKind::Socket => "socket",
+            FileKind::CharDevice => "char_device",
+            FileKind::BlockDevice => "block_device",
+        })
     }
 }
 
@@ -112,16 +155,145 @@ pub enum Mode {
     WriteAt,
     #[serde(rename = "truncate")]
     Truncate,
+    #[serde(rename = "create")]
+    Create, // like Write, but fails with 409 if the path already exists
 }
 
-impl Mode {
-    pub fn to_string(&self) -> String {
-        match self {
-            Mode::Write => "write".to_string(),
-            Mode::Append => "append".to_string(),
-            Mode::WriteAt => "write_at".to_string(),
-            Mode::Truncate => "truncate".to_string(),
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Mode::Write => "write",
+            Mode::Append => "append",
+            Mode::WriteAt => "write_at",
+            Mode::Truncate => "truncate",
+            Mode::Create => "create",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CopyRequest {
+    #[serde(with = "serde_path")]
+    pub source: OsString,
+    pub source_offset: u64,
+    pub dest_offset: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub length: Option<u64>, // None copies up to the end of the source
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CopyResponse {
+    pub copied: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Copy)]
+pub enum AllocateOperation {
+    #[serde(rename = "allocate")]
+    Allocate,
+    #[serde(rename = "punch_hole")]
+    PunchHole,
+    #[serde(rename = "zero_range")]
+    ZeroRange,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FallocateRequest {
+    pub operation: AllocateOperation,
+    pub offset: u64,
+    pub length: u64,
+    pub keep_size: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlagsRequest {
+    pub flags: u32,
+    pub clear: bool, // flags already set may be removed
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Extent {
+    pub offset: u64,
+    pub length: u64,
+}
+
+// SHA-256 of a file, whole and block by block (the last block may be shorter)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileChecksums {
+    pub size: u64,
+    pub sha256: Option<String>, // None only when the blocks alone were asked for
+    pub block_size: u64,
+    pub blocks: Vec<String>,
+}
+
+impl FileChecksums {
+    // Checks the blocks lying entirely in `data`, read from `offset`: the others
+    // cannot be told apart. Returns the index of the first block that differs
+    pub fn mismatch(&self, offset: u64, data: &[u8]) -> Option<usize> {
+        if self.block_size == 0 {
+            return None;
+        }
+        let end = offset + data.len() as u64;
+        let mut index = offset.div_ceil(self.block_size) as usize;
+        while index < self.blocks.len() {
+            let start = index as u64 * self.block_size;
+            let stop = (start + self.block_size).min(self.size);
+            if stop > end {
+                break;
+            }
+            let block = &data[(start - offset) as usize..(stop - offset) as usize];
+            if format!("{:x}", Sha256::digest(block)) != self.blocks[index] {
+                return Some(index);
+            }
+            index += 1;
         }
+        None
+    }
+}
+
+// Data ranges of a file, sorted and disjoint: everything else is a hole
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileExtents {
+    pub size: u64,
+    pub extents: Vec<Extent>,
+}
+
+impl FileExtents {
+    // First offset >= `offset` holding data, as SEEK_DATA
+    pub fn next_data(&self, offset: u64) -> Option<u64> {
+        self.extents
+            .iter()
+            .find(|e| e.offset + e.length > offset)
+            .map(|e| e.offset.max(offset))
+            .filter(|&data| data < self.size)
+    }
+
+    // First offset >= `offset` inside a hole, as SEEK_HOLE: the end of the file
+    // counts as one
+    pub fn next_hole(&self, offset: u64) -> u64 {
+        self.extents
+            .iter()
+            .find(|e| e.offset <= offset && offset < e.offset + e.length)
+            .map_or(offset, |e| e.offset + e.length)
+            .min(self.size)
+    }
+
+    // Parts of [start, start + len) holding data
+    pub fn data_within(&self, start: u64, len: u64) -> Vec<Extent> {
+        let end = start + len;
+        self.extents
+            .iter()
+            .filter(|e| e.offset < end && start < e.offset + e.length)
+            .map(|e| {
+                let offset = e.offset.max(start);
+                Extent {
+                    offset,
+                    length: (e.offset + e.length).min(end) - offset,
+                }
+            })
+            .collect()
     }
 }
 
@@ -138,8 +310,21 @@ pub struct CreateFileRequest {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CreateDirectoryRequest {
-    pub path: String,
+    #[serde(skip)]
+    pub path: OsString, // sent as the path parameter
+    pub permissions_octal: String,
+}
+
+// Named pipes, sockets and device nodes, kept by the server as placeholders
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateNodeRequest {
+    #[serde(skip)]
+    pub path: OsString, // sent as the path parameter
+    pub kind: FileKind,
+    pub rdev: u32,
     pub permissions_octal: String,
 }
 
@@ -147,3 +332,9 @@ pub struct CreateDirectoryRequest {
 pub struct RegisterRequest {
     pub username: String,
 }
+
+// Answer of GET /time: the server clock in milliseconds since the epoch
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerTime {
+    pub time: i64,
+}
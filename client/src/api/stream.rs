@@ -0,0 +1,176 @@
+use serde::de::{DeserializeOwned, Error as _};
+
+// Incremental decoder for a top-level JSON array: elements are handed out as soon as
+// their last byte arrives, so a large listing never has to be buffered whole
+pub struct JsonArrayDecoder {
+    buf: Vec<u8>,
+    pos: usize,                   // first byte of `buf` not scanned yet
+    element_start: Option<usize>, // first byte of the element being read
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    started: bool,
+    finished: bool,
+}
+
+impl Default for JsonArrayDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JsonArrayDecoder {
+    pub fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+            pos: 0,
+            element_start: None,
+            depth: 0,
+            in_string: false,
+            escaped: false,
+            started: false,
+            finished: false,
+        }
+    }
+
+    pub fn push<T: DeserializeOwned>(&mut self, chunk: &[u8]) -> Result<Vec<T>, serde_json::Error> {
+        self.buf.extend_from_slice(chunk);
+        let mut decoded = Vec::new();
+
+        while self.pos < self.buf.len() {
+            let i = self.pos;
+            let byte = self.buf[i];
+            self.pos += 1;
+
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if byte == b'\\' {
+                    self.escaped = true;
+                } else if byte == b'"' {
+                    self.in_string = false;
+                }
+                continue;
+            }
+
+            if byte.is_ascii_whitespace() {
+                continue;
+            }
+
+            if !self.started {
+                if byte != b'[' {
+                    return Err(serde_json::Error::custom("expected a JSON array"));
+                }
+                self.started = true;
+                continue;
+            }
+
+            if self.finished {
+                return Err(serde_json::Error::custom(
+                    "trailing characters after JSON array",
+                ));
+            }
+
+            match byte {
+                b',' | b']' if self.depth == 0 => {
+                    if let Some(start) = self.element_start.take() {
+                        decoded.push(serde_json::from_slice(&self.buf[start..i])?);
+                    } else if byte == b',' {
+                        return Err(serde_json::Error::custom("missing array element"));
+                    }
+                    self.finished = byte == b']';
+                }
+                b'{' | b'[' => {
+                    self.element_start.get_or_insert(i);
+                    self.depth += 1;
+                }
+                b'}' | b']' => {
+                    self.depth = self
+                        .depth
+                        .checked_sub(1)
+                        .ok_or_else(|| serde_json::Error::custom("unbalanced JSON array"))?;
+                }
+                b'"' => {
+                    self.element_start.get_or_insert(i);
+                    self.in_string = true;
+                }
+                _ => {
+                    self.element_start.get_or_insert(i);
+                }
+            }
+        }
+
+        // keep only the bytes of the element still incomplete
+        let keep_from = self.element_start.unwrap_or(self.pos);
+        self.buf.drain(..keep_from);
+        self.pos -= keep_from;
+        if let Some(start) = self.element_start.as_mut() {
+            *start -= keep_from;
+        }
+
+        Ok(decoded)
+    }
+
+    pub fn finish(self) -> Result<(), serde_json::Error> {
+        if self.finished {
+            Ok(())
+        } else {
+            Err(serde_json::Error::custom("unexpected end of JSON array"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn decode_in_chunks(json: &[u8], size: usize) -> Result<Vec<Value>, serde_json::Error> {
+        let mut decoder = JsonArrayDecoder::new();
+        let mut values = Vec::new();
+        for chunk in json.chunks(size) {
+            values.extend(decoder.push::<Value>(chunk)?);
+        }
+        decoder.finish()?;
+        Ok(values)
+    }
+
+    #[test]
+    fn decodes_whatever_the_chunks() {
+        let json = br#" [ {"name": "a,b]", "x": [1, {"y": "}"}]}, "q\"]", 3, null ] "#;
+        let expected: Vec<Value> = serde_json::from_slice(json).unwrap();
+        for size in 1..=json.len() {
+            assert_eq!(
+                decode_in_chunks(json, size).unwrap(),
+                expected,
+                "chunks of {}",
+                size
+            );
+        }
+    }
+
+    #[test]
+    fn hands_out_elements_as_they_complete() {
+        let mut decoder = JsonArrayDecoder::new();
+        assert_eq!(decoder.push::<u32>(b"[1, 2").unwrap(), vec![1]);
+        assert_eq!(decoder.push::<u32>(b"2, 3").unwrap(), vec![22]);
+        assert_eq!(decoder.push::<u32>(b"]").unwrap(), vec![3]);
+        decoder.finish().unwrap();
+    }
+
+    #[test]
+    fn decodes_an_empty_array() {
+        assert!(decode_in_chunks(b"[]", 1).unwrap().is_empty());
+        assert!(decode_in_chunks(b" [ ] ", 2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_arrays() {
+        assert!(decode_in_chunks(b"{}", 1).is_err());
+        assert!(decode_in_chunks(b"[1,,2]", 1).is_err());
+        assert!(decode_in_chunks(b"[1] 2", 1).is_err());
+        assert!(decode_in_chunks(b"[1}]", 1).is_err());
+        assert!(decode_in_chunks(b"[1, 2", 1).is_err());
+        assert!(decode_in_chunks(b"[1 2]", 1).is_err());
+    }
+}
@@ -0,0 +1,186 @@
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, ClientConfig, OwnedTrustAnchor, PrivateKey, RootCertStore, ServerName};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::config::connection::Http2Mode;
+use crate::config::tls::{Pin, TlsPolicy, TlsVersion};
+
+#[derive(Debug, thiserror::Error)]
+pub enum TlsError {
+    #[error("Cannot read the {what} `{path}`: {source}")]
+    Read {
+        what: &'static str,
+        path: String,
+        source: std::io::Error,
+    },
+
+    #[error("No PEM certificate found in the {what} `{path}`")]
+    NoCertificate { what: &'static str, path: String },
+
+    #[error("No PEM private key found in `{path}`")]
+    NoKey { path: String },
+
+    #[error("Invalid TLS settings: {0}")]
+    Invalid(String),
+}
+
+// TLS settings of the HTTP client: public roots plus the extra CA bundle, the client
+// certificate, the accepted versions and, when there are pins, a verifier checking
+// the server certificate against them after the usual validation
+pub fn client_config(policy: &TlsPolicy, http2: Http2Mode) -> Result<ClientConfig, TlsError> {
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            anchor.subject,
+            anchor.spki,
+            anchor.name_constraints,
+        )
+    }));
+    if let Some(path) = &policy.ca_bundle {
+        for cert in read_certs("CA bundle", path)? {
+            roots
+                .add(&cert)
+                .map_err(|e| TlsError::Invalid(format!("CA bundle `{}`: {}", path.display(), e)))?;
+        }
+    }
+
+    let versions: &[&rustls::SupportedProtocolVersion] = match policy.min_version {
+        TlsVersion::Tls12 => &[&rustls::version::TLS13, &rustls::version::TLS12],
+        TlsVersion::Tls13 => &[&rustls::version::TLS13],
+    };
+    let builder = ClientConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(versions)
+        .map_err(|e| TlsError::Invalid(e.to_string()))?
+        .with_root_certificates(roots.clone());
+
+    let mut config = match (&policy.client_cert, &policy.client_key) {
+        (Some(cert), Some(key)) => builder
+            .with_client_auth_cert(read_certs("client certificate", cert)?, read_key(key)?)
+            .map_err(|e| TlsError::Invalid(format!("client certificate: {}", e)))?,
+        _ => builder.with_no_client_auth(),
+    };
+
+    let pins = policy.parsed_pins().map_err(TlsError::Invalid)?;
+    if !pins.is_empty() {
+        config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(PinnedVerifier {
+                inner: WebPkiVerifier::new(roots, None),
+                pins,
+            }));
+    }
+
+    // offered during the handshake, as the HTTP client does with its own settings
+    config.alpn_protocols = match http2 {
+        Http2Mode::Negotiate => vec![b"h2".to_vec(), b"http/1.1".to_vec()],
+        Http2Mode::PriorKnowledge => vec![b"h2".to_vec()],
+        Http2Mode::Off => vec![b"http/1.1".to_vec()],
+    };
+
+    Ok(config)
+}
+
+fn open(what: &'static str, path: &Path) -> Result<BufReader<File>, TlsError> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|source| TlsError::Read {
+            what,
+            path: path.display().to_string(),
+            source,
+        })
+}
+
+fn read_certs(what: &'static str, path: &Path) -> Result<Vec<Certificate>, TlsError> {
+    let certs = rustls_pemfile::certs(&mut open(what, path)?).map_err(|source| TlsError::Read {
+        what,
+        path: path.display().to_string(),
+        source,
+    })?;
+    if certs.is_empty() {
+        return Err(TlsError::NoCertificate {
+            what,
+            path: path.display().to_string(),
+        });
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn read_key(path: &Path) -> Result<PrivateKey, TlsError> {
+    let what = "client key";
+    let items =
+        rustls_pemfile::read_all(&mut open(what, path)?).map_err(|source| TlsError::Read {
+            what,
+            path: path.display().to_string(),
+            source,
+        })?;
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| TlsError::NoKey {
+            path: path.display().to_string(),
+        })
+}
+
+struct PinnedVerifier {
+    inner: WebPkiVerifier,
+    pins: Vec<Pin>,
+}
+
+impl PinnedVerifier {
+    fn matches(&self, cert: &Certificate) -> bool {
+        let cert_digest: [u8; 32] = Sha256::digest(&cert.0).into();
+        let spki_digest: Option<[u8; 32]> = X509Certificate::from_der(&cert.0)
+            .ok()
+            .map(|(_, parsed)| Sha256::digest(parsed.public_key().raw).into());
+        self.pins.iter().any(|pin| match pin {
+            Pin::Certificate(digest) => *digest == cert_digest,
+            Pin::Spki(digest) => Some(*digest) == spki_digest,
+        })
+    }
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self.inner.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )?;
+
+        // only the certificate of the server itself: the intermediates are whatever
+        // the server chose to send, and a pinned certificate is public, so anyone could
+        // append it to a chain of their own
+        if self.matches(end_entity) {
+            Ok(verified)
+        } else {
+            eprintln!("❌ [TLS] The server certificate matches none of the configured pins");
+            Err(rustls::Error::General(
+                "server certificate does not match any pin".to_string(),
+            ))
+        }
+    }
+}
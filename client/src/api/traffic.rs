@@ -0,0 +1,181 @@
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, OwnedSemaphorePermit, Semaphore};
+
+use crate::config::traffic::TrafficPolicy;
+
+// Longest a bulk transfer gives way to foreground requests before starting anyway,
+// so that a steady stream of them cannot hold it back for good
+const FOREGROUND_GRACE: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    Interactive, // metadata and small reads and writes, someone is waiting for them
+    Bulk,        // large uploads and downloads, like those of a `cp -r`
+}
+
+// Token bucket allowing one second of burst. Transfers are charged when their size is
+// known and may drive the balance below zero: bulk transfers then wait for it to be
+// paid back, while interactive ones never wait but still use up the bandwidth
+struct RateLimit {
+    rate: f64,                     // bytes per second
+    bucket: Mutex<(f64, Instant)>, // balance, last refill
+}
+
+impl RateLimit {
+    fn new(rate: u64) -> Option<Self> {
+        (rate > 0).then(|| RateLimit {
+            rate: rate as f64,
+            bucket: Mutex::new((rate as f64, Instant::now())),
+        })
+    }
+
+    fn refill(&self, bucket: &mut (f64, Instant)) {
+        let now = Instant::now();
+        bucket.0 =
+            (bucket.0 + now.duration_since(bucket.1).as_secs_f64() * self.rate).min(self.rate);
+        bucket.1 = now;
+    }
+
+    fn charge(&self, bytes: u64) {
+        let mut bucket = self.bucket.lock();
+        self.refill(&mut bucket);
+        bucket.0 -= bytes as f64;
+    }
+
+    async fn wait(&self) {
+        loop {
+            let debt = {
+                let mut bucket = self.bucket.lock();
+                self.refill(&mut bucket);
+                -bucket.0
+            };
+            if debt <= 0.0 {
+                return;
+            }
+            tokio::time::sleep(Duration::from_secs_f64(debt / self.rate)).await;
+        }
+    }
+}
+
+// Schedules the requests of the client: rate limits for both directions, a cap on the
+// bulk transfers in flight and precedence of interactive requests over bulk ones
+pub struct TrafficShaper {
+    upload: Option<RateLimit>,
+    download: Option<RateLimit>,
+    bulk_threshold: u64,
+    bulk_slots: Arc<Semaphore>,
+    interactive: AtomicUsize, // interactive requests in flight
+    foreground_idle: Notify,
+}
+
+// Place of a request in the schedule, held until its answer has been read
+pub enum Slot {
+    Interactive(Arc<TrafficShaper>),
+    Bulk { _permit: OwnedSemaphorePermit },
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        if let Slot::Interactive(shaper) = self {
+            if shaper.interactive.fetch_sub(1, Ordering::AcqRel) == 1 {
+                shaper.foreground_idle.notify_waiters();
+            }
+        }
+    }
+}
+
+impl TrafficShaper {
+    pub fn new(policy: &TrafficPolicy) -> Self {
+        TrafficShaper {
+            upload: RateLimit::new(policy.upload_limit),
+            download: RateLimit::new(policy.download_limit),
+            bulk_threshold: policy.bulk_threshold,
+            bulk_slots: Arc::new(Semaphore::new(policy.max_bulk_transfers)),
+            interactive: AtomicUsize::new(0),
+            foreground_idle: Notify::new(),
+        }
+    }
+
+    // Priority of an operation moving `bytes` in either direction
+    pub fn classify(&self, bytes: u64) -> Priority {
+        if bytes >= self.bulk_threshold {
+            Priority::Bulk
+        } else {
+            Priority::Interactive
+        }
+    }
+
+    // Waits for the turn of a request, bulk when the body sent or the range asked for
+    // reaches the threshold, and counts its body against the upload limit
+    pub async fn admit(self: &Arc<Self>, request: &reqwest::Request) -> Slot {
+        let header = |name| request.headers().get(name).and_then(|v| v.to_str().ok());
+        let upload = header(reqwest::header::CONTENT_LENGTH)
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(0);
+        let download = header(reqwest::header::RANGE)
+            .and_then(range_length)
+            .unwrap_or(0);
+        self.admit_as(self.classify(upload.max(download)), upload)
+            .await
+    }
+
+    // Waits for the turn of an operation of the given priority, counting `upload`
+    // bytes against the upload limit. The requests it is made of then go without
+    // taking a place of their own, so a bulk operation takes a single bulk slot
+    pub async fn admit_as(self: &Arc<Self>, priority: Priority, upload: u64) -> Slot {
+        let slot = match priority {
+            Priority::Interactive => {
+                self.interactive.fetch_add(1, Ordering::AcqRel);
+                Slot::Interactive(self.clone())
+            }
+            Priority::Bulk => {
+                let permit = self
+                    .bulk_slots
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("bulk transfer semaphore closed");
+                let _ = tokio::time::timeout(FOREGROUND_GRACE, self.foreground_idle()).await;
+                if let Some(limit) = &self.upload {
+                    limit.wait().await;
+                }
+                if let Some(limit) = &self.download {
+                    limit.wait().await;
+                }
+                Slot::Bulk { _permit: permit }
+            }
+        };
+        if let Some(limit) = &self.upload {
+            limit.charge(upload);
+        }
+        slot
+    }
+
+    // Counts the bytes of an answer against the download limit
+    pub fn downloaded(&self, bytes: u64) {
+        if let Some(limit) = &self.download {
+            limit.charge(bytes);
+        }
+    }
+
+    async fn foreground_idle(&self) {
+        loop {
+            let idle = self.foreground_idle.notified();
+            if self.interactive.load(Ordering::Acquire) == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+}
+
+// Bytes asked for by a `bytes=start-end` Range header
+fn range_length(range: &str) -> Option<u64> {
+    let (start, end) = range.strip_prefix("bytes=")?.split_once('-')?;
+    let start: u64 = start.trim().parse().ok()?;
+    let end: u64 = end.trim().parse().ok()?;
+    end.checked_sub(start).map(|span| span + 1)
+}
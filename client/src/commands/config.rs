@@ -1,4 +1,11 @@
+use bifrost::config::cache::{CachePolicy, CacheRule};
+use bifrost::config::connection::{ConnectionPolicy, Http2Mode};
+use bifrost::config::health::HealthPolicy;
+use bifrost::config::proxy::ProxyPolicy;
+use bifrost::config::retry::RetryPolicy;
 use bifrost::config::settings::{Config, ConfigError};
+use bifrost::config::tls::{Pin, TlsPolicy, TlsVersion};
+use bifrost::config::traffic::TrafficPolicy;
 use std::io::{self, Write};
 use std::path::PathBuf;
 use std::time::Duration;
@@ -29,6 +36,45 @@ pub async fn run() {
         default_mount.to_str().unwrap_or("/tmp/bifrostFS"),
     );
     let timeout_secs = prompt_parse::<u64>("Timeout in seconds", 60);
+    let max_attempts = prompt_parse::<u32>("Attempts per request (1 = no retries)", 4);
+
+    println!("\nCache settings (lower values suit folders shared with other users):");
+    let metadata_ttl_secs = prompt_parse::<u64>("Metadata cache lifetime in seconds", 180);
+    let kernel_ttl_secs = prompt_parse::<u64>("Kernel attribute lifetime in seconds", 1);
+    let read_buffer_entries = prompt_parse::<u64>("Files kept in the read cache", 512);
+    let read_prefetch_kib = prompt_parse::<u64>("Read prefetch in KiB", 2048);
+    let rules = prompt_cache_rules();
+
+    println!("\nBandwidth settings (0 = unlimited):");
+    let upload_limit_kib = prompt_parse::<u64>("Upload limit in KiB/s", 0);
+    let download_limit_kib = prompt_parse::<u64>("Download limit in KiB/s", 0);
+    let max_bulk_transfers = prompt_parse::<usize>("Large transfers running at once", 2);
+
+    println!("\nConnection settings:");
+    let http2 = prompt_parse::<Http2Mode>(
+        "HTTP/2 (negotiate, prior-knowledge, off)",
+        Http2Mode::Negotiate,
+    );
+    let parallel_requests =
+        prompt_parse::<usize>("Parallel requests for large reads and listings", 4);
+
+    // only asked for servers reached over HTTPS
+    let tls = if server_url.starts_with("https://") {
+        prompt_tls()
+    } else {
+        TlsPolicy::default()
+    };
+
+    let proxy = prompt_proxy();
+
+    let default_permissions = prompt_parse::<bool>(
+        "\nLet the kernel check permissions (default_permissions)",
+        false,
+    );
+    let strict_integrity = prompt_parse::<bool>(
+        "Reject server responses that are not signed (strict integrity)",
+        false,
+    );
 
     let config = Config {
         server_url,
@@ -36,8 +82,42 @@ pub async fn run() {
         mount_point,
         timeout: Duration::from_secs(timeout_secs),
         api_key: None,
+        cache: CachePolicy {
+            metadata_ttl: Duration::from_secs(metadata_ttl_secs),
+            metadata_tti: Duration::from_secs(metadata_ttl_secs),
+            read_buffer_entries,
+            read_prefetch: read_prefetch_kib * 1024,
+            kernel_ttl: Duration::from_secs(kernel_ttl_secs),
+            rules,
+            ..CachePolicy::default()
+        },
+        retry: RetryPolicy {
+            max_attempts,
+            ..RetryPolicy::default()
+        },
+        health: HealthPolicy::default(),
+        traffic: TrafficPolicy {
+            upload_limit: upload_limit_kib * 1024,
+            download_limit: download_limit_kib * 1024,
+            max_bulk_transfers,
+            ..TrafficPolicy::default()
+        },
+        connection: ConnectionPolicy {
+            http2,
+            parallel_requests,
+            ..ConnectionPolicy::default()
+        },
+        tls,
+        proxy,
+        default_permissions,
+        strict_integrity,
     };
 
+    if let Err(e) = config.validate() {
+        eprintln!("{}", e);
+        return;
+    }
+
     match config.save_to_file() {
         Ok(_) => {
             println!(
@@ -68,6 +148,11 @@ fn prompt(field: &str, default: &str) -> String {
     }
 }
 
+// Like `prompt`, without echoing what is typed
+fn prompt_secret(field: &str) -> String {
+    rpassword::prompt_password(format!("{}: ", field)).unwrap()
+}
+
 fn prompt_parse<T>(field: &str, default: T) -> T
 where
     T: std::str::FromStr + Clone + std::fmt::Debug,
@@ -93,3 +178,120 @@ where
 fn prompt_path(field: &str, default: &str) -> PathBuf {
     PathBuf::from(prompt(field, default))
 }
+
+// An existing file, or None when left empty
+fn prompt_file(field: &str) -> Option<PathBuf> {
+    loop {
+        let input = prompt(field, "");
+        if input.is_empty() {
+            return None;
+        }
+        let path = PathBuf::from(input);
+        if path.is_file() {
+            return Some(path);
+        }
+        println!("No such file, please try again.");
+    }
+}
+
+fn prompt_tls() -> TlsPolicy {
+    println!("\nTLS settings (leave empty for none):");
+    let ca_bundle = prompt_file("Extra CA bundle (PEM) trusted besides the public roots");
+    let client_cert = prompt_file("Client certificate (PEM) for mutual TLS");
+    let client_key = match client_cert {
+        Some(_) => loop {
+            if let Some(key) = prompt_file("Client private key (PEM)") {
+                break Some(key);
+            }
+            println!("A client certificate needs its private key.");
+        },
+        None => None,
+    };
+    let pins = prompt_pins();
+    let min_version =
+        prompt_parse::<TlsVersion>("Minimum TLS version (1.2, 1.3)", TlsVersion::Tls12);
+
+    TlsPolicy {
+        ca_bundle,
+        client_cert,
+        client_key,
+        pins,
+        min_version,
+    }
+}
+
+fn prompt_proxy() -> ProxyPolicy {
+    println!("\nProxy settings (leave the URL empty to follow HTTPS_PROXY and NO_PROXY):");
+    let url = prompt("Proxy URL (http://, https://, socks5:// or socks5h://)", "");
+    if url.is_empty() {
+        return ProxyPolicy::default();
+    }
+
+    let username = Some(prompt("Proxy username", "")).filter(|u| !u.is_empty());
+    let password = username.as_ref().map(|_| prompt_secret("Proxy password"));
+    let no_proxy = prompt("Hosts reached without the proxy, comma separated", "")
+        .split(',')
+        .map(str::trim)
+        .filter(|h| !h.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    ProxyPolicy {
+        url: Some(url),
+        username,
+        password,
+        no_proxy,
+        ..ProxyPolicy::default()
+    }
+}
+
+// Pins are entered separated by commas, e.g. `spki-sha256:<hex>, cert-sha256:<hex>`
+fn prompt_pins() -> Vec<String> {
+    loop {
+        let input = prompt(
+            "Pinned certificates as spki-sha256:<hex> or cert-sha256:<hex>, comma separated",
+            "",
+        );
+
+        let pins: Vec<String> = input
+            .split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        match pins.iter().find_map(|p| p.parse::<Pin>().err()) {
+            None => return pins,
+            Some(e) => println!("{}, please try again.", e),
+        }
+    }
+}
+
+// Rules are entered as `glob=seconds` pairs separated by commas,
+// e.g. `*.sqlite=0, /datasets/**=3600`
+fn prompt_cache_rules() -> Vec<CacheRule> {
+    loop {
+        let input = prompt(
+            "Cache rules as glob=seconds, comma separated (0 = never cached)",
+            "",
+        );
+
+        let rules: Option<Vec<CacheRule>> = input
+            .split(',')
+            .map(str::trim)
+            .filter(|r| !r.is_empty())
+            .map(|r| {
+                let (pattern, secs) = r.rsplit_once('=')?;
+                Some(CacheRule {
+                    pattern: pattern.trim().to_string(),
+                    ttl: Duration::from_secs(secs.trim().parse().ok()?),
+                })
+            })
+            .collect();
+
+        match rules {
+            Some(rules) if rules.iter().all(|r| !r.pattern.is_empty()) => return rules,
+            _ => println!("Invalid input, please try again."),
+        }
+    }
+}
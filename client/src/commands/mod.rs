@@ -1,4 +1,6 @@
 pub mod config;
+pub mod pin;
 pub mod register;
 pub mod start;
-pub mod stop;
\ No newline at end of file
+pub mod stop;
+pub mod unpin;
\ No newline at end of file
@@ -0,0 +1,115 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use bifrost::api::client::RemoteClient;
+use bifrost::config::settings::Config;
+use bifrost::pin::store::PinStore;
+use bifrost::util::auth::UserKeys;
+
+pub async fn run(path: Option<PathBuf>, list: bool) {
+    let config = match Config::from_file() {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let store = match PinStore::open() {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if list {
+        print_pinned(&store);
+        return;
+    }
+
+    let Some(path) = path else {
+        eprintln!("\nSpecify the path to pin, or use `--list` to show the pinned ones.");
+        std::process::exit(1);
+    };
+
+    let user_keys = match UserKeys::load_from_files() {
+        Ok(keys) => keys,
+        Err(e) => {
+            eprintln!("\n{}.\nRun `bifrost register` to register a new user.", e);
+            std::process::exit(1);
+        }
+    };
+
+    let remote = remote_path(&config, &path);
+    let client = match RemoteClient::new(&config, Some(user_keys)) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("\n{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let shown = remote.to_string_lossy();
+    println!("\nPinning `{}`...", shown);
+    match store.pin(&client, &remote).await {
+        Ok(size) => println!("Pinned `{}` ({})", shown, format_size(size)),
+        Err(e) => {
+            eprintln!("Failed to pin `{}`: {}", shown, e);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn print_pinned(store: &PinStore) {
+    let pinned = store.pinned();
+    if pinned.is_empty() {
+        println!("\nNothing is pinned.");
+        return;
+    }
+
+    println!("\nPinned paths:");
+    for root in pinned {
+        println!(
+            "  {}  {} in {} file(s)",
+            root.path.to_string_lossy(),
+            format_size(root.size),
+            root.files
+        );
+    }
+}
+
+// Accepts both a path inside the mount point and a path on the server
+pub fn remote_path(config: &Config, path: &Path) -> OsString {
+    let local = path.to_path_buf();
+    let absolute = if local.is_absolute() {
+        local
+    } else {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(&local))
+            .unwrap_or(local)
+    };
+
+    let relative = match absolute.strip_prefix(&config.mount_point) {
+        Ok(inside_mount) => inside_mount.to_path_buf(),
+        Err(_) => path.strip_prefix("/").unwrap_or(path).to_path_buf(),
+    };
+
+    let cleaned = path_clean::clean(PathBuf::from("/").join(relative));
+    cleaned.into_os_string()
+}
+
+fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", size, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
@@ -26,18 +26,24 @@ pub async fn run() {
     let mut secret_key_path = dir.clone();
     secret_key_path.push("secret_key");
 
-    if keys_exist_and_nonempty(&api_key_path, &secret_key_path) {
-        if !ask_confirmation("\nKeys already exist. Overwrite them?") {
-            println!("Aborted.");
-            return;
-        }
+    if keys_exist_and_nonempty(&api_key_path, &secret_key_path)
+        && !ask_confirmation("\nKeys already exist. Overwrite them?")
+    {
+        println!("Aborted.");
+        return;
     }
 
     println!("\nBegin registration:");
 
     let username = get_current_user();
 
-    let client = RemoteClient::new(&config, None);
+    let client = match RemoteClient::new(&config, None) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("\n{}", e);
+            return;
+        }
+    };
 
     let user_keys: UserKeys = client
         .user_registration(username.clone())
@@ -59,7 +65,7 @@ fn keys_exist_and_nonempty(api_key_path: &Path, secret_key_path: &Path) -> bool
             return false;
         }
         let metadata = fs::metadata(path).ok();
-        if metadata.map_or(true, |m| m.len() == 0) {
+        if metadata.is_none_or(|m| m.len() == 0) {
             return false;
         }
     }
@@ -1,11 +1,15 @@
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 use bifrost::{
     api::client::RemoteClient, config::settings::Config, fs::operations::RemoteFileSystem,
-    util::auth::UserKeys,
+    pin::store::PinStore, util::auth::UserKeys,
 };
 use fuser::{mount2, MountOption};
 
+const PIN_REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
 pub async fn run(enable_service: bool) {
     let config = match Config::from_file() {
         Ok(cfg) => cfg,
@@ -35,16 +39,38 @@ pub async fn run(enable_service: bool) {
     println!("📡 Server: {}", config.server_full_url());
     println!("📁 Mount point: {:?}", config.mount_point);
 
+    // TLS files and settings are checked here, before anything gets mounted
+    let client = match RemoteClient::new(&config, Some(user_keys)) {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("❌ {}", e);
+            std::process::exit(1);
+        }
+    };
+
     prepare_mount_point(&config.mount_point);
 
-    let filesystem = RemoteFileSystem::new(RemoteClient::new(&config, Some(user_keys)));
+    let pin_store = match PinStore::open() {
+        Ok(store) => Arc::new(store),
+        Err(e) => {
+            eprintln!("❌ Error in opening the pinned files: {}", e);
+            std::process::exit(1);
+        }
+    };
+    spawn_pin_refresh(&config, pin_store.clone());
+
+    let filesystem = RemoteFileSystem::new(client.with_pin_store(pin_store))
+        .with_default_permissions(config.default_permissions);
     println!("✅ Filesystem initialized");
 
-    let options = [
+    let mut options = vec![
         MountOption::RW,
         MountOption::FSName("bifrost".to_string()),
         MountOption::AutoUnmount,
     ];
+    if config.default_permissions {
+        options.push(MountOption::DefaultPermissions);
+    }
 
     println!("🔧 Mounting filesystem...");
     println!("📋 To test it: ls {:?}", config.mount_point);
@@ -64,6 +90,28 @@ pub async fn run(enable_service: bool) {
     }
 }
 
+// Keeps the pinned copies in line with the server, with a client of its own so that
+// refreshing never competes with the mount's caches
+fn spawn_pin_refresh(config: &Config, pin_store: Arc<PinStore>) {
+    let user_keys = match UserKeys::load_from_files() {
+        Ok(keys) => keys,
+        Err(_) => return,
+    };
+    let Ok(client) = RemoteClient::new(config, Some(user_keys)) else {
+        return;
+    };
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PIN_REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = pin_store.refresh(&client).await {
+                eprintln!("⚠️ Pinned files refresh failed: {}", e);
+            }
+        }
+    });
+}
+
 fn install_systemd_user_service(service_name: &str, exec: &std::path::Path) -> Result<(), String> {
     let home = std::env::var("HOME").map_err(|e| format!("HOME not set: {}", e))?;
     let dir = format!("{}/.config/systemd/user", home);
@@ -17,10 +17,11 @@ pub async fn run(disabled_autorun: bool) {
     {
         let pids = String::from_utf8_lossy(&output.stdout);
         for pid in pids.lines() {
-            if let Ok(_) = std::process::Command::new("kill")
+            if std::process::Command::new("kill")
                 .arg("-9")
                 .arg(pid)
                 .output()
+                .is_ok()
             {
                 println!("🗑️ Terminato processo bifrost con PID {}", pid);
             }
@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+
+use bifrost::config::settings::Config;
+use bifrost::pin::store::PinStore;
+
+use super::pin::remote_path;
+
+pub async fn run(path: PathBuf) {
+    let config = match Config::from_file() {
+        Ok(cfg) => cfg,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let store = match PinStore::open() {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let remote = remote_path(&config, &path);
+    let shown = remote.to_string_lossy();
+    match store.unpin(&remote) {
+        Ok(()) => println!("\nUnpinned `{}`", shown),
+        Err(e) => {
+            eprintln!("\nFailed to unpin `{}`: {}", shown, e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::time::Duration;
+
+use crate::config::settings::ConfigError;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CachePolicy {
+    pub metadata_ttl: Duration,
+    pub metadata_tti: Duration,
+    pub read_buffer_entries: u64,
+    pub read_align: u64,
+    pub read_prefetch: u64,
+    pub stream_write: usize,
+    pub kernel_ttl: Duration,
+    pub negative_lookup_ttl: Duration,
+    pub negative_entry_ttl: Duration,
+    pub rules: Vec<CacheRule>,
+}
+
+// Overrides every cache lifetime for the paths matching `pattern`: a zero `ttl` means
+// the path is never cached. Patterns starting with '/' are matched against the whole
+// path, the others against the file name; `*` and `?` stay within one path component,
+// `**` spans any number of them
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CacheRule {
+    pub pattern: String,
+    pub ttl: Duration,
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        CachePolicy {
+            metadata_ttl: Duration::from_secs(3 * 60),
+            metadata_tti: Duration::from_secs(3 * 60),
+            read_buffer_entries: 512,
+            read_align: 4096,
+            read_prefetch: 2 * 1024 * 1024,
+            stream_write: 4 * 1024 * 1024,
+            kernel_ttl: Duration::from_secs(1),
+            negative_lookup_ttl: Duration::from_secs(30),
+            negative_entry_ttl: Duration::from_secs(1),
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl CachePolicy {
+    // Lifetime imposed by the first rule matching `path`, if any
+    pub fn rule_ttl(&self, path: &OsStr) -> Option<Duration> {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(path))
+            .map(|rule| rule.ttl)
+    }
+
+    pub fn is_cached(&self, path: &OsStr) -> bool {
+        self.rule_ttl(path).is_none_or(|ttl| !ttl.is_zero())
+    }
+
+    // Attribute and entry TTL handed to the kernel for `path`
+    pub fn kernel_ttl_for(&self, path: &OsStr) -> Duration {
+        self.rule_ttl(path).unwrap_or(self.kernel_ttl)
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.read_align == 0 || !self.read_align.is_power_of_two() {
+            return Err(ConfigError::Validation(
+                "Read alignment must be a power of two".to_string(),
+            ));
+        }
+
+        if self.read_prefetch < self.read_align {
+            return Err(ConfigError::Validation(
+                "Read prefetch cannot be smaller than the read alignment".to_string(),
+            ));
+        }
+
+        if self.stream_write == 0 {
+            return Err(ConfigError::Validation(
+                "Stream write size must be greater than 0".to_string(),
+            ));
+        }
+
+        if let Some(rule) = self.rules.iter().find(|rule| rule.pattern.is_empty()) {
+            return Err(ConfigError::Validation(format!(
+                "Cache rule with an empty pattern (ttl {:?})",
+                rule.ttl
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+impl CacheRule {
+    // Names are matched byte by byte, whatever their encoding
+    pub fn matches(&self, path: &OsStr) -> bool {
+        let path = path.as_bytes();
+        if self.pattern.starts_with('/') {
+            glob_matches(self.pattern.as_bytes(), path)
+        } else {
+            let name = path.rsplit(|&b| b == b'/').next().unwrap_or(path);
+            glob_matches(self.pattern.as_bytes(), name)
+        }
+    }
+}
+
+fn glob_matches(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some(b'*') if pattern.get(1) == Some(&b'*') => {
+            // `**/` also matches no directory at all
+            let rest = &pattern[2..];
+            if rest.first() == Some(&b'/') && glob_matches(&rest[1..], text) {
+                return true;
+            }
+            (0..=text.len()).any(|i| glob_matches(rest, &text[i..]))
+        }
+        Some(b'*') => {
+            let rest = &pattern[1..];
+            for i in 0..=text.len() {
+                if glob_matches(rest, &text[i..]) {
+                    return true;
+                }
+                if text.get(i) == Some(&b'/') {
+                    break;
+                }
+            }
+            false
+        }
+        Some(b'?') => {
+            matches!(text.first(), Some(&c) if c != b'/') && glob_matches(&pattern[1..], &text[1..])
+        }
+        Some(&c) => text.first() == Some(&c) && glob_matches(&pattern[1..], &text[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        CacheRule {
+            pattern: pattern.to_string(),
+            ttl: Duration::ZERO,
+        }
+        .matches(OsStr::new(path))
+    }
+
+    #[test]
+    fn relative_patterns_match_the_name() {
+        assert!(matches("*.log", "/var/app/today.log"));
+        assert!(matches("*.log", "/today.log"));
+        assert!(!matches("*.log", "/var/app.log/today"));
+        assert!(matches("?.tmp", "/dir/a.tmp"));
+        assert!(!matches("?.tmp", "/dir/ab.tmp"));
+    }
+
+    #[test]
+    fn absolute_patterns_match_the_whole_path() {
+        assert!(matches("/build/*", "/build/out"));
+        assert!(!matches("/build/*", "/src/build/out"));
+        assert!(!matches("/build", "/build/out"));
+    }
+
+    #[test]
+    fn single_wildcards_stay_within_a_component() {
+        assert!(!matches("/build/*", "/build/out/obj"));
+        assert!(!matches("/a?b", "/a/b"));
+    }
+
+    #[test]
+    fn double_wildcards_span_components() {
+        assert!(matches("/build/**", "/build/out/obj/x.o"));
+        assert!(matches("/**/*.o", "/x.o"));
+        assert!(matches("/**/*.o", "/build/out/x.o"));
+        assert!(matches("/src/**/mod.rs", "/src/mod.rs"));
+        assert!(!matches("/src/**/mod.rs", "/src/a/mod.rs.bak"));
+    }
+
+    #[test]
+    fn names_need_not_be_utf8() {
+        let rule = CacheRule {
+            pattern: "*.tmp".to_string(),
+            ttl: Duration::ZERO,
+        };
+        assert!(rule.matches(OsStr::from_bytes(b"/dir/\xff\xfe.tmp")));
+    }
+}
@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::config::settings::ConfigError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Http2Mode {
+    Negotiate,      // HTTP/2 when the server offers it through ALPN (TLS only)
+    PriorKnowledge, // HTTP/2 straight away, also over plain HTTP: the server must speak it
+    Off,            // HTTP/1.1 only
+}
+
+impl FromStr for Http2Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "negotiate" => Ok(Http2Mode::Negotiate),
+            "prior-knowledge" => Ok(Http2Mode::PriorKnowledge),
+            "off" => Ok(Http2Mode::Off),
+            other => Err(format!("unknown HTTP/2 mode `{}`", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ConnectionPolicy {
+    pub http2: Http2Mode,
+    pub pool_max_idle_per_host: usize,
+    pub pool_idle_timeout: Duration, // 0 keeps idle connections open for good
+    pub tcp_keepalive: Duration,     // 0 disables keep-alive probes
+    pub http2_keepalive: Duration,   // interval of the HTTP/2 pings, 0 disables them
+    pub tcp_nodelay: bool,
+    pub parallel_requests: usize, // sub-requests in flight for a single large operation
+    pub parallel_chunk: u64,      // size of the sub-requests a large read is split into
+}
+
+impl Default for ConnectionPolicy {
+    fn default() -> Self {
+        ConnectionPolicy {
+            http2: Http2Mode::Negotiate,
+            pool_max_idle_per_host: 32,
+            pool_idle_timeout: Duration::from_secs(90),
+            tcp_keepalive: Duration::from_secs(60),
+            http2_keepalive: Duration::from_secs(30),
+            tcp_nodelay: true,
+            parallel_requests: 4,
+            parallel_chunk: 512 * 1024, // the read prefetch in `parallel_requests` pieces
+        }
+    }
+}
+
+impl ConnectionPolicy {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.parallel_requests == 0 {
+            return Err(ConfigError::Validation(
+                "Parallel requests must be at least 1".to_string(),
+            ));
+        }
+
+        if self.parallel_chunk == 0 {
+            return Err(ConfigError::Validation(
+                "Parallel chunk size must be greater than 0".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::config::settings::ConfigError;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HealthPolicy {
+    pub failure_threshold: u32, // requests in a row left without answer before going offline
+    pub probe_interval: Duration,
+    pub connect_timeout: Duration,
+}
+
+impl Default for HealthPolicy {
+    fn default() -> Self {
+        HealthPolicy {
+            failure_threshold: 3,
+            probe_interval: Duration::from_secs(5),
+            connect_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl HealthPolicy {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.failure_threshold == 0 {
+            return Err(ConfigError::Validation(
+                "Failure threshold must be at least 1".to_string(),
+            ));
+        }
+
+        if self.probe_interval.is_zero() || self.connect_timeout.is_zero() {
+            return Err(ConfigError::Validation(
+                "Probe interval and connect timeout must be greater than 0".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
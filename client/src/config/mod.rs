@@ -1 +1,8 @@
-pub mod settings;
\ No newline at end of file
+pub mod cache;
+pub mod connection;
+pub mod health;
+pub mod proxy;
+pub mod retry;
+pub mod settings;
+pub mod tls;
+pub mod traffic;
\ No newline at end of file
@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::settings::ConfigError;
+
+const PROXY_SCHEMES: &[&str] = &["http", "https", "socks5", "socks5h"];
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ProxyPolicy {
+    pub url: Option<String>, // http(s):// or socks5(h):// proxy all requests go through
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub no_proxy: Vec<String>, // hosts, domains and CIDR blocks reached directly
+    pub use_env: bool,         // HTTPS_PROXY, HTTP_PROXY and NO_PROXY apply when no url is set
+}
+
+impl Default for ProxyPolicy {
+    fn default() -> Self {
+        ProxyPolicy {
+            url: None,
+            username: None,
+            password: None,
+            no_proxy: Vec::new(),
+            use_env: true,
+        }
+    }
+}
+
+impl ProxyPolicy {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if let Some(url) = &self.url {
+            let parsed = reqwest::Url::parse(url).map_err(|e| {
+                ConfigError::Validation(format!("Invalid proxy URL `{}`: {}", url, e))
+            })?;
+            if !PROXY_SCHEMES.contains(&parsed.scheme()) {
+                return Err(ConfigError::Validation(format!(
+                    "Unsupported proxy scheme `{}`, expected one of {}",
+                    parsed.scheme(),
+                    PROXY_SCHEMES.join(", ")
+                )));
+            }
+        }
+
+        if self.password.is_some() && self.username.is_none() {
+            return Err(ConfigError::Validation(
+                "Proxy password given without a username".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
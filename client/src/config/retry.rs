@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::config::settings::ConfigError;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    pub max_attempts: u32, // first attempt included: 1 never retries
+    pub base_delay: Duration,
+    pub max_delay: Duration,       // also the longest Retry-After waited for
+    pub attempt_timeout: Duration, // longest wait for an answer, within the timeout
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(10),
+            attempt_timeout: Duration::from_secs(20),
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Wait before the attempt following `attempt` (1 for the first): a random share
+    // of an exponentially growing delay, so that clients failing together spread out
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .base_delay
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+            .min(self.max_delay);
+        ceiling.mul_f64(rand::random::<f64>())
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.max_attempts == 0 {
+            return Err(ConfigError::Validation(
+                "Attempts per request must be at least 1".to_string(),
+            ));
+        }
+
+        if self.attempt_timeout.is_zero() {
+            return Err(ConfigError::Validation(
+                "Attempt timeout must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.base_delay > self.max_delay {
+            return Err(ConfigError::Validation(
+                "Retry base delay cannot exceed the maximum delay".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -3,6 +3,14 @@ use std::fs;
 use std::path::PathBuf;
 use std::time::Duration;
 
+use crate::config::cache::CachePolicy;
+use crate::config::connection::ConnectionPolicy;
+use crate::config::health::HealthPolicy;
+use crate::config::proxy::ProxyPolicy;
+use crate::config::retry::RetryPolicy;
+use crate::config::tls::TlsPolicy;
+use crate::config::traffic::TrafficPolicy;
+
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigError {
     #[error("\nNo configuration file found.\nRun `bifrost config` to create one.")]
@@ -31,6 +39,24 @@ pub struct Config {
     pub timeout: Duration,
     pub mount_point: PathBuf,
     pub api_key: Option<String>,
+    #[serde(default)]
+    pub cache: CachePolicy,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    #[serde(default)]
+    pub health: HealthPolicy,
+    #[serde(default)]
+    pub traffic: TrafficPolicy,
+    #[serde(default)]
+    pub connection: ConnectionPolicy,
+    #[serde(default)]
+    pub tls: TlsPolicy,
+    #[serde(default)]
+    pub proxy: ProxyPolicy,
+    #[serde(default)]
+    pub default_permissions: bool, // permission checks done by the kernel instead of bifrost
+    #[serde(default)]
+    pub strict_integrity: bool, // unsigned server responses rejected rather than accepted
 }
 
 impl Default for Config {
@@ -41,6 +67,15 @@ impl Default for Config {
             mount_point: PathBuf::from("/mnt/bifrost"),
             timeout: Duration::from_secs(60),
             api_key: None,
+            cache: CachePolicy::default(),
+            retry: RetryPolicy::default(),
+            health: HealthPolicy::default(),
+            traffic: TrafficPolicy::default(),
+            connection: ConnectionPolicy::default(),
+            tls: TlsPolicy::default(),
+            proxy: ProxyPolicy::default(),
+            default_permissions: false,
+            strict_integrity: false,
         }
     }
 }
@@ -62,7 +97,7 @@ impl Config {
             return Err(ConfigError::NotFound);
         }
 
-        let content = std::fs::read_to_string(config_path).map_err(|e| ConfigError::FileRead(e))?;
+        let content = std::fs::read_to_string(config_path).map_err(ConfigError::FileRead)?;
 
         let config: Config =
             toml::from_str(&content).map_err(|e| ConfigError::Parse(e.to_string()))?;
@@ -75,7 +110,7 @@ impl Config {
         let content =
             toml::to_string_pretty(self).map_err(|e| ConfigError::Serialize(e.to_string()))?;
 
-        std::fs::write(Self::default_path(), content).map_err(|e| ConfigError::FileWrite(e))?;
+        std::fs::write(Self::default_path(), content).map_err(ConfigError::FileWrite)?;
 
         Ok(())
     }
@@ -105,6 +140,14 @@ impl Config {
             ));
         }
 
+        self.cache.validate()?;
+        self.retry.validate()?;
+        self.health.validate()?;
+        self.traffic.validate()?;
+        self.connection.validate()?;
+        self.tls.validate()?;
+        self.proxy.validate()?;
+
         Ok(())
     }
 
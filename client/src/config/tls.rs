@@ -0,0 +1,115 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::config::settings::ConfigError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum TlsVersion {
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+impl FromStr for TlsVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "1.2" => Ok(TlsVersion::Tls12),
+            "1.3" => Ok(TlsVersion::Tls13),
+            other => Err(format!("unsupported TLS version `{}`", other)),
+        }
+    }
+}
+
+// SHA-256 the certificate of the server has to match, written as
+// `spki-sha256:<hex>` (its public key, which survives renewals with the same key)
+// or `cert-sha256:<hex>` (the whole certificate, in DER form)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pin {
+    Spki([u8; 32]),
+    Certificate([u8; 32]),
+}
+
+impl FromStr for Pin {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "invalid pin `{}`, expected spki-sha256:<hex> or cert-sha256:<hex>",
+                s
+            )
+        };
+        let (kind, digest) = s.trim().split_once(':').ok_or_else(invalid)?;
+        let mut bytes = [0u8; 32];
+        hex::decode_to_slice(digest, &mut bytes).map_err(|_| invalid())?;
+        match kind {
+            "spki-sha256" => Ok(Pin::Spki(bytes)),
+            "cert-sha256" => Ok(Pin::Certificate(bytes)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TlsPolicy {
+    pub ca_bundle: Option<PathBuf>, // PEM certificates trusted besides the public roots
+    pub client_cert: Option<PathBuf>, // PEM chain presented to servers asking for one
+    pub client_key: Option<PathBuf>, // PEM private key of the client certificate
+    pub pins: Vec<String>,          // any of them has to match, see Pin
+    pub min_version: TlsVersion,
+}
+
+impl Default for TlsPolicy {
+    fn default() -> Self {
+        TlsPolicy {
+            ca_bundle: None,
+            client_cert: None,
+            client_key: None,
+            pins: Vec::new(),
+            min_version: TlsVersion::Tls12,
+        }
+    }
+}
+
+impl TlsPolicy {
+    // Whether anything differs from the defaults of the HTTP client
+    pub fn is_custom(&self) -> bool {
+        self.ca_bundle.is_some()
+            || self.client_cert.is_some()
+            || !self.pins.is_empty()
+            || self.min_version != TlsVersion::Tls12
+    }
+
+    pub fn parsed_pins(&self) -> Result<Vec<Pin>, String> {
+        self.pins.iter().map(|pin| pin.parse()).collect()
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.client_cert.is_some() != self.client_key.is_some() {
+            return Err(ConfigError::Validation(
+                "Client certificate and client key must be given together".to_string(),
+            ));
+        }
+
+        for path in [&self.ca_bundle, &self.client_cert, &self.client_key]
+            .into_iter()
+            .flatten()
+        {
+            if !path.is_file() {
+                return Err(ConfigError::Validation(format!(
+                    "TLS file `{}` does not exist",
+                    path.display()
+                )));
+            }
+        }
+
+        self.parsed_pins().map_err(ConfigError::Validation)?;
+
+        Ok(())
+    }
+}
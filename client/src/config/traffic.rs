@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+
+use crate::config::settings::ConfigError;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TrafficPolicy {
+    pub upload_limit: u64,         // bytes per second, 0 for no limit
+    pub download_limit: u64,       // bytes per second, 0 for no limit
+    pub bulk_threshold: u64,       // transfers from this many bytes up are bulk traffic
+    pub max_bulk_transfers: usize, // bulk transfers in flight at the same time
+}
+
+impl Default for TrafficPolicy {
+    fn default() -> Self {
+        TrafficPolicy {
+            upload_limit: 0,
+            download_limit: 0,
+            bulk_threshold: 256 * 1024,
+            max_bulk_transfers: 2,
+        }
+    }
+}
+
+impl TrafficPolicy {
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.max_bulk_transfers == 0 {
+            return Err(ConfigError::Validation(
+                "Concurrent bulk transfers must be at least 1".to_string(),
+            ));
+        }
+
+        if self.bulk_threshold == 0 {
+            return Err(ConfigError::Validation(
+                "Bulk transfer threshold must be greater than 0".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -2,14 +2,14 @@ use chrono::DateTime;
 use fuser::{FileAttr, FileType};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::api::models::MetaFile;
+use crate::api::models::{FileKind, MetaFile};
 
 pub fn new_file_attr(ino: u64, size: u64, permission_octal: u32) -> FileAttr {
     let now = SystemTime::now();
     FileAttr {
         ino,
         size,
-        blocks: (size + 511) / 512,
+        blocks: size.div_ceil(512),
         atime: now,
         mtime: now,
         ctime: now,
@@ -45,8 +45,40 @@ pub fn new_directory_attr(ino: u64, permission_octal: u32) -> FileAttr {
         blksize: 4096,
     }
 }
-fn parse_permissions(perm: &str) -> u16 {
-    if perm.len() == 3 && perm.chars().all(|c| c.is_ascii_digit() && c <= '7') {
+// ino 0 tells the kernel to cache the name as a negative dentry for the entry TTL
+pub fn new_negative_entry_attr() -> FileAttr {
+    let mut attr = new_file_attr(0, 0, 0);
+    attr.nlink = 0;
+    attr
+}
+
+// Permission bits kept for created objects: rwx for everyone plus setuid, setgid
+// and sticky
+pub const MODE_BITS: u32 = 0o7777;
+
+// Mode a creation asks for, once the caller's umask is applied
+pub fn effective_mode(mode: u32, umask: u32) -> u32 {
+    mode & MODE_BITS & !(umask & 0o777)
+}
+
+// umask of the process `pid`, for the creations where the kernel does not provide
+// one; the usual 022 when it cannot be read (the process is gone, or from a kernel
+// before 4.7)
+pub fn caller_umask(pid: u32) -> u32 {
+    std::fs::read_to_string(format!("/proc/{}/status", pid))
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix("Umask:"))
+                .and_then(|umask| u32::from_str_radix(umask.trim(), 8).ok())
+        })
+        .unwrap_or(0o022)
+}
+
+pub fn parse_permissions(perm: &str) -> u16 {
+    if (perm.len() == 3 || perm.len() == 4) && perm.chars().all(|c| c.is_ascii_digit() && c <= '7')
+    {
         return u16::from_str_radix(perm, 8).unwrap_or(0o644);
     }
 
@@ -77,7 +109,7 @@ fn parse_permissions(perm: &str) -> u16 {
 fn symbolic_to_octal(symbolic: &str) -> u16 {
     let mut octal = 0u16;
 
-    if symbolic.chars().nth(0) == Some('r') {
+    if symbolic.starts_with('r') {
         octal += 0o400;
     }
     if symbolic.chars().nth(1) == Some('w') {
@@ -110,6 +142,20 @@ fn symbolic_to_octal(symbolic: &str) -> u16 {
     octal
 }
 
+// hard links appear as regular files
+pub fn file_type(kind: FileKind) -> FileType {
+    match kind {
+        FileKind::RegularFile => FileType::RegularFile,
+        FileKind::Directory => FileType::Directory,
+        FileKind::Symlink => FileType::Symlink,
+        FileKind::Hardlink => FileType::RegularFile,
+        FileKind::Fifo => FileType::NamedPipe,
+        FileKind::Socket => FileType::Socket,
+        FileKind::CharDevice => FileType::CharDevice,
+        FileKind::BlockDevice => FileType::BlockDevice,
+    }
+}
+
 pub fn from_metadata(new_inode: u64, metadata: &MetaFile) -> FileAttr {
     let parse_timestamp = |timestamp_str: &str| -> SystemTime {
         if let Ok(dt) = DateTime::parse_from_rfc3339(timestamp_str) {
@@ -128,23 +174,18 @@ pub fn from_metadata(new_inode: u64, metadata: &MetaFile) -> FileAttr {
     FileAttr {
         ino: new_inode,
         size: metadata.size,
-        blocks: (metadata.size + 511) / 512,
+        blocks: metadata.size.div_ceil(512),
         atime: parse_timestamp(&metadata.atime),
         mtime: parse_timestamp(&metadata.mtime),
         ctime: parse_timestamp(&metadata.ctime),
         crtime: parse_timestamp(&metadata.crtime),
-        kind: match metadata.kind {
-            crate::api::models::FileKind::RegularFile => FileType::RegularFile,
-            crate::api::models::FileKind::Directory => FileType::Directory,
-            crate::api::models::FileKind::Symlink => FileType::Symlink,
-            crate::api::models::FileKind::Hardlink => FileType::RegularFile,
-        },
+        kind: file_type(metadata.kind),
         perm: parse_permissions(&metadata.perm),
         nlink: metadata.nlink,
         uid: unsafe { libc::getuid() },
         gid: unsafe { libc::getgid() },
-        rdev: 0,
-        flags: 0,
+        rdev: metadata.rdev,
+        flags: metadata.flags,
         blksize: 4096,
     }
 }
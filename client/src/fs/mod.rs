@@ -1,2 +1,3 @@
 pub mod attributes;
-pub mod operations;
\ No newline at end of file
+pub mod operations;
+pub mod unlinked;
\ No newline at end of file
@@ -2,19 +2,36 @@
 
 use crate::api::client::{ClientError, RemoteClient};
 use crate::api::models::*;
-use crate::fs::attributes::{self, new_directory_attr, new_file_attr};
-use fuser::consts::FOPEN_DIRECT_IO;
+use crate::fs::attributes::{
+    self, caller_umask, effective_mode, new_directory_attr, new_file_attr, MODE_BITS,
+};
+use crate::fs::unlinked::{
+    is_unlinked_name, unlinked_age, unlinked_name, UnlinkedJournal, UNLINKED_MAX_AGE,
+};
+use crate::util::path::{get_file_name, get_parent_path, join_path};
+use fuser::consts::{FOPEN_DIRECT_IO, FUSE_DO_READDIRPLUS};
 use fuser::{
-    FileType, Filesystem, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry, ReplyOpen, Request,
+    FileAttr, FileType, Filesystem, ReplyAttr, ReplyData, ReplyDirectory, ReplyDirectoryPlus,
+    ReplyEntry, ReplyOpen, Request,
 };
 use std::collections::HashMap;
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::OsStrExt;
 use std::time::{Duration, SystemTime};
 
-const STREAM_WRITE: usize = 4 * 1024 * 1024; // 4MB
+const READDIR_PAGE_SIZE: usize = 1024;
+const COPY_RANGE_MAX: u64 = 256 * 1024 * 1024; // per server-side copy request
+const ZERO_BLOCK: usize = 4096; // smallest all-zero write left to the server to fill
+const SHA256_XATTR: &str = "user.bifrost.sha256"; // read-only, hex digest of the content
+
+// lsattr/chattr requests; the 32-bit variants carry the same int
+const FS_IOC_GETFLAGS: u32 = 0x8008_6601;
+const FS_IOC_SETFLAGS: u32 = 0x4008_6602;
+const FS_IOC32_GETFLAGS: u32 = 0x8004_6601;
+const FS_IOC32_SETFLAGS: u32 = 0x4004_6602;
 pub struct RemoteFileSystem {
-    inode_to_path: HashMap<u64, String>,
-    path_to_inode: HashMap<String, u64>,
+    inode_to_path: HashMap<u64, OsString>,
+    path_to_inode: HashMap<OsString, u64>,
     next_inode: u64,
 
     client: RemoteClient,
@@ -24,6 +41,8 @@ pub struct RemoteFileSystem {
 
     open_dirs: HashMap<u64, OpenDir>,
     file_locks: HashMap<u64, Vec<FileLock>>, // inode -> locks
+    unlinked: UnlinkedJournal,               // open files unlinked under a hidden name
+    default_permissions: bool,               // permission checks left to the kernel
 }
 
 struct FileLock {
@@ -34,37 +53,116 @@ struct FileLock {
     lock_owner: u64,
 }
 
+// Window over the listing of an open directory. Offsets are stable for the handle:
+// "." is 1, ".." is 2 and the i-th child (in the order of the server) is 3 + i
 struct OpenDir {
-    path: String,
+    path: OsString,
     flags: i32,
+    page: Vec<MetaFile>,
+    page_start: usize, // index of the first child in `page`
+    next_cursor: Option<String>,
 }
 
 struct OpenFile {
-    path: String,
+    path: OsString,
     flags: i32,
     write_buffer: Vec<u8>,
     buffer_dirty: bool, // points out if the buffer must be flushed
 }
 
-struct Permissions {
-    owner: u32,
-    group: u32,
-    other: u32,
+// The server may store other bits than asked for (e.g. it drops setgid when its
+// user is not in the group): the attributes handed out are the stored ones
+fn check_stored_mode(tag: &str, path: &OsStr, requested: u32, metadata: &MetaFile) {
+    let stored = attributes::parse_permissions(&metadata.perm) as u32;
+    if stored != requested {
+        log::warn!(
+            "⚠️ [{}] Permessi richiesti {:o}, salvati {:o}: {}",
+            tag,
+            requested,
+            stored,
+            path.to_string_lossy()
+        );
+    }
+}
+
+// Supplementary groups of the process behind a request
+fn caller_groups(pid: u32) -> Vec<u32> {
+    std::fs::read_to_string(format!("/proc/{}/status", pid))
+        .ok()
+        .and_then(|status| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix("Groups:"))
+                .map(|groups| {
+                    groups
+                        .split_whitespace()
+                        .filter_map(|g| g.parse().ok())
+                        .collect()
+                })
+        })
+        .unwrap_or_default()
 }
 
-fn parse_permissions(perm_str: &str) -> Permissions {
-    match u32::from_str_radix(perm_str, 8) {
-        Ok(perms) => Permissions {
-            owner: (perms >> 6) & 0o7,
-            group: (perms >> 3) & 0o7,
-            other: perms & 0o7,
-        },
-        Err(_) => Permissions {
-            owner: 0o6, // Default read+write
-            group: 0o4, // Default read
-            other: 0o4, // Default read
-        },
+// Every entry belongs to the user running the mount (see attributes::from_metadata)
+fn is_owner(req: &Request<'_>) -> bool {
+    req.uid() == 0 || req.uid() == unsafe { libc::getuid() }
+}
+
+// POSIX evaluation of `mask` (R_OK, W_OK, X_OK) for the caller of `req` against the
+// owner, group or other bits of an entry. The server keeps no ACLs, so the mode
+// bits are all there is to evaluate
+fn permission_granted(req: &Request<'_>, metadata: &MetaFile, mask: i32) -> bool {
+    let mode = attributes::parse_permissions(&metadata.perm) as u32;
+    let wanted = (mask & (libc::R_OK | libc::W_OK | libc::X_OK)) as u32;
+
+    if req.uid() == 0 {
+        // root is only refused the execution of files nobody can execute
+        return (wanted & 0o1) == 0 || metadata.kind == FileKind::Directory || (mode & 0o111) != 0;
     }
+
+    let owner_gid = unsafe { libc::getgid() };
+    let granted = if req.uid() == unsafe { libc::getuid() } {
+        (mode >> 6) & 0o7
+    } else if req.gid() == owner_gid || caller_groups(req.pid()).contains(&owner_gid) {
+        (mode >> 3) & 0o7
+    } else {
+        mode & 0o7
+    };
+    (granted & wanted) == wanted
+}
+
+// Immutable and append-only files cannot be renamed, replaced, linked or deleted
+fn is_locked(metadata: &MetaFile) -> bool {
+    (metadata.flags & (FS_IMMUTABLE_FL | FS_APPEND_FL)) != 0
+}
+
+// open(2) of a flagged file: an immutable one only for reading, an append-only one
+// for reading or with O_APPEND, never with O_TRUNC
+fn flags_permit_open(file_flags: u32, open_flags: i32) -> bool {
+    let writing = (open_flags & libc::O_ACCMODE) != libc::O_RDONLY;
+    if (open_flags & libc::O_TRUNC) != 0 && (file_flags & (FS_IMMUTABLE_FL | FS_APPEND_FL)) != 0 {
+        return false;
+    }
+    if (file_flags & FS_IMMUTABLE_FL) != 0 {
+        return !writing;
+    }
+    if (file_flags & FS_APPEND_FL) != 0 {
+        return !writing || (open_flags & libc::O_APPEND) != 0;
+    }
+    true
+}
+
+// Access needed by open(2) for `flags`
+fn open_mask(flags: i32) -> i32 {
+    let mut mask = match flags & libc::O_ACCMODE {
+        libc::O_RDONLY => libc::R_OK,
+        libc::O_WRONLY => libc::W_OK,
+        _ => libc::R_OK | libc::W_OK,
+    };
+    if (flags & libc::O_TRUNC) != 0 {
+        mask |= libc::W_OK;
+    }
+    mask
 }
 
 fn ranges_overlap(start1: u64, end1: u64, start2: u64, end2: u64) -> bool {
@@ -77,6 +175,7 @@ fn locks_conflict(typ1: i32, typ2: i32) -> bool {
 
 impl RemoteFileSystem {
     pub fn new(client: RemoteClient) -> Self {
+        let unlinked = UnlinkedJournal::open(client.server_url());
         let mut fs = Self {
             inode_to_path: HashMap::new(),
             path_to_inode: HashMap::new(),
@@ -86,25 +185,34 @@ impl RemoteFileSystem {
             next_fh: 1,
             open_dirs: HashMap::new(),
             file_locks: HashMap::new(),
+            unlinked,
+            default_permissions: false,
         };
 
-        fs.inode_to_path.insert(1, "/".to_string());
-        fs.path_to_inode.insert("/".to_string(), 1);
+        fs.inode_to_path.insert(1, OsString::from("/"));
+        fs.path_to_inode.insert(OsString::from("/"), 1);
 
         fs
     }
 
+    // With `default_permissions` the kernel checks the mode bits itself before every
+    // request, and bifrost does not repeat the checks
+    pub fn with_default_permissions(mut self, enabled: bool) -> Self {
+        self.default_permissions = enabled;
+        self
+    }
+
     fn generate_inode(&mut self) -> u64 {
         let inode = self.next_inode;
         self.next_inode += 1;
         inode
     }
 
-    fn get_path(&self, inode: u64) -> Option<&String> {
+    fn get_path(&self, inode: u64) -> Option<&OsString> {
         self.inode_to_path.get(&inode)
     }
 
-    fn register_inode(&mut self, inode: u64, path: String) {
+    fn register_inode(&mut self, inode: u64, path: OsString) {
         self.inode_to_path.insert(inode, path.clone());
         self.path_to_inode.insert(path, inode);
     }
@@ -115,7 +223,7 @@ impl RemoteFileSystem {
         }
     }
 
-    fn remove_path_mapping(&mut self, path: &str) {
+    fn remove_path_mapping(&mut self, path: &OsStr) {
         if let Some(inode) = self.path_to_inode.remove(path) {
             if let Some(current) = self.inode_to_path.get(&inode).cloned() {
                 if current == path {
@@ -131,7 +239,7 @@ impl RemoteFileSystem {
         }
     }
 
-    fn get_current_attributes(&mut self, ino: u64, path: &str, reply: ReplyAttr) {
+    fn get_current_attributes(&mut self, ino: u64, path: &OsStr, reply: ReplyAttr) {
         let rt = match tokio::runtime::Handle::try_current() {
             Ok(handle) => handle,
             Err(_) => {
@@ -143,7 +251,7 @@ impl RemoteFileSystem {
         match rt.block_on(async { self.client.get_file_metadata(path).await }) {
             Ok(metadata) => {
                 let attr = attributes::from_metadata(ino, &metadata);
-                let ttl = Duration::from_secs(1); // Cache TTL
+                let ttl = self.kernel_ttl(path);
 
                 reply.attr(&ttl, &attr);
             }
@@ -152,6 +260,456 @@ impl RemoteFileSystem {
             }
         }
     }
+
+    // Sends the appends still buffered for `fh`, so that the server holds the whole file
+    fn flush_write_buffer(&mut self, fh: u64) -> Result<(), ClientError> {
+        let (path, data) = match self.open_files.get(&fh) {
+            Some(file) if file.buffer_dirty && !file.write_buffer.is_empty() => {
+                (file.path.clone(), file.write_buffer.clone())
+            }
+            _ => return Ok(()),
+        };
+
+        let rt = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => {
+                let runtime = tokio::runtime::Runtime::new().expect("Failed to create runtime");
+                runtime.handle().clone()
+            }
+        };
+        let metadata = rt.block_on(async { self.client.get_file_metadata(&path).await })?;
+
+        let now_iso = chrono::Utc::now().to_rfc3339();
+        let write_request = WriteRequest {
+            offset: None,
+            path,
+            new_path: None,
+            size: data.len() as u64,
+            atime: metadata.atime,
+            mtime: now_iso.clone(),
+            ctime: now_iso,
+            crtime: metadata.crtime,
+            kind: metadata.kind,
+            ref_path: metadata.ref_path,
+            perm: metadata.perm,
+            mode: Mode::Append,
+            data: Some(data),
+        };
+        rt.block_on(async { self.client.write_file(&write_request).await })?;
+
+        if let Some(file) = self.open_files.get_mut(&fh) {
+            file.write_buffer.clear();
+            file.buffer_dirty = false;
+        }
+        Ok(())
+    }
+
+    // Writes `len` zeroes at `offset` without uploading them: the server fills the
+    // range itself, extending the file when the range goes past its end
+    fn write_zeroes(
+        &mut self,
+        fh: u64,
+        path: &OsStr,
+        offset: u64,
+        len: u64,
+    ) -> Result<(), ClientError> {
+        self.flush_write_buffer(fh)?;
+
+        let rt = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => {
+                let runtime = tokio::runtime::Runtime::new().expect("Failed to create runtime");
+                runtime.handle().clone()
+            }
+        };
+        let fallocate_request = FallocateRequest {
+            operation: AllocateOperation::ZeroRange,
+            offset,
+            length: len,
+            keep_size: false,
+        };
+        rt.block_on(async { self.client.fallocate(path, &fallocate_request).await })
+    }
+
+    // Unlink of a file that is still open: it is moved to a hidden name in the same
+    // directory, where the handles keep working until the last release deletes it
+    fn silly_rename(&mut self, path: &OsStr) -> Result<(), ClientError> {
+        let rt = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => {
+                let runtime = tokio::runtime::Runtime::new().expect("Failed to create runtime");
+                runtime.handle().clone()
+            }
+        };
+        let metadata = rt.block_on(async { self.client.get_file_metadata(path).await })?;
+
+        let parent_path = get_parent_path(path);
+        let hidden_path = join_path(&parent_path, &unlinked_name());
+
+        // journaled first: a crash after the rename must not leave an untracked orphan
+        if let Err(e) = self.unlinked.add(&hidden_path) {
+            log::warn!(
+                "⚠️ [UNLINK] Impossibile registrare {}: {}",
+                hidden_path.to_string_lossy(),
+                e
+            );
+        }
+
+        let now_iso = chrono::Utc::now().to_rfc3339();
+        let rename_request = WriteRequest {
+            offset: None,
+            path: path.to_os_string(),
+            new_path: Some(hidden_path.clone()),
+            size: metadata.size,
+            atime: metadata.atime.clone(),
+            mtime: metadata.mtime.clone(),
+            ctime: now_iso,
+            crtime: metadata.crtime.clone(),
+            kind: metadata.kind,
+            ref_path: None,
+            perm: metadata.perm.clone(),
+            mode: Mode::Write,
+            data: None,
+        };
+        if let Err(e) = rt.block_on(async { self.client.write_file(&rename_request).await }) {
+            let _ = self.unlinked.remove(&hidden_path);
+            return Err(e);
+        }
+
+        for file in self.open_files.values_mut() {
+            if file.path == path {
+                file.path = hidden_path.clone();
+            }
+        }
+        // the inode stays reachable for fstat on the open handles
+        if let Some(inode) = self.path_to_inode.remove(path) {
+            self.register_inode(inode, hidden_path);
+        }
+        Ok(())
+    }
+
+    // Hidden files neither renamed nor modified for UNLINKED_MAX_AGE were left behind
+    // by a client that is gone, possibly on another host whose journal is out of
+    // reach: they are deleted when a listing comes across them
+    fn collect_stale_unlinked(&mut self, parent: &OsStr, entry: &MetaFile) {
+        let modified = chrono::DateTime::parse_from_rfc3339(&entry.mtime)
+            .ok()
+            .and_then(|mtime| {
+                (chrono::Utc::now() - mtime.with_timezone(&chrono::Utc))
+                    .to_std()
+                    .ok()
+            });
+        let stale = |age: Duration| age > UNLINKED_MAX_AGE;
+        if !unlinked_age(&entry.name).is_some_and(stale) || !modified.is_some_and(stale) {
+            return;
+        }
+        let path = join_path(parent, &entry.name);
+        if self.open_files.values().any(|file| file.path == path) {
+            return;
+        }
+
+        let rt = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => {
+                let runtime = tokio::runtime::Runtime::new().expect("Failed to create runtime");
+                runtime.handle().clone()
+            }
+        };
+        match rt.block_on(async { self.client.delete(&path).await }) {
+            Ok(()) | Err(ClientError::NotFound { .. }) => {
+                log::info!("[READDIR] Eliminato file orfano {}", path.to_string_lossy());
+                let _ = self.unlinked.remove(&path);
+            }
+            Err(e) => log::warn!(
+                "⚠️ [READDIR] Impossibile eliminare il file orfano {}: {}",
+                path.to_string_lossy(),
+                e
+            ),
+        }
+    }
+
+    // Deletes a silly-renamed file once no handle refers to it anymore
+    fn reap_unlinked(&mut self, path: &OsStr) {
+        if !is_unlinked_name(&get_file_name(path))
+            || self.open_files.values().any(|file| file.path == path)
+        {
+            return;
+        }
+
+        let rt = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => {
+                let runtime = tokio::runtime::Runtime::new().expect("Failed to create runtime");
+                runtime.handle().clone()
+            }
+        };
+        match rt.block_on(async { self.client.delete(path).await }) {
+            Ok(()) | Err(ClientError::NotFound { .. }) => {
+                self.remove_path_mapping(path);
+                if let Err(e) = self.unlinked.remove(path) {
+                    log::warn!("⚠️ [RELEASE] Impossibile aggiornare il registro: {}", e);
+                }
+            }
+            Err(e) => {
+                // stays in the journal, the next mount deletes it
+                eprintln!(
+                    "❌ [RELEASE] Errore eliminazione di {}: {}",
+                    path.to_string_lossy(),
+                    e
+                );
+            }
+        }
+    }
+
+    // Checks `mask` on the entry at `path` for the caller of `req`
+    fn check_access(&self, req: &Request<'_>, path: &OsStr, mask: i32) -> Result<(), libc::c_int> {
+        if self.default_permissions {
+            return Ok(());
+        }
+
+        let rt = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => {
+                let runtime = tokio::runtime::Runtime::new().expect("Failed to create runtime");
+                runtime.handle().clone()
+            }
+        };
+        let metadata = match rt.block_on(async { self.client.get_file_metadata(path).await }) {
+            Ok(metadata) => metadata,
+            Err(ClientError::NotFound { .. }) => return Err(libc::ENOENT),
+            Err(_) => return Err(libc::EIO),
+        };
+
+        if permission_granted(req, &metadata, mask) {
+            Ok(())
+        } else {
+            Err(libc::EACCES)
+        }
+    }
+
+    // Removing or replacing an entry of `parent_path` needs write and search on the
+    // directory; with the sticky bit set, only the owner of the entry may do it
+    fn check_remove(&self, req: &Request<'_>, parent_path: &OsStr) -> Result<(), libc::c_int> {
+        if self.default_permissions {
+            return Ok(());
+        }
+
+        let rt = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => {
+                let runtime = tokio::runtime::Runtime::new().expect("Failed to create runtime");
+                runtime.handle().clone()
+            }
+        };
+        let parent = match rt.block_on(async { self.client.get_file_metadata(parent_path).await }) {
+            Ok(metadata) => metadata,
+            Err(ClientError::NotFound { .. }) => return Err(libc::ENOENT),
+            Err(_) => return Err(libc::EIO),
+        };
+
+        if !permission_granted(req, &parent, libc::W_OK | libc::X_OK) {
+            return Err(libc::EACCES);
+        }
+        let mode = attributes::parse_permissions(&parent.perm) as u32;
+        if (mode & libc::S_ISVTX as u32) != 0 && !is_owner(req) {
+            return Err(libc::EPERM);
+        }
+        Ok(())
+    }
+
+    // EPERM if the entry at `path` is immutable or append-only
+    fn check_not_locked(&self, path: &OsStr) -> Result<(), libc::c_int> {
+        let rt = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => {
+                let runtime = tokio::runtime::Runtime::new().expect("Failed to create runtime");
+                runtime.handle().clone()
+            }
+        };
+        match rt.block_on(async { self.client.get_file_metadata(path).await }) {
+            Ok(metadata) if is_locked(&metadata) => Err(libc::EPERM),
+            Ok(_) | Err(ClientError::NotFound { .. }) => Ok(()),
+            Err(_) => Err(libc::EIO),
+        }
+    }
+
+    // chattr: only root may change the flags (CAP_LINUX_IMMUTABLE on Linux), and
+    // only the ones the server keeps; what it asks for replaces the flags, clearing
+    // included
+    fn set_file_flags(
+        &self,
+        req: &Request<'_>,
+        path: &OsStr,
+        flags: u32,
+    ) -> Result<(), libc::c_int> {
+        if req.uid() != 0 {
+            return Err(libc::EPERM);
+        }
+        if (flags & !(FS_IMMUTABLE_FL | FS_APPEND_FL)) != 0 {
+            log::warn!(
+                "⚠️ [FLAGS] Flag non supportati {:#x}: {}",
+                flags,
+                path.to_string_lossy()
+            );
+            return Err(libc::EOPNOTSUPP);
+        }
+
+        let rt = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => {
+                let runtime = tokio::runtime::Runtime::new().expect("Failed to create runtime");
+                runtime.handle().clone()
+            }
+        };
+        match rt.block_on(async { self.client.set_flags(path, flags, true).await }) {
+            Ok(()) => Ok(()),
+            Err(ClientError::NotFound { .. }) => Err(libc::ENOENT),
+            // directories and links keep no flags on the server
+            Err(ClientError::Server { status: 400, .. })
+            | Err(ClientError::PermissionDenied(_)) => Err(libc::EOPNOTSUPP),
+            Err(e) => {
+                eprintln!(
+                    "❌ [FLAGS] Errore impostazione flag per {}: {}",
+                    path.to_string_lossy(),
+                    e
+                );
+                Err(libc::EIO)
+            }
+        }
+    }
+
+    // TTL of the attributes and entries handed to the kernel for `path`
+    fn kernel_ttl(&self, path: &OsStr) -> Duration {
+        self.client.cache_policy().kernel_ttl_for(path)
+    }
+
+    // TTL of the negative entry of `path`: never longer than its rule allows, so that
+    // names created under an uncached directory show up as soon as other entries do
+    fn negative_ttl(&self, path: &OsStr) -> Duration {
+        let policy = self.client.cache_policy();
+        policy.kernel_ttl_for(path).min(policy.negative_entry_ttl)
+    }
+
+    fn fetch_directory_page(
+        &self,
+        path: &OsStr,
+        cursor: Option<&str>,
+    ) -> Result<ListingPage, ClientError> {
+        let rt = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => {
+                let runtime = tokio::runtime::Runtime::new().expect("Failed to create runtime");
+                runtime.handle().clone()
+            }
+        };
+        rt.block_on(async {
+            self.client
+                .list_directory_page(path, cursor, READDIR_PAGE_SIZE)
+                .await
+        })
+    }
+
+    // Entries of the open directory `fh` that follow `offset` ("." and ".." included),
+    // at most one page of children, paired with their own offset and registering an
+    // inode for every child. Attributes come from the listing itself, so that
+    // readdirplus can prime the kernel without a lookup per entry
+    fn directory_entries(
+        &mut self,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+    ) -> Result<Vec<(i64, u64, OsString, FileAttr)>, ClientError> {
+        let path = match self.open_dirs.get(&fh) {
+            Some(dir) => dir.path.clone(),
+            None => return Ok(Vec::new()),
+        };
+
+        let mut entries = Vec::new();
+
+        if offset < 1 {
+            entries.push((1, ino, OsString::from("."), new_directory_attr(ino, 0o755)));
+        }
+        if offset < 2 {
+            let parent_ino = if path == "/" {
+                1 // Root directory
+            } else {
+                let parent_path = get_parent_path(&path);
+
+                self.path_to_inode.get(&parent_path).copied().unwrap_or(1)
+            };
+            entries.push((
+                2,
+                parent_ino,
+                OsString::from(".."),
+                new_directory_attr(parent_ino, 0o755),
+            ));
+        }
+
+        // move the window until it holds the first child wanted
+        let wanted = (offset.max(2) - 2) as usize;
+        loop {
+            let (page_start, page_len, next_cursor) = match self.open_dirs.get(&fh) {
+                Some(dir) => (dir.page_start, dir.page.len(), dir.next_cursor.clone()),
+                None => return Ok(entries),
+            };
+
+            let (cursor, mut new_start) = if wanted < page_start {
+                (None, 0) // rewinddir or seekdir backwards: restart from the first page
+            } else if wanted >= page_start + page_len {
+                match next_cursor {
+                    Some(cursor) => (Some(cursor), page_start + page_len),
+                    None => return Ok(entries), // end of directory
+                }
+            } else {
+                break;
+            };
+
+            let page = match self.fetch_directory_page(&path, cursor.as_deref()) {
+                // the server dropped the listing after a while: it starts over, in the
+                // same order as long as the directory did not change
+                Err(ClientError::CursorExpired) if cursor.is_some() => {
+                    new_start = 0;
+                    self.fetch_directory_page(&path, None)?
+                }
+                page => page?,
+            };
+            if let Some(dir) = self.open_dirs.get_mut(&fh) {
+                dir.page = page.files;
+                dir.page_start = new_start;
+                dir.next_cursor = page.next_cursor;
+            }
+        }
+
+        let (page_start, children) = match self.open_dirs.get(&fh) {
+            Some(dir) => (dir.page_start, dir.page[wanted - dir.page_start..].to_vec()),
+            None => return Ok(entries),
+        };
+
+        for (i, file_entry) in children.into_iter().enumerate() {
+            // files unlinked while open are gone for everyone but their handles
+            if is_unlinked_name(&file_entry.name) {
+                self.collect_stale_unlinked(&path, &file_entry);
+                continue;
+            }
+
+            let entry_path = join_path(&path, &file_entry.name);
+
+            let entry_ino = if let Some(&existing_ino) = self.path_to_inode.get(&entry_path) {
+                existing_ino
+            } else {
+                let new_ino = self.generate_inode();
+                self.register_inode(new_ino, entry_path.clone());
+                new_ino
+            };
+
+            // hard links appear as regular files (see attributes::from_metadata)
+            let attr = attributes::from_metadata(entry_ino, &file_entry);
+            let entry_offset = 3 + (wanted + i) as i64;
+            entries.push((entry_offset, entry_ino, file_entry.name, attr));
+        }
+
+        Ok(entries)
+    }
 }
 
 impl Filesystem for RemoteFileSystem {
@@ -162,6 +720,8 @@ impl Filesystem for RemoteFileSystem {
     ) -> Result<(), libc::c_int> {
         let _ = _config.set_max_write(1024 * 1024);
         let _ = _config.set_max_readahead(1024 * 1024);
+        // listings already carry full metadata: let the kernel always ask for it
+        let _ = _config.add_capabilities(FUSE_DO_READDIRPLUS);
 
         let rt = match tokio::runtime::Handle::try_current() {
             Ok(handle) => handle,
@@ -171,15 +731,32 @@ impl Filesystem for RemoteFileSystem {
             }
         };
         match rt.block_on(async {
-            match self.client.get_file_metadata("/").await {
+            match self.client.get_file_metadata(OsStr::new("/")).await {
                 Ok(_) => Ok(()),
                 Err(ClientError::NotFound { .. }) => Ok(()),
                 Err(e) => Err(e),
             }
         }) {
             Ok(_) => {
+                // unlinked files left behind by mounts that are gone without releasing
+                // them; those of mounts still running stay theirs
+                for path in self.unlinked.orphans() {
+                    match rt.block_on(async { self.client.delete(&path).await }) {
+                        Ok(()) | Err(ClientError::NotFound { .. }) => {
+                            let _ = self.unlinked.remove(&path);
+                        }
+                        Err(e) => {
+                            log::warn!(
+                                "⚠️ [INIT] Impossibile eliminare {}: {}",
+                                path.to_string_lossy(),
+                                e
+                            );
+                        }
+                    }
+                }
+
                 let _ = rt.block_on(async {
-                    if let Ok(listing) = self.client.list_directory("/").await {
+                    if let Ok(listing) = self.client.list_directory(OsStr::new("/")).await {
                         for entry in listing.files {
                             if !self.path_to_inode.contains_key(&entry.name) {
                                 let new_inode = self.generate_inode();
@@ -198,17 +775,13 @@ impl Filesystem for RemoteFileSystem {
     fn destroy(&mut self) {}
 
     fn lookup(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
-        let filename = match name.to_str() {
-            Some(s) => s,
-            None => {
-                eprintln!("❌ [LOOKUP] Nome file non valido: {:?}", name);
-                reply.error(libc::EINVAL);
-                return;
-            }
-        };
+        let filename = name;
 
         if filename == "." {
-            let parent_path = self.get_path(parent).cloned().unwrap_or("/".to_string());
+            let parent_path = self
+                .get_path(parent)
+                .cloned()
+                .unwrap_or_else(|| OsString::from("/"));
             let rt = match tokio::runtime::Handle::try_current() {
                 Ok(handle) => handle,
                 Err(_) => {
@@ -220,13 +793,13 @@ impl Filesystem for RemoteFileSystem {
             match rt.block_on(async { self.client.get_file_metadata(&parent_path).await }) {
                 Ok(metadata) => {
                     let attr = attributes::from_metadata(parent, &metadata);
-                    let ttl = Duration::from_secs(1);
+                    let ttl = self.kernel_ttl(&parent_path);
                     reply.entry(&ttl, &attr, 0);
                     return;
                 }
                 Err(_) => {
                     let attr = attributes::new_directory_attr(parent, 0o755);
-                    let ttl = Duration::from_secs(1);
+                    let ttl = self.kernel_ttl(&parent_path);
                     reply.entry(&ttl, &attr, 0);
                     return;
                 }
@@ -237,15 +810,11 @@ impl Filesystem for RemoteFileSystem {
             let parent_attr = if parent == 1 {
                 attributes::new_directory_attr(1, 0o755)
             } else {
-                let parent_path = self.get_path(parent).cloned().unwrap_or("/".to_string());
-                let grandparent_path = if parent_path == "/" {
-                    "/".to_string()
-                } else {
-                    std::path::Path::new(&parent_path)
-                        .parent()
-                        .map(|p| p.to_string_lossy().to_string())
-                        .unwrap_or("/".to_string())
-                };
+                let parent_path = self
+                    .get_path(parent)
+                    .cloned()
+                    .unwrap_or_else(|| OsString::from("/"));
+                let grandparent_path = get_parent_path(&parent_path);
 
                 let grandparent_ino = self
                     .path_to_inode
@@ -255,7 +824,7 @@ impl Filesystem for RemoteFileSystem {
                 attributes::new_directory_attr(grandparent_ino, 0o755)
             };
 
-            let ttl = Duration::from_secs(1);
+            let ttl = self.client.cache_policy().kernel_ttl;
             reply.entry(&ttl, &parent_attr, 0);
             return;
         }
@@ -272,11 +841,7 @@ impl Filesystem for RemoteFileSystem {
             }
         };
 
-        let full_path = if parent_path == "/" {
-            format!("/{}", filename)
-        } else {
-            format!("{}/{}", parent_path, filename)
-        };
+        let full_path = join_path(&parent_path, filename);
 
         if let Some(&existing_inode) = self.path_to_inode.get(&full_path) {
             let rt = match tokio::runtime::Handle::try_current() {
@@ -289,19 +854,20 @@ impl Filesystem for RemoteFileSystem {
             match rt.block_on(async { self.client.get_file_metadata(&full_path).await }) {
                 Ok(metadata) => {
                     let attr = attributes::from_metadata(existing_inode, &metadata);
-                    let ttl = Duration::from_secs(1);
+                    let ttl = self.kernel_ttl(&full_path);
                     reply.entry(&ttl, &attr, 0);
                     return;
                 }
                 Err(ClientError::NotFound { .. }) => {
                     self.unregister_inode(existing_inode);
-                    reply.error(libc::ENOENT);
+                    let ttl = self.negative_ttl(&full_path);
+                    reply.entry(&ttl, &attributes::new_negative_entry_attr(), 0);
                     return;
                 }
                 Err(e) => {
                     eprintln!("❌ [LOOKUP] Errore verifica cache: {}", e);
                     let attr = attributes::new_file_attr(existing_inode, 0, 0o644);
-                    let ttl = Duration::from_secs(1);
+                    let ttl = self.kernel_ttl(&full_path);
                     reply.entry(&ttl, &attr, 0);
                     return;
                 }
@@ -324,15 +890,19 @@ impl Filesystem for RemoteFileSystem {
                 self.register_inode(new_inode, full_path.clone());
 
                 let attr = attributes::from_metadata(new_inode, &metadata);
-                let ttl = Duration::from_secs(1);
+                let ttl = self.kernel_ttl(&full_path);
                 reply.entry(&ttl, &attr, 0);
             }
             Err(ClientError::NotFound { .. }) => {
-                reply.error(libc::ENOENT);
+                let ttl = self.negative_ttl(&full_path);
+                reply.entry(&ttl, &attributes::new_negative_entry_attr(), 0);
             }
             Err(ClientError::PermissionDenied(_)) => {
                 reply.error(libc::EACCES);
             }
+            Err(ClientError::Unavailable) => {
+                reply.error(libc::EHOSTDOWN);
+            }
             Err(e) => {
                 reply.error(libc::EIO);
             }
@@ -344,7 +914,7 @@ impl Filesystem for RemoteFileSystem {
     fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr) {
         if ino == 1 {
             let attr = attributes::new_directory_attr(1, 0o755);
-            let ttl = Duration::from_secs(1);
+            let ttl = self.client.cache_policy().kernel_ttl;
             reply.attr(&ttl, &attr);
             return;
         }
@@ -371,12 +941,15 @@ impl Filesystem for RemoteFileSystem {
             Ok(metadata) => {
                 let attr = attributes::from_metadata(ino, &metadata);
 
-                let ttl = Duration::from_secs(1);
+                let ttl = self.kernel_ttl(&path);
                 reply.attr(&ttl, &attr);
             }
             Err(ClientError::NotFound { .. }) => {
                 reply.error(libc::ENOENT);
             }
+            Err(ClientError::Unavailable) => {
+                reply.error(libc::EHOSTDOWN);
+            }
             Err(e) => {
                 reply.error(libc::EIO);
             }
@@ -384,7 +957,7 @@ impl Filesystem for RemoteFileSystem {
     }
     fn setattr(
         &mut self,
-        _req: &Request<'_>,
+        req: &Request<'_>,
         ino: u64,
         mode: Option<u32>,
         uid: Option<u32>,
@@ -429,24 +1002,66 @@ impl Filesystem for RemoteFileSystem {
             match rt.block_on(async { self.client.get_file_metadata(&path).await }) {
                 Ok(metadata) => metadata,
                 Err(ClientError::NotFound { .. }) => {
-                    eprintln!("❌ [SETATTR] File non trovato sul server: {}", path);
+                    eprintln!(
+                        "❌ [SETATTR] File non trovato sul server: {}",
+                        path.to_string_lossy()
+                    );
                     reply.error(libc::ENOENT);
                     return;
                 }
                 Err(e) => {
                     eprintln!(
                         "❌ [SETATTR] Errore recupero metadati per '{}': {}",
-                        path, e
+                        path.to_string_lossy(),
+                        e
                     );
                     reply.error(libc::EIO);
                     return;
                 }
             };
 
+        // as chmod(2), truncate(2) and utimensat(2) require
+        if !self.default_permissions {
+            let explicit_time = matches!(_atime, Some(fuser::TimeOrNow::SpecificTime(_)))
+                || matches!(_mtime, Some(fuser::TimeOrNow::SpecificTime(_)));
+            let touch_now = matches!(_atime, Some(fuser::TimeOrNow::Now))
+                || matches!(_mtime, Some(fuser::TimeOrNow::Now));
+
+            if (mode.is_some() || explicit_time) && !is_owner(req) {
+                reply.error(libc::EPERM);
+                return;
+            }
+            if (size.is_some() || (touch_now && !is_owner(req)))
+                && !permission_granted(req, &current_metadata, libc::W_OK)
+            {
+                reply.error(libc::EACCES);
+                return;
+            }
+        }
+
+        // a flagged file keeps its size, mode and times until the flags are cleared
+        if is_locked(&current_metadata)
+            && (size.is_some()
+                || mode.is_some()
+                || _atime.is_some()
+                || _mtime.is_some()
+                || _ctime.is_some())
+        {
+            log::warn!(
+                "⚠️ [SETATTR] File immutabile o append-only: {}",
+                path.to_string_lossy()
+            );
+            reply.error(libc::EPERM);
+            return;
+        }
+
         if let Some(new_size) = size {
             match current_metadata.kind {
                 FileKind::Directory => {
-                    log::warn!("⚠️ [SETATTR] Tentativo di truncate su directory: {}", path);
+                    log::warn!(
+                        "⚠️ [SETATTR] Tentativo di truncate su directory: {}",
+                        path.to_string_lossy()
+                    );
                     reply.error(libc::EISDIR);
                     return;
                 }
@@ -531,7 +1146,7 @@ impl Filesystem for RemoteFileSystem {
         }
 
         if let Some(new_mode) = mode {
-            let new_permissions = format!("{:o}", new_mode & 0o777);
+            let new_permissions = format!("{:o}", new_mode & MODE_BITS);
             let now_iso = chrono::Utc::now().to_rfc3339();
 
             let chmod_request = WriteRequest {
@@ -613,9 +1228,11 @@ impl Filesystem for RemoteFileSystem {
             return;
         }
 
-        if flags.is_some() {
-            log::warn!("⚠️ [SETATTR] Cambio flags non supportato");
-            reply.error(libc::ENOSYS);
+        if let Some(new_flags) = flags {
+            match self.set_file_flags(req, &path, new_flags) {
+                Ok(()) => self.get_current_attributes(ino, &path, reply),
+                Err(errno) => reply.error(errno),
+            }
             return;
         }
 
@@ -641,30 +1258,41 @@ impl Filesystem for RemoteFileSystem {
         };
 
         match rt.block_on(async { self.client.get_file_metadata(&path).await }) {
-            Ok(metadata) => {
-                match (metadata.kind, &metadata.ref_path) {
-                    (FileKind::Symlink, Some(target)) if !target.is_empty() => {
-                        reply.data(target.as_bytes());
-                    }
-                    (FileKind::Symlink, _) => {
-                        println!("❌ [READLINK] Symlink senza target valido: {}", path);
-                        reply.error(libc::EIO);
-                    }
-                    (FileKind::RegularFile, _) => {
-                        reply.error(libc::EINVAL);
-                    }
-                    (FileKind::Directory, _) => {
-                        println!("❌ [READLINK] Tentativo di readlink su directory: {}", path);
-                        reply.error(libc::EINVAL);
-                    }
-                    (FileKind::Hardlink, _) => {
-                        println!("❌ [READLINK] Tentativo di readlink su hardlink: {}", path);
-                        reply.error(libc::EINVAL);
-                    }
+            Ok(metadata) => match (metadata.kind, &metadata.ref_path) {
+                (FileKind::Symlink, Some(target)) if !target.is_empty() => {
+                    reply.data(target.as_bytes());
                 }
-            }
+                (FileKind::Symlink, _) => {
+                    println!(
+                        "❌ [READLINK] Symlink senza target valido: {}",
+                        path.to_string_lossy()
+                    );
+                    reply.error(libc::EIO);
+                }
+                (FileKind::RegularFile, _)
+                | (FileKind::Fifo, _)
+                | (FileKind::Socket, _)
+                | (FileKind::CharDevice, _)
+                | (FileKind::BlockDevice, _) => {
+                    reply.error(libc::EINVAL);
+                }
+                (FileKind::Directory, _) => {
+                    println!(
+                        "❌ [READLINK] Tentativo di readlink su directory: {}",
+                        path.to_string_lossy()
+                    );
+                    reply.error(libc::EINVAL);
+                }
+                (FileKind::Hardlink, _) => {
+                    println!(
+                        "❌ [READLINK] Tentativo di readlink su hardlink: {}",
+                        path.to_string_lossy()
+                    );
+                    reply.error(libc::EINVAL);
+                }
+            },
             Err(ClientError::NotFound { .. }) => {
-                eprintln!("❌ [READLINK] File non trovato: {}", path);
+                eprintln!("❌ [READLINK] File non trovato: {}", path.to_string_lossy());
                 reply.error(libc::ENOENT);
             }
             Err(e) => {
@@ -684,14 +1312,7 @@ impl Filesystem for RemoteFileSystem {
         rdev: u32,
         reply: ReplyEntry,
     ) {
-        let filename = match name.to_str() {
-            Some(s) => s,
-            None => {
-                eprintln!("❌ [MKNOD] Nome file non valido: {:?}", name);
-                reply.error(libc::EINVAL);
-                return;
-            }
-        };
+        let filename = name;
 
         let parent_path = match self.get_path(parent) {
             Some(p) => p.clone(),
@@ -705,17 +1326,7 @@ impl Filesystem for RemoteFileSystem {
             }
         };
 
-        let full_path = if parent_path == "/" {
-            format!("/{}", filename)
-        } else {
-            format!("{}/{}", parent_path, filename)
-        };
-
-        if self.path_to_inode.contains_key(&full_path) {
-            log::warn!("⚠️ [MKNOD] File già esistente: {}", full_path);
-            reply.error(libc::EEXIST);
-            return;
-        }
+        let full_path = join_path(&parent_path, filename);
 
         let file_type = mode & libc::S_IFMT;
 
@@ -741,8 +1352,8 @@ impl Filesystem for RemoteFileSystem {
                     crtime: chrono::Utc::now().to_rfc3339(),
                     kind: FileKind::RegularFile,
                     ref_path: None,
-                    perm: (mode & 0o777 & !(umask & 0o777)).to_string(),
-                    mode: Mode::Write,
+                    perm: format!("{:o}", effective_mode(mode, umask)),
+                    mode: Mode::Create,
                     data: Some(Vec::new()),
                 };
 
@@ -759,8 +1370,14 @@ impl Filesystem for RemoteFileSystem {
 
                         match metadata_result {
                             Ok(metadata) => {
+                                check_stored_mode(
+                                    "MKNOD",
+                                    &full_path,
+                                    effective_mode(mode, umask),
+                                    &metadata,
+                                );
                                 let attr = attributes::from_metadata(new_inode, &metadata);
-                                let ttl = Duration::from_secs(1);
+                                let ttl = self.kernel_ttl(&full_path);
                                 reply.entry(&ttl, &attr, 0);
                             }
                             Err(e) => {
@@ -768,9 +1385,9 @@ impl Filesystem for RemoteFileSystem {
                                     "❌ [MKNOD] Errore recupero metadati dopo creazione: {}",
                                     e
                                 );
-                                let effective_perms = mode & 0o777 & !(umask & 0o777);
+                                let effective_perms = effective_mode(mode, umask);
                                 let attr = new_file_attr(new_inode, 0, effective_perms);
-                                let ttl = Duration::from_secs(1);
+                                let ttl = self.kernel_ttl(&full_path);
                                 reply.entry(&ttl, &attr, 0);
                             }
                         }
@@ -779,34 +1396,78 @@ impl Filesystem for RemoteFileSystem {
                         eprintln!("❌ [MKNOD] Errore creazione file sul server: {}", e);
                         match e {
                             ClientError::NotFound { .. } => reply.error(libc::ENOENT),
+                            ClientError::Server { status: 409, .. } => reply.error(libc::EEXIST),
                             _ => reply.error(libc::EIO),
                         }
                     }
                 }
             }
-            libc::S_IFIFO => {
-                log::warn!("⚠️ [MKNOD] Named pipe non supportato: {}", full_path);
-                reply.error(libc::EPERM);
-            }
-            libc::S_IFCHR => {
-                log::warn!(
-                    "⚠️ [MKNOD] Character device non supportato: {} (rdev: {})",
-                    full_path,
-                    rdev
-                );
-                reply.error(libc::EPERM);
-            }
-            libc::S_IFBLK => {
-                log::warn!(
-                    "⚠️ [MKNOD] Block device non supportato: {} (rdev: {})",
-                    full_path,
-                    rdev
-                );
-                reply.error(libc::EPERM);
-            }
-            libc::S_IFSOCK => {
-                log::warn!("⚠️ [MKNOD] Socket non supportato: {}", full_path);
-                reply.error(libc::EPERM);
+            libc::S_IFIFO | libc::S_IFSOCK | libc::S_IFCHR | libc::S_IFBLK => {
+                let kind = match file_type {
+                    libc::S_IFIFO => FileKind::Fifo,
+                    libc::S_IFSOCK => FileKind::Socket,
+                    libc::S_IFCHR => FileKind::CharDevice,
+                    _ => FileKind::BlockDevice,
+                };
+                // solo i device hanno un numero
+                let rdev = match kind {
+                    FileKind::CharDevice | FileKind::BlockDevice => rdev,
+                    _ => 0,
+                };
+
+                let rt = match tokio::runtime::Handle::try_current() {
+                    Ok(handle) => handle,
+                    Err(_) => {
+                        let runtime =
+                            tokio::runtime::Runtime::new().expect("Failed to create runtime");
+                        runtime.handle().clone()
+                    }
+                };
+
+                let create_request = CreateNodeRequest {
+                    path: full_path.clone(),
+                    kind,
+                    rdev,
+                    permissions_octal: format!("{:o}", effective_mode(mode, umask)),
+                };
+
+                match rt.block_on(async { self.client.create_node(&create_request).await }) {
+                    Ok(()) => {
+                        let new_inode = self.generate_inode();
+                        self.register_inode(new_inode, full_path.clone());
+
+                        let attr = match rt
+                            .block_on(async { self.client.get_file_metadata(&full_path).await })
+                        {
+                            Ok(metadata) => attributes::from_metadata(new_inode, &metadata),
+                            Err(e) => {
+                                eprintln!(
+                                    "❌ [MKNOD] Errore recupero metadati dopo creazione: {}",
+                                    e
+                                );
+                                let mut attr =
+                                    new_file_attr(new_inode, 0, effective_mode(mode, umask));
+                                attr.kind = attributes::file_type(kind);
+                                attr.rdev = rdev;
+                                attr
+                            }
+                        };
+                        let ttl = self.kernel_ttl(&full_path);
+                        reply.entry(&ttl, &attr, 0);
+                    }
+                    Err(e) => {
+                        eprintln!(
+                            "❌ [MKNOD] Errore creazione nodo {} sul server: {}",
+                            full_path.to_string_lossy(),
+                            e
+                        );
+                        match e {
+                            ClientError::NotFound { .. } => reply.error(libc::ENOENT),
+                            ClientError::Server { status: 409, .. } => reply.error(libc::EEXIST),
+                            _ => reply.error(libc::EIO),
+                        }
+                    }
+                }
             }
             _ => {
                 eprintln!("❌ [MKNOD] Tipo file sconosciuto: {:#o}", file_type);
@@ -824,14 +1485,7 @@ impl Filesystem for RemoteFileSystem {
         umask: u32,
         reply: ReplyEntry,
     ) {
-        let dirname = match name.to_str() {
-            Some(s) => s,
-            None => {
-                eprintln!("❌ [MKDIR] Nome directory non valido: {:?}", name);
-                reply.error(libc::EINVAL);
-                return;
-            }
-        };
+        let dirname = name;
 
         let parent_path = match self.get_path(parent) {
             Some(p) => p.clone(),
@@ -845,20 +1499,13 @@ impl Filesystem for RemoteFileSystem {
             }
         };
 
-        let full_path = if parent_path == "/" {
-            format!("/{}", dirname)
-        } else {
-            format!("{}/{}", parent_path, dirname)
-        };
+        let full_path = join_path(&parent_path, dirname);
 
-        if self.path_to_inode.contains_key(&full_path) {
-            log::warn!("⚠️ [MKDIR] Directory già esistente: {}", full_path);
-            reply.error(libc::EEXIST);
-            return;
-        }
-
-        let effective_permissions = mode & 0o777 & !(umask & 0o777);
-        let permissions_octal = format!("{:o}", effective_permissions);
+        let effective_permissions = effective_mode(mode, umask);
+        let create_request = CreateDirectoryRequest {
+            path: full_path.clone(),
+            permissions_octal: format!("{:o}", effective_permissions),
+        };
 
         let rt = match tokio::runtime::Handle::try_current() {
             Ok(handle) => handle,
@@ -868,7 +1515,8 @@ impl Filesystem for RemoteFileSystem {
             }
         };
 
-        let create_result = rt.block_on(async { self.client.create_directory(&full_path).await });
+        let create_result =
+            rt.block_on(async { self.client.create_directory(&create_request).await });
 
         match create_result {
             Ok(()) => {
@@ -880,14 +1528,15 @@ impl Filesystem for RemoteFileSystem {
 
                 match metadata_result {
                     Ok(metadata) => {
+                        check_stored_mode("MKDIR", &full_path, effective_permissions, &metadata);
                         let attr = attributes::from_metadata(new_inode, &metadata);
-                        let ttl = Duration::from_secs(1);
+                        let ttl = self.kernel_ttl(&full_path);
                         reply.entry(&ttl, &attr, 0);
                     }
                     Err(e) => {
                         eprintln!("❌ [MKDIR] Errore recupero metadati dopo creazione: {}", e);
                         let attr = new_directory_attr(new_inode, effective_permissions);
-                        let ttl = Duration::from_secs(1);
+                        let ttl = self.kernel_ttl(&full_path);
                         reply.entry(&ttl, &attr, 0);
                     }
                 }
@@ -896,21 +1545,23 @@ impl Filesystem for RemoteFileSystem {
                 eprintln!("❌ [MKDIR] Errore creazione directory sul server: {}", e);
                 match e {
                     ClientError::NotFound { .. } => reply.error(libc::ENOENT),
+                    // the server answers 409 for a missing parent as well
+                    ClientError::Server { status: 409, .. } => {
+                        match rt
+                            .block_on(async { self.client.get_file_metadata(&parent_path).await })
+                        {
+                            Err(ClientError::NotFound { .. }) => reply.error(libc::ENOENT),
+                            _ => reply.error(libc::EEXIST),
+                        }
+                    }
                     _ => reply.error(libc::EIO),
                 }
             }
         }
     }
 
-    fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
-        let filename = match name.to_str() {
-            Some(s) => s,
-            None => {
-                eprintln!("❌ [UNLINK] Nome file non valido: {:?}", name);
-                reply.error(libc::EINVAL);
-                return;
-            }
-        };
+    fn unlink(&mut self, req: &Request<'_>, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
+        let filename = name;
 
         let parent_path = match self.get_path(parent) {
             Some(p) => p.clone(),
@@ -924,16 +1575,23 @@ impl Filesystem for RemoteFileSystem {
             }
         };
 
-        let full_path = if parent_path == "/" {
-            format!("/{}", filename)
-        } else {
-            format!("{}/{}", parent_path, filename)
-        };
+        let full_path = join_path(&parent_path, filename);
+
+        if let Err(errno) = self
+            .check_remove(req, &parent_path)
+            .and_then(|()| self.check_not_locked(&full_path))
+        {
+            reply.error(errno);
+            return;
+        }
 
         let file_inode = match self.path_to_inode.get(&full_path) {
             Some(&inode) => inode,
             None => {
-                log::warn!("⚠️ [UNLINK] File non trovato nella cache: {}", full_path);
+                log::warn!(
+                    "⚠️ [UNLINK] File non trovato nella cache: {}",
+                    full_path.to_string_lossy()
+                );
                 let rt = match tokio::runtime::Handle::try_current() {
                     Ok(handle) => handle,
                     Err(_) => {
@@ -971,7 +1629,7 @@ impl Filesystem for RemoteFileSystem {
                     if metadata.kind == FileKind::Directory {
                         log::warn!(
                             "⚠️ [UNLINK] Tentativo di unlink su directory: {}",
-                            full_path
+                            full_path.to_string_lossy()
                         );
                         reply.error(libc::EISDIR);
                         return;
@@ -989,6 +1647,18 @@ impl Filesystem for RemoteFileSystem {
             }
         }
 
+        if self.open_files.values().any(|file| file.path == full_path) {
+            match self.silly_rename(&full_path) {
+                Ok(()) => reply.ok(),
+                Err(ClientError::NotFound { .. }) => reply.error(libc::ENOENT),
+                Err(e) => {
+                    eprintln!("❌ [UNLINK] Errore rinomina file aperto: {}", e);
+                    reply.error(libc::EIO);
+                }
+            }
+            return;
+        }
+
         let rt = match tokio::runtime::Handle::try_current() {
             Ok(handle) => handle,
             Err(_) => {
@@ -1005,7 +1675,10 @@ impl Filesystem for RemoteFileSystem {
                 reply.ok();
             }
             Err(ClientError::NotFound { .. }) => {
-                log::warn!("⚠️ [UNLINK] File già eliminato dal server: {}", full_path);
+                log::warn!(
+                    "⚠️ [UNLINK] File già eliminato dal server: {}",
+                    full_path.to_string_lossy()
+                );
                 self.remove_path_mapping(&full_path);
                 reply.ok();
             }
@@ -1017,19 +1690,12 @@ impl Filesystem for RemoteFileSystem {
     }
 
     fn rmdir(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
-        let dirname = match name.to_str() {
-            Some(s) => s,
-            None => {
-                eprintln!("❌ [RMDIR] Nome directory non valido: {:?}", name);
-                reply.error(libc::EINVAL);
-                return;
-            }
-        };
+        let dirname = name;
 
         if dirname == "." || dirname == ".." {
             log::warn!(
                 "⚠️ [RMDIR] Tentativo di eliminare directory speciale: {}",
-                dirname
+                dirname.to_string_lossy()
             );
             reply.error(libc::EINVAL);
             return;
@@ -1047,11 +1713,7 @@ impl Filesystem for RemoteFileSystem {
             }
         };
 
-        let full_path = if parent_path == "/" {
-            format!("/{}", dirname)
-        } else {
-            format!("{}/{}", parent_path, dirname)
-        };
+        let full_path = join_path(&parent_path, dirname);
 
         if full_path == "/" {
             log::warn!("⚠️ [RMDIR] Tentativo di eliminare directory root");
@@ -1064,7 +1726,7 @@ impl Filesystem for RemoteFileSystem {
             None => {
                 log::warn!(
                     "⚠️ [RMDIR] Directory non trovata nella cache: {}",
-                    full_path
+                    full_path.to_string_lossy()
                 );
                 let rt = match tokio::runtime::Handle::try_current() {
                     Ok(handle) => handle,
@@ -1077,7 +1739,10 @@ impl Filesystem for RemoteFileSystem {
                 match rt.block_on(async { self.client.get_file_metadata(&full_path).await }) {
                     Ok(metadata) => {
                         if metadata.kind != FileKind::Directory {
-                            log::warn!("⚠️ [RMDIR] '{}' non è una directory", full_path);
+                            log::warn!(
+                                "⚠️ [RMDIR] '{}' non è una directory",
+                                full_path.to_string_lossy()
+                            );
                             reply.error(libc::ENOTDIR);
                             return;
                         }
@@ -1107,7 +1772,10 @@ impl Filesystem for RemoteFileSystem {
             match rt.block_on(async { self.client.get_file_metadata(&full_path).await }) {
                 Ok(metadata) => {
                     if metadata.kind != FileKind::Directory {
-                        log::warn!("⚠️ [RMDIR] Tentativo di rmdir su file: {}", full_path);
+                        log::warn!(
+                            "⚠️ [RMDIR] Tentativo di rmdir su file: {}",
+                            full_path.to_string_lossy()
+                        );
                         reply.error(libc::ENOTDIR);
                         return;
                     }
@@ -1136,7 +1804,7 @@ impl Filesystem for RemoteFileSystem {
                 if !listing.files.is_empty() {
                     log::warn!(
                         "⚠️ [RMDIR] Directory non vuota: {} ({} elementi)",
-                        full_path,
+                        full_path.to_string_lossy(),
                         listing.files.len()
                     );
                     reply.error(libc::ENOTEMPTY);
@@ -1164,7 +1832,7 @@ impl Filesystem for RemoteFileSystem {
             Err(ClientError::NotFound { .. }) => {
                 log::warn!(
                     "⚠️ [RMDIR] Directory già eliminata dal server: {}",
-                    full_path
+                    full_path.to_string_lossy()
                 );
                 if dir_inode != 0 {
                     self.unregister_inode(dir_inode);
@@ -1188,23 +1856,9 @@ impl Filesystem for RemoteFileSystem {
     ) {
 
 
-        let link_name = match name.to_str() {
-            Some(s) => s,
-            None => {
-                eprintln!("❌ [SYMLINK] Nome symlink non valido: {:?}", name);
-                reply.error(libc::EINVAL);
-                return;
-            }
-        };
+        let link_name = name;
 
-        let target_path = match link.to_str() {
-            Some(s) => s,
-            None => {
-                eprintln!("❌ [SYMLINK] Path target non valido: {:?}", link);
-                reply.error(libc::EINVAL);
-                return;
-            }
-        };
+        let target_path = link.as_os_str();
 
         let parent_path = match self.get_path(parent) {
             Some(p) => p.clone(),
@@ -1218,21 +1872,11 @@ impl Filesystem for RemoteFileSystem {
             }
         };
 
-        let symlink_path = if parent_path == "/" {
-            format!("/{}", link_name)
-        } else {
-            format!("{}/{}", parent_path, link_name)
-        };
+        let symlink_path = join_path(&parent_path, link_name);
 
 
 
 
-        if self.path_to_inode.contains_key(&symlink_path) {
-            log::warn!("⚠️ [SYMLINK] Symlink già esistente: {}", symlink_path);
-            reply.error(libc::EEXIST);
-            return;
-        }
-
         let rt = match tokio::runtime::Handle::try_current() {
             Ok(handle) => handle,
             Err(_) => {
@@ -1244,7 +1888,7 @@ impl Filesystem for RemoteFileSystem {
 
         let symlink_request = WriteRequest {
             offset: None,
-            path: symlink_path.to_string().clone(),
+            path: symlink_path.clone(),
             new_path: None,
             size: target_path.len() as u64,
             atime: now_iso.clone(),
@@ -1252,8 +1896,8 @@ impl Filesystem for RemoteFileSystem {
             ctime: now_iso.clone(),
             crtime: now_iso,
             kind: FileKind::Symlink,
-            ref_path: Some(target_path.to_string().clone()),
-            perm: "777".to_string(),
+            ref_path: Some(target_path.to_os_string()),
+            perm: "777".to_string(), // symlinks have no permissions of their own
             mode: Mode::Write,
             data: None,
         };
@@ -1262,7 +1906,7 @@ impl Filesystem for RemoteFileSystem {
         match rt.block_on(async { self.client.write_file(&symlink_request).await }) {
             Ok(()) => {
                 let new_inode = self.generate_inode();
-                self.register_inode(new_inode, symlink_path.to_string().clone());
+                self.register_inode(new_inode, symlink_path.clone());
 
                 let metadata_result =
                     rt.block_on(async { self.client.get_file_metadata(&symlink_path).await });
@@ -1270,7 +1914,7 @@ impl Filesystem for RemoteFileSystem {
                 match metadata_result {
                     Ok(metadata) => {
                         let attr = attributes::from_metadata(new_inode, &metadata);
-                        let ttl = Duration::from_secs(1);
+                        let ttl = self.kernel_ttl(&symlink_path);
                         reply.entry(&ttl, &attr, 0);
                     }
                     Err(e) => {
@@ -1282,6 +1926,7 @@ impl Filesystem for RemoteFileSystem {
                 eprintln!("❌ [SYMLINK] Errore creazione symlink sul server: {}", e);
                 match e {
                     ClientError::NotFound { .. } => reply.error(libc::ENOENT),
+                    ClientError::Server { status: 409, .. } => reply.error(libc::EEXIST),
                     ClientError::PermissionDenied(_) => reply.error(libc::EPERM),
                     _ => reply.error(libc::EIO),
                 }
@@ -1291,7 +1936,7 @@ impl Filesystem for RemoteFileSystem {
 
     fn rename(
         &mut self,
-        _req: &Request<'_>,
+        req: &Request<'_>,
         parent: u64,
         name: &OsStr,
         newparent: u64,
@@ -1299,20 +1944,8 @@ impl Filesystem for RemoteFileSystem {
         flags: u32,
         reply: fuser::ReplyEmpty,
     ) {
-        let old_filename = match name.to_str() {
-            Some(s) => s,
-            None => {
-                reply.error(libc::EINVAL);
-                return;
-            }
-        };
-        let new_filename = match newname.to_str() {
-            Some(s) => s,
-            None => {
-                reply.error(libc::EINVAL);
-                return;
-            }
-        };
+        let old_filename = name;
+        let new_filename = newname;
 
         if flags != 0 {
             log::warn!("⚠️ [RENAME] Flags non supportati: {}", flags);
@@ -1341,16 +1974,8 @@ impl Filesystem for RemoteFileSystem {
             }
         };
 
-        let old_path = if old_parent_path == "/" {
-            format!("/{}", old_filename)
-        } else {
-            format!("{}/{}", old_parent_path, old_filename)
-        };
-        let new_path = if new_parent_path == "/" {
-            format!("/{}", new_filename)
-        } else {
-            format!("{}/{}", new_parent_path, new_filename)
-        };
+        let old_path = join_path(&old_parent_path, old_filename);
+        let new_path = join_path(&new_parent_path, new_filename);
 
         if old_path == "/" {
             reply.error(libc::EBUSY);
@@ -1412,6 +2037,29 @@ impl Filesystem for RemoteFileSystem {
             }
         }
 
+        // the source leaves its directory, the destination is created or replaced
+        let permission = self.check_remove(req, &old_parent_path).and_then(|()| {
+            if dest_metadata_opt.is_some() {
+                self.check_remove(req, &new_parent_path)
+            } else {
+                self.check_access(req, &new_parent_path, libc::W_OK | libc::X_OK)
+            }
+        });
+        if let Err(errno) = permission {
+            reply.error(errno);
+            return;
+        }
+
+        if is_locked(&old_metadata) || dest_metadata_opt.as_ref().map_or(false, is_locked) {
+            log::warn!(
+                "⚠️ [RENAME] File immutabile o append-only: {} -> {}",
+                old_path.to_string_lossy(),
+                new_path.to_string_lossy()
+            );
+            reply.error(libc::EPERM);
+            return;
+        }
+
         // Rename request
         let now_iso = chrono::Utc::now().to_rfc3339();
         let rename_request = WriteRequest {
@@ -1450,11 +2098,11 @@ impl Filesystem for RemoteFileSystem {
                     if old_metadata.kind == FileKind::Directory {
                         let mut updates = Vec::new();
                         for (ino, p) in self.inode_to_path.iter() {
-                            if p.starts_with(&old_path) && *ino != file_inode {
+                            if p.as_bytes().starts_with(old_path.as_bytes()) && *ino != file_inode {
                                 // build new path
-                                let suffix = &p[old_path.len()..];
+                                let suffix = OsStr::from_bytes(&p.as_bytes()[old_path.len()..]);
                                 let mut np = new_path.clone();
-                                np.push_str(suffix);
+                                np.push(suffix);
                                 updates.push((*ino, np));
                             }
                         }
@@ -1469,11 +2117,11 @@ impl Filesystem for RemoteFileSystem {
                         if of.path == old_path {
                             of.path = new_path.clone();
                         } else if old_metadata.kind == FileKind::Directory
-                            && of.path.starts_with(&old_path)
+                            && of.path.as_bytes().starts_with(old_path.as_bytes())
                         {
-                            let suffix = &of.path[old_path.len()..];
+                            let suffix = OsStr::from_bytes(&of.path.as_bytes()[old_path.len()..]);
                             let mut np = new_path.clone();
-                            np.push_str(suffix);
+                            np.push(suffix);
                             of.path = np;
                         }
                     }
@@ -1508,14 +2156,7 @@ impl Filesystem for RemoteFileSystem {
             ino, newparent, newname
         );
 
-        let link_name = match newname.to_str() {
-            Some(s) => s,
-            None => {
-                eprintln!("❌ [LINK] Nome hard link non valido: {:?}", newname);
-                reply.error(libc::EINVAL);
-                return;
-            }
-        };
+        let link_name = newname;
 
         let source_path = match self.inode_to_path.get(&ino) {
             Some(p) => p.clone(),
@@ -1540,18 +2181,19 @@ impl Filesystem for RemoteFileSystem {
 
         println!(
             "Parent path: {}, Link name: {}, Source path: {:?}",
-            parent_path, link_name, source_path
+            parent_path.to_string_lossy(),
+            link_name.to_string_lossy(),
+            source_path
         );
 
-        let link_path = if parent_path == "/" {
-            format!("/{}", link_name)
-        } else {
-            format!("{}/{}", parent_path, link_name)
-        };
-        println!("Richiesta con {}", link_path);
+        let link_path = join_path(&parent_path, link_name);
+        println!("Richiesta con {}", link_path.to_string_lossy());
 
         if self.path_to_inode.contains_key(&link_path) {
-            log::warn!("⚠️ [LINK] Hard link già esistente: {}", link_path);
+            log::warn!(
+                "⚠️ [LINK] Hard link già esistente: {}",
+                link_path.to_string_lossy()
+            );
             reply.error(libc::EEXIST);
             return;
         }
@@ -1568,7 +2210,10 @@ impl Filesystem for RemoteFileSystem {
             match rt.block_on(async { self.client.get_file_metadata(&source_path).await }) {
                 Ok(metadata) => metadata,
                 Err(ClientError::NotFound { .. }) => {
-                    eprintln!("❌ [LINK] File sorgente non trovato: {}", source_path);
+                    eprintln!(
+                        "❌ [LINK] File sorgente non trovato: {}",
+                        source_path.to_string_lossy()
+                    );
                     reply.error(libc::ENOENT);
                     return;
                 }
@@ -1584,7 +2229,7 @@ impl Filesystem for RemoteFileSystem {
             FileKind::Directory => {
                 log::warn!(
                     "⚠️ [LINK] Impossibile creare hard link su directory: {}",
-                    source_path
+                    source_path.to_string_lossy()
                 );
                 reply.error(libc::EPERM);
                 return;
@@ -1592,7 +2237,7 @@ impl Filesystem for RemoteFileSystem {
             FileKind::Symlink => {
                 log::warn!(
                     "⚠️ [LINK] Hard link su symlink non supportato: {}",
-                    source_path
+                    source_path.to_string_lossy()
                 );
                 reply.error(libc::EPERM);
                 return;
@@ -1607,6 +2252,15 @@ impl Filesystem for RemoteFileSystem {
             }
         }
 
+        if is_locked(&source_metadata) {
+            log::warn!(
+                "⚠️ [LINK] File immutabile o append-only: {}",
+                source_path.to_string_lossy()
+            );
+            reply.error(libc::EPERM);
+            return;
+        }
+
         let now_iso = chrono::Utc::now().to_rfc3339();
 
         let link_request = WriteRequest {
@@ -1639,13 +2293,13 @@ impl Filesystem for RemoteFileSystem {
                     };
 
                 let attr = attributes::from_metadata(ino, &updated_metadata);
-                let ttl = Duration::from_secs(1);
+                let ttl = self.kernel_ttl(&link_path);
                 reply.entry(&ttl, &attr, 0);
             }
             Err(ClientError::NotFound { .. }) => {
                 eprintln!(
                     "❌ [LINK] File sorgente non trovato durante creazione: {}",
-                    source_path
+                    source_path.to_string_lossy()
                 );
                 reply.error(libc::ENOENT);
             }
@@ -1660,7 +2314,7 @@ impl Filesystem for RemoteFileSystem {
         }
     }
 
-    fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+    fn open(&mut self, req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
         let path = match self.inode_to_path.get(&ino) {
             Some(p) => p.clone(),
             None => {
@@ -1685,10 +2339,14 @@ impl Filesystem for RemoteFileSystem {
 
         let metadata_result = rt.block_on(async { self.client.get_file_metadata(&path).await });
 
+        let mut just_created = false;
         let metadata = match metadata_result {
             Ok(m) => m,
             Err(ClientError::NotFound { .. }) => {
                 if create_flag {
+                    // open() gets no mode from the kernel: the default one for new files,
+                    // less the umask of the process opening it
+                    let create_mode = effective_mode(0o666, caller_umask(req.pid()));
                     let now_iso = chrono::Utc::now().to_rfc3339();
                     let create_req = WriteRequest {
                         offset: None,
@@ -1701,19 +2359,38 @@ impl Filesystem for RemoteFileSystem {
                         crtime: now_iso,
                         kind: FileKind::RegularFile,
                         ref_path: None,
-                        perm: "644".to_string(), // default
-                        mode: Mode::Write,
+                        perm: format!("{:o}", create_mode),
+                        mode: Mode::Create,
                         data: Some(Vec::new()),
                     };
-                    if let Err(e) = rt.block_on(async { self.client.write_file(&create_req).await })
-                    {
-                        eprintln!("❌ [OPEN] Creazione fallita {}: {}", path, e);
-                        reply.error(libc::EIO);
-                        return;
-                    }
+                    let created =
+                        match rt.block_on(async { self.client.write_file(&create_req).await }) {
+                            Ok(()) => true,
+                            // created meanwhile by someone else
+                            Err(ClientError::Server { status: 409, .. }) if !excl_flag => false,
+                            Err(ClientError::Server { status: 409, .. }) => {
+                                reply.error(libc::EEXIST);
+                                return;
+                            }
+                            Err(e) => {
+                                eprintln!(
+                                    "❌ [OPEN] Creazione fallita {}: {}",
+                                    path.to_string_lossy(),
+                                    e
+                                );
+                                reply.error(libc::EIO);
+                                return;
+                            }
+                        };
 
                     match rt.block_on(async { self.client.get_file_metadata(&path).await }) {
-                        Ok(m2) => m2,
+                        Ok(m2) => {
+                            if created {
+                                check_stored_mode("OPEN", &path, create_mode, &m2);
+                                just_created = true;
+                            }
+                            m2
+                        }
                         Err(_) => {
                             reply.error(libc::EIO);
                             return;
@@ -1724,46 +2401,47 @@ impl Filesystem for RemoteFileSystem {
                     return;
                 }
             }
+            Err(ClientError::Unavailable) => {
+                reply.error(libc::EHOSTDOWN);
+                return;
+            }
             Err(e) => {
-                eprintln!("❌ [OPEN] Errore metadati {}: {}", path, e);
+                eprintln!(
+                    "❌ [OPEN] Errore metadati {}: {}",
+                    path.to_string_lossy(),
+                    e
+                );
                 reply.error(libc::EIO);
                 return;
             }
         };
 
-        let perms = parse_permissions(&metadata.perm);
-        let owner_bits = perms.owner;
+        if !matches!(access_mode, libc::O_RDONLY | libc::O_WRONLY | libc::O_RDWR) {
+            reply.error(libc::EINVAL);
+            return;
+        }
 
-        match access_mode {
-            libc::O_RDONLY => {
-                if (owner_bits & 0o4) == 0 {
-                    reply.error(libc::EACCES);
-                    return;
-                }
-            }
-            libc::O_WRONLY => {
-                if (owner_bits & 0o2) == 0 {
-                    reply.error(libc::EACCES);
-                    return;
-                }
-            }
-            libc::O_RDWR => {
-                if (owner_bits & 0o6) != 0o6 {
-                    reply.error(libc::EACCES);
-                    return;
-                }
-            }
-            _ => {
-                reply.error(libc::EINVAL);
-                return;
-            }
+        // whoever creates a file may open it, whatever mode it was given
+        if !just_created
+            && !self.default_permissions
+            && !permission_granted(req, &metadata, open_mask(flags))
+        {
+            reply.error(libc::EACCES);
+            return;
+        }
+
+        if !flags_permit_open(metadata.flags, flags) {
+            log::warn!(
+                "⚠️ [OPEN] File immutabile o append-only: {}",
+                path.to_string_lossy()
+            );
+            reply.error(libc::EPERM);
+            return;
         }
 
         if create_flag && excl_flag {
-            if metadata.size >= 0 {
-                reply.error(libc::EEXIST);
-                return;
-            }
+            reply.error(libc::EEXIST);
+            return;
         }
 
         if trunc_flag && access_mode != libc::O_RDONLY {
@@ -1784,7 +2462,11 @@ impl Filesystem for RemoteFileSystem {
                 data: None,
             };
             if let Err(e) = rt.block_on(async { self.client.write_file(&trunc_req).await }) {
-                eprintln!("❌ [OPEN] Truncate fallito {}: {}", path, e);
+                eprintln!(
+                    "❌ [OPEN] Truncate fallito {}: {}",
+                    path.to_string_lossy(),
+                    e
+                );
                 reply.error(libc::EIO);
                 return;
             }
@@ -1845,7 +2527,7 @@ impl Filesystem for RemoteFileSystem {
         if access_mode == libc::O_WRONLY {
             log::warn!(
                 "⚠️ [READ] Tentativo di lettura su file aperto in WRITE-ONLY: {}",
-                path
+                path.to_string_lossy()
             );
             reply.error(libc::EBADF);
             return;
@@ -1861,10 +2543,17 @@ impl Filesystem for RemoteFileSystem {
         let metadata = match rt.block_on(async { self.client.get_file_metadata(&path).await }) {
             Ok(metadata) => metadata,
             Err(ClientError::NotFound { .. }) => {
-                eprintln!("❌ [READ] File non trovato sul server: {}", path);
+                eprintln!(
+                    "❌ [READ] File non trovato sul server: {}",
+                    path.to_string_lossy()
+                );
                 reply.error(libc::ENOENT);
                 return;
             }
+            Err(ClientError::Unavailable) => {
+                reply.error(libc::EHOSTDOWN);
+                return;
+            }
             Err(e) => {
                 eprintln!("❌ [READ] Errore verifica metadati: {}", e);
                 reply.error(libc::EIO);
@@ -1875,7 +2564,10 @@ impl Filesystem for RemoteFileSystem {
         match metadata.kind {
             FileKind::RegularFile | FileKind::Symlink => {}
             FileKind::Directory => {
-                log::warn!("⚠️ [READ] Tentativo di read su directory: {}", path);
+                log::warn!(
+                    "⚠️ [READ] Tentativo di read su directory: {}",
+                    path.to_string_lossy()
+                );
                 reply.error(libc::EISDIR);
                 return;
             }
@@ -1928,13 +2620,22 @@ impl Filesystem for RemoteFileSystem {
                 }
             }
             Err(ClientError::NotFound { .. }) => {
-                eprintln!("❌ [READ] File eliminato durante la lettura: {}", path);
+                eprintln!(
+                    "❌ [READ] File eliminato durante la lettura: {}",
+                    path.to_string_lossy()
+                );
                 reply.error(libc::ENOENT);
             }
             Err(ClientError::PermissionDenied(_)) => {
-                eprintln!("❌ [READ] Permesso di lettura negato: {}", path);
+                eprintln!(
+                    "❌ [READ] Permesso di lettura negato: {}",
+                    path.to_string_lossy()
+                );
                 reply.error(libc::EACCES);
             }
+            Err(ClientError::Unavailable) => {
+                reply.error(libc::EHOSTDOWN);
+            }
             Err(e) => {
                 eprintln!("❌ [READ] Errore lettura dal server: {}", e);
                 reply.error(libc::EIO);
@@ -1983,7 +2684,7 @@ impl Filesystem for RemoteFileSystem {
         if access_mode == libc::O_RDONLY {
             log::warn!(
                 "⚠️ [WRITE] Tentativo di scrittura su file aperto in READ-ONLY: {}",
-                path
+                path.to_string_lossy()
             );
             reply.error(libc::EBADF);
             return;
@@ -2000,7 +2701,10 @@ impl Filesystem for RemoteFileSystem {
         let metadata = match rt.block_on(async { self.client.get_file_metadata(&path).await }) {
             Ok(metadata) => metadata,
             Err(ClientError::NotFound { .. }) => {
-                eprintln!("❌ [WRITE] File non trovato sul server: {}", path);
+                eprintln!(
+                    "❌ [WRITE] File non trovato sul server: {}",
+                    path.to_string_lossy()
+                );
                 reply.error(libc::ENOENT);
                 return;
             }
@@ -2014,7 +2718,10 @@ impl Filesystem for RemoteFileSystem {
         match metadata.kind {
             FileKind::RegularFile | FileKind::Symlink => {}
             FileKind::Directory => {
-                log::warn!("⚠️ [WRITE] Tentativo di write su directory: {}", path);
+                log::warn!(
+                    "⚠️ [WRITE] Tentativo di write su directory: {}",
+                    path.to_string_lossy()
+                );
                 reply.error(libc::EISDIR);
                 return;
             }
@@ -2028,8 +2735,43 @@ impl Filesystem for RemoteFileSystem {
             }
         }
 
+        // flagged after the open
+        if !flags_permit_open(metadata.flags, open_flags & !libc::O_TRUNC) {
+            log::warn!(
+                "⚠️ [WRITE] File immutabile o append-only: {}",
+                path.to_string_lossy()
+            );
+            reply.error(libc::EPERM);
+            return;
+        }
+
         let current_file_size = metadata.size;
 
+        // blocks of zeroes past the end, leaving a gap behind them, are filled in by
+        // the server instead of being uploaded; inside the file and right at its
+        // end they go the usual way, through the append buffer when they can
+        let buffered = self.open_files.get(&fh).map_or(0, |f| f.write_buffer.len());
+        let buffered_end = current_file_size + buffered as u64;
+        if data_len >= ZERO_BLOCK
+            && (open_flags & libc::O_APPEND) == 0
+            && offset_u64 > buffered_end
+            && data.iter().all(|&b| b == 0)
+        {
+            match self.write_zeroes(fh, &path, offset_u64, data_len as u64) {
+                Ok(()) => {
+                    reply.written(data_len as u32);
+                    return;
+                }
+                // the server cannot fill ranges: the zeroes are uploaded
+                Err(ClientError::Server { status: 501, .. }) => {}
+                Err(e) => {
+                    eprintln!("❌ [WRITE] Errore scrittura blocco di zeri: {}", e);
+                    reply.error(libc::EIO);
+                    return;
+                }
+            }
+        }
+
         let effective_offset = if (open_flags & libc::O_APPEND) != 0 {
             current_file_size
         } else {
@@ -2045,10 +2787,11 @@ impl Filesystem for RemoteFileSystem {
             (Mode::Write, data.to_vec())
         };
 
+        let stream_write = self.client.cache_policy().stream_write;
         let open_file = self.open_files.get_mut(&fh);
         let file = open_file.unwrap();
 
-        if write_mode == Mode::Append && file.write_buffer.len() < STREAM_WRITE {
+        if write_mode == Mode::Append && file.write_buffer.len() < stream_write {
             let open_file = self.open_files.get_mut(&fh);
             if let Some(file) = open_file {
                 file.write_buffer.extend_from_slice(&final_data);
@@ -2138,19 +2881,28 @@ impl Filesystem for RemoteFileSystem {
                 reply.written(data_len as u32);
             }
             Err(ClientError::NotFound { .. }) => {
-                eprintln!("❌ [WRITE] File eliminato durante la scrittura: {}", path);
+                eprintln!(
+                    "❌ [WRITE] File eliminato durante la scrittura: {}",
+                    path.to_string_lossy()
+                );
                 reply.error(libc::ENOENT);
             }
             Err(ClientError::PermissionDenied(_)) => {
-                eprintln!("❌ [WRITE] Permesso di scrittura negato: {}", path);
+                eprintln!(
+                    "❌ [WRITE] Permesso di scrittura negato: {}",
+                    path.to_string_lossy()
+                );
                 reply.error(libc::EACCES);
             }
             Err(ClientError::Server { status: 413, .. }) => {
-                eprintln!("❌ [WRITE] File troppo grande: {}", path);
+                eprintln!("❌ [WRITE] File troppo grande: {}", path.to_string_lossy());
                 reply.error(libc::EFBIG);
             }
             Err(ClientError::Server { status: 507, .. }) => {
-                eprintln!("❌ [WRITE] Spazio insufficiente sul server: {}", path);
+                eprintln!(
+                    "❌ [WRITE] Spazio insufficiente sul server: {}",
+                    path.to_string_lossy()
+                );
                 reply.error(libc::ENOSPC);
             }
             Err(e) => {
@@ -2193,7 +2945,10 @@ impl Filesystem for RemoteFileSystem {
             match rt.block_on(async { self.client.get_file_metadata(&open_file.path).await }) {
                 Ok(metadata) => metadata,
                 Err(ClientError::NotFound { .. }) => {
-                    eprintln!("❌ [WRITE] File non trovato sul server: {}", open_file.path);
+                    eprintln!(
+                        "❌ [WRITE] File non trovato sul server: {}",
+                        open_file.path.to_string_lossy()
+                    );
                     reply.error(libc::ENOENT);
                     return;
                 }
@@ -2280,6 +3035,7 @@ impl Filesystem for RemoteFileSystem {
         if flush {}
 
         if let Some(removed_file) = self.open_files.remove(&fh) {}
+        self.reap_unlinked(&path);
 
         reply.ok();
     }
@@ -2305,7 +3061,10 @@ impl Filesystem for RemoteFileSystem {
 
         let access_mode = open_file.flags & libc::O_ACCMODE;
         if access_mode == libc::O_RDONLY {
-            log::warn!("⚠️ [FSYNC] File aperto in read-only: {}", path);
+            log::warn!(
+                "⚠️ [FSYNC] File aperto in read-only: {}",
+                path.to_string_lossy()
+            );
             reply.error(libc::EBADF);
             return;
         }
@@ -2322,7 +3081,10 @@ impl Filesystem for RemoteFileSystem {
                 reply.ok();
             }
             Err(ClientError::NotFound { .. }) => {
-                eprintln!("❌ [FSYNC] File eliminato durante fsync: {}", path);
+                eprintln!(
+                    "❌ [FSYNC] File eliminato durante fsync: {}",
+                    path.to_string_lossy()
+                );
                 reply.error(libc::ENOENT);
             }
             Err(e) => {
@@ -2352,10 +3114,17 @@ impl Filesystem for RemoteFileSystem {
         let metadata = match rt.block_on(async { self.client.get_file_metadata(&path).await }) {
             Ok(metadata) => metadata,
             Err(ClientError::NotFound { .. }) => {
-                eprintln!("❌ [OPENDIR] Directory non trovata sul server: {}", path);
+                eprintln!(
+                    "❌ [OPENDIR] Directory non trovata sul server: {}",
+                    path.to_string_lossy()
+                );
                 reply.error(libc::ENOENT);
                 return;
             }
+            Err(ClientError::Unavailable) => {
+                reply.error(libc::EHOSTDOWN);
+                return;
+            }
             Err(e) => {
                 eprintln!("❌ [OPENDIR] Errore verifica metadati: {}", e);
                 reply.error(libc::EIO);
@@ -2366,26 +3135,34 @@ impl Filesystem for RemoteFileSystem {
         if metadata.kind != FileKind::Directory {
             log::warn!(
                 "⚠️ [OPENDIR] '{}' non è una directory: {:?}",
-                path,
+                path.to_string_lossy(),
                 metadata.kind
             );
             reply.error(libc::ENOTDIR);
             return;
         }
 
-        match rt.block_on(async { self.client.list_directory(&path).await }) {
-            Ok(_) => {}
+        // the first page both checks access and serves the first readdir
+        let first_page = match self.fetch_directory_page(&path, None) {
+            Ok(page) => page,
             Err(ClientError::PermissionDenied(_)) => {
-                eprintln!("❌ [OPENDIR] Permesso di lettura negato: {}", path);
+                eprintln!(
+                    "❌ [OPENDIR] Permesso di lettura negato: {}",
+                    path.to_string_lossy()
+                );
                 reply.error(libc::EACCES);
                 return;
             }
+            Err(ClientError::Unavailable) => {
+                reply.error(libc::EHOSTDOWN);
+                return;
+            }
             Err(e) => {
                 eprintln!("❌ [OPENDIR] Errore accesso directory: {}", e);
                 reply.error(libc::EIO);
                 return;
             }
-        }
+        };
 
         let dh = self.next_fh;
         self.next_fh += 1;
@@ -2395,6 +3172,9 @@ impl Filesystem for RemoteFileSystem {
             OpenDir {
                 path: path.clone(),
                 flags,
+                page: first_page.files,
+                page_start: 0,
+                next_cursor: first_page.next_cursor,
             },
         );
 
@@ -2420,27 +3200,28 @@ impl Filesystem for RemoteFileSystem {
 
         let path = open_dir.path.clone();
 
-        let rt = match tokio::runtime::Handle::try_current() {
-            Ok(handle) => handle,
-            Err(_) => {
-                let runtime = tokio::runtime::Runtime::new().expect("Failed to create runtime");
-                runtime.handle().clone()
-            }
-        };
-        let listing_result = rt.block_on(async { self.client.list_directory(&path).await });
-
-        let listing = match listing_result {
-            Ok(listing) => listing,
+        let entries = match self.directory_entries(ino, fh, offset) {
+            Ok(entries) => entries,
             Err(ClientError::NotFound { .. }) => {
-                eprintln!("❌ [READDIR] Directory non trovata sul server: {}", path);
+                eprintln!(
+                    "❌ [READDIR] Directory non trovata sul server: {}",
+                    path.to_string_lossy()
+                );
                 reply.error(libc::ENOENT);
                 return;
             }
             Err(ClientError::PermissionDenied(_)) => {
-                eprintln!("❌ [READDIR] Permesso di lettura negato: {}", path);
+                eprintln!(
+                    "❌ [READDIR] Permesso di lettura negato: {}",
+                    path.to_string_lossy()
+                );
                 reply.error(libc::EACCES);
                 return;
             }
+            Err(ClientError::Unavailable) => {
+                reply.error(libc::EHOSTDOWN);
+                return;
+            }
             Err(e) => {
                 eprintln!("❌ [READDIR] Errore lettura directory: {}", e);
                 reply.error(libc::EIO);
@@ -2448,66 +3229,67 @@ impl Filesystem for RemoteFileSystem {
             }
         };
 
-        let mut entries = Vec::new();
-
-        entries.push((ino, FileType::Directory, ".".to_string()));
-
-        let parent_ino = if path == "/" {
-            1 // Root directory
-        } else {
-            let parent_path = std::path::Path::new(&path)
-                .parent()
-                .map(|p| p.to_string_lossy().to_string())
-                .unwrap_or_else(|| "/".to_string());
-
-            self.path_to_inode.get(&parent_path).copied().unwrap_or(1)
-        };
-        entries.push((parent_ino, FileType::Directory, "..".to_string()));
-
-        for file_entry in listing.files {
-            let entry_path = if path == "/" {
-                format!("/{}", file_entry.name)
-            } else {
-                format!("{}/{}", path, file_entry.name)
-            };
-
-            let entry_ino = if let Some(&existing_ino) = self.path_to_inode.get(&entry_path) {
-                existing_ino
-            } else {
-                let new_ino = self.generate_inode();
-                self.register_inode(new_ino, entry_path.clone());
-                new_ino
-            };
-
-            let file_type = match file_entry.kind {
-                FileKind::Directory => FileType::Directory,
-                FileKind::RegularFile => FileType::RegularFile,
-                FileKind::Symlink => FileType::Symlink,
-                FileKind::Hardlink => FileType::RegularFile, // Hard link appears as normal files
-                _ => {
-                    log::warn!(
-                        "⚠️ [READDIR] Tipo file non supportato: {:?}",
-                        file_entry.kind
-                    );
-                    FileType::RegularFile // Fallback
-                }
-            };
+        for (entry_offset, entry_ino, name, attr) in entries {
+            let buffer_full = reply.add(entry_ino, entry_offset, attr.kind, name);
 
-            entries.push((entry_ino, file_type, file_entry.name));
+            if buffer_full {
+                break;
+            }
         }
 
-        let start_index = if offset == 0 { 0 } else { offset as usize };
+        reply.ok();
+    }
 
-        if start_index >= entries.len() {
-            reply.ok();
-            return;
-        }
+    fn readdirplus(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectoryPlus,
+    ) {
+        let path = match self.open_dirs.get(&fh) {
+            Some(dir) => dir.path.clone(),
+            None => {
+                eprintln!("❌ [READDIRPLUS] Directory handle {} non trovato", fh);
+                reply.error(libc::EBADF);
+                return;
+            }
+        };
 
-        let mut current_offset = start_index;
-        for (entry_ino, file_type, name) in entries.into_iter().skip(start_index) {
-            current_offset += 1;
+        let entries = match self.directory_entries(ino, fh, offset) {
+            Ok(entries) => entries,
+            Err(ClientError::NotFound { .. }) => {
+                eprintln!(
+                    "❌ [READDIRPLUS] Directory non trovata sul server: {}",
+                    path.to_string_lossy()
+                );
+                reply.error(libc::ENOENT);
+                return;
+            }
+            Err(ClientError::PermissionDenied(_)) => {
+                eprintln!(
+                    "❌ [READDIRPLUS] Permesso di lettura negato: {}",
+                    path.to_string_lossy()
+                );
+                reply.error(libc::EACCES);
+                return;
+            }
+            Err(ClientError::Unavailable) => {
+                reply.error(libc::EHOSTDOWN);
+                return;
+            }
+            Err(e) => {
+                eprintln!("❌ [READDIRPLUS] Errore lettura directory: {}", e);
+                reply.error(libc::EIO);
+                return;
+            }
+        };
 
-            let buffer_full = reply.add(entry_ino, current_offset as i64, file_type, name);
+        for (entry_offset, entry_ino, name, attr) in entries {
+            let entry_path = join_path(&path, &name);
+            let ttl = self.kernel_ttl(&entry_path);
+            let buffer_full = reply.add(entry_ino, entry_offset, name, &ttl, &attr, 0);
 
             if buffer_full {
                 break;
@@ -2573,7 +3355,10 @@ impl Filesystem for RemoteFileSystem {
         let metadata = match rt.block_on(async { self.client.get_file_metadata(&path).await }) {
             Ok(metadata) => metadata,
             Err(ClientError::NotFound { .. }) => {
-                eprintln!("❌ [FSYNCDIR] Directory non trovata: {}", path);
+                eprintln!(
+                    "❌ [FSYNCDIR] Directory non trovata: {}",
+                    path.to_string_lossy()
+                );
                 reply.error(libc::ENOENT);
                 return;
             }
@@ -2585,7 +3370,10 @@ impl Filesystem for RemoteFileSystem {
         };
 
         if metadata.kind != FileKind::Directory {
-            eprintln!("❌ [FSYNCDIR] '{}' non è una directory", path);
+            eprintln!(
+                "❌ [FSYNCDIR] '{}' non è una directory",
+                path.to_string_lossy()
+            );
             reply.error(libc::ENOTDIR);
             return;
         }
@@ -2597,12 +3385,15 @@ impl Filesystem for RemoteFileSystem {
             Err(ClientError::NotFound { .. }) => {
                 eprintln!(
                     "❌ [FSYNCDIR] Directory eliminata durante fsyncdir: {}",
-                    path
+                    path.to_string_lossy()
                 );
                 reply.error(libc::ENOENT);
             }
             Err(ClientError::PermissionDenied(_)) => {
-                eprintln!("❌ [FSYNCDIR] Permesso negato per directory: {}", path);
+                eprintln!(
+                    "❌ [FSYNCDIR] Permesso negato per directory: {}",
+                    path.to_string_lossy()
+                );
                 reply.error(libc::EACCES);
             }
             Err(e) => {
@@ -2641,6 +3432,10 @@ impl Filesystem for RemoteFileSystem {
         position: u32,
         reply: fuser::ReplyEmpty,
     ) {
+        if name == SHA256_XATTR {
+            reply.error(libc::EPERM);
+            return;
+        }
         reply.error(libc::ENOSYS);
     }
 
@@ -2652,11 +3447,107 @@ impl Filesystem for RemoteFileSystem {
         size: u32,
         reply: fuser::ReplyXattr,
     ) {
-        reply.error(libc::ENOSYS);
+        if name != SHA256_XATTR {
+            reply.error(libc::ENODATA);
+            return;
+        }
+
+        let path = match self.inode_to_path.get(&ino) {
+            Some(p) => p.clone(),
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+
+        let rt = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => {
+                let runtime = tokio::runtime::Runtime::new().expect("Failed to create runtime");
+                runtime.handle().clone()
+            }
+        };
+
+        // il digest in elenco se noto, altrimenti calcolato dal server
+        let digest = rt.block_on(async {
+            let metadata = self.client.get_file_metadata(&path).await?;
+            if metadata.kind != FileKind::RegularFile {
+                return Ok(None);
+            }
+            match metadata.sha256 {
+                Some(sha256) => Ok(Some(sha256)),
+                None => self
+                    .client
+                    .get_checksums(&path, false)
+                    .await
+                    .map(|checksums| checksums.sha256.clone()),
+            }
+        });
+
+        match digest {
+            Ok(Some(sha256)) => {
+                if size == 0 {
+                    reply.size(sha256.len() as u32);
+                } else if (size as usize) < sha256.len() {
+                    reply.error(libc::ERANGE);
+                } else {
+                    reply.data(sha256.as_bytes());
+                }
+            }
+            Ok(None) => reply.error(libc::ENODATA),
+            Err(ClientError::NotFound { .. }) => reply.error(libc::ENOENT),
+            Err(ClientError::Unavailable) => reply.error(libc::EHOSTDOWN),
+            Err(e) => {
+                eprintln!("❌ [GETXATTR] Errore lettura checksum: {}", e);
+                reply.error(libc::EIO);
+            }
+        }
     }
 
-    fn listxattr(&mut self, _req: &Request<'_>, ino: u64, size: u32, reply: fuser::ReplyXattr) {
-        reply.error(libc::ENOSYS);
+    fn listxattr(&mut self, _req: &Request<'_>, ino: u64, size: u32, reply: fuser::ReplyXattr) {
+        let path = match self.inode_to_path.get(&ino) {
+            Some(p) => p.clone(),
+            None => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+
+        let rt = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => {
+                let runtime = tokio::runtime::Runtime::new().expect("Failed to create runtime");
+                runtime.handle().clone()
+            }
+        };
+
+        // nomi separati da NUL: solo i file regolari hanno il checksum
+        let names: Vec<u8> = match rt.block_on(async { self.client.get_file_metadata(&path).await })
+        {
+            Ok(metadata) if metadata.kind == FileKind::RegularFile => {
+                let mut names = SHA256_XATTR.as_bytes().to_vec();
+                names.push(0);
+                names
+            }
+            Ok(_) => Vec::new(),
+            Err(ClientError::NotFound { .. }) => {
+                reply.error(libc::ENOENT);
+                return;
+            }
+            Err(e) => {
+                eprintln!("❌ [LISTXATTR] Errore verifica metadati: {}", e);
+                reply.error(libc::EIO);
+                return;
+            }
+        };
+
+        if size == 0 {
+            reply.size(names.len() as u32);
+        } else if (size as usize) < names.len() {
+            reply.error(libc::ERANGE);
+        } else {
+            reply.data(&names);
+        }
     }
 
     fn removexattr(
@@ -2666,10 +3557,14 @@ impl Filesystem for RemoteFileSystem {
         name: &OsStr,
         reply: fuser::ReplyEmpty,
     ) {
+        if name == SHA256_XATTR {
+            reply.error(libc::EPERM);
+            return;
+        }
         reply.error(libc::ENOSYS);
     }
 
-    fn access(&mut self, _req: &Request<'_>, ino: u64, mask: i32, reply: fuser::ReplyEmpty) {
+    fn access(&mut self, req: &Request<'_>, ino: u64, mask: i32, reply: fuser::ReplyEmpty) {
         let path = match self.inode_to_path.get(&ino) {
             Some(p) => p.clone(),
             None => {
@@ -2694,12 +3589,15 @@ impl Filesystem for RemoteFileSystem {
         let metadata = match rt.block_on(async { self.client.get_file_metadata(&path).await }) {
             Ok(metadata) => metadata,
             Err(ClientError::NotFound { .. }) => {
-                eprintln!("❌ [ACCESS] File non trovato: {}", path);
+                eprintln!("❌ [ACCESS] File non trovato: {}", path.to_string_lossy());
                 reply.error(libc::ENOENT);
                 return;
             }
             Err(ClientError::PermissionDenied(_)) => {
-                eprintln!("❌ [ACCESS] Permesso negato per metadati: {}", path);
+                eprintln!(
+                    "❌ [ACCESS] Permesso negato per metadati: {}",
+                    path.to_string_lossy()
+                );
                 reply.error(libc::EACCES);
                 return;
             }
@@ -2715,25 +3613,13 @@ impl Filesystem for RemoteFileSystem {
             return;
         }
 
-        let perms = parse_permissions(&metadata.perm);
-
-        let effective_perms = perms.owner;
-
-        let mut access_denied = false;
-
-        if check_read && (effective_perms & 0o4) == 0 {
-            reply.error(libc::EACCES);
-            return;
-        }
-        if check_write && (effective_perms & 0o2) == 0 {
-            reply.error(libc::EACCES);
-            return;
-        }
-        if check_exec && (effective_perms & 0o1) == 0 && metadata.kind != FileKind::Directory {
+        if !self.default_permissions && !permission_granted(req, &metadata, mask) {
             reply.error(libc::EACCES);
             return;
         }
 
+        let mut access_denied = false;
+
         if check_exec && metadata.kind == FileKind::Directory {
         } else if check_exec && metadata.kind != FileKind::RegularFile {
             log::warn!("⚠️ [ACCESS] Tipo file non eseguibile: {:?}", metadata.kind);
@@ -2749,7 +3635,7 @@ impl Filesystem for RemoteFileSystem {
 
     fn create(
         &mut self,
-        _req: &Request<'_>,
+        req: &Request<'_>,
         parent: u64,
         name: &OsStr,
         mode: u32,
@@ -2757,14 +3643,7 @@ impl Filesystem for RemoteFileSystem {
         flags: i32,
         reply: fuser::ReplyCreate,
     ) {
-        let filename = match name.to_str() {
-            Some(s) => s,
-            None => {
-                eprintln!("❌ [CREATE] Nome file non valido: {:?}", name);
-                reply.error(libc::EINVAL);
-                return;
-            }
-        };
+        let filename = name;
 
         let parent_path = match self.get_path(parent) {
             Some(p) => p.clone(),
@@ -2778,19 +3657,14 @@ impl Filesystem for RemoteFileSystem {
             }
         };
 
-        let full_path = if parent_path == "/" {
-            format!("/{}", filename)
-        } else {
-            format!("{}/{}", parent_path, filename)
-        };
+        let full_path = join_path(&parent_path, filename);
 
-        if self.path_to_inode.contains_key(&full_path) {
-            log::warn!("⚠️ [CREATE] File già esistente: {}", full_path);
-            reply.error(libc::EEXIST);
+        if let Err(errno) = self.check_access(req, &parent_path, libc::W_OK | libc::X_OK) {
+            reply.error(errno);
             return;
         }
 
-        let effective_permissions = mode & 0o777 & !(umask & 0o777);
+        let effective_permissions = effective_mode(mode, umask);
         let effective_permissions_str = format!("{:o}", effective_permissions);
 
         let access_mode = flags & libc::O_ACCMODE;
@@ -2817,13 +3691,43 @@ impl Filesystem for RemoteFileSystem {
             kind: FileKind::RegularFile,
             ref_path: None,
             perm: effective_permissions_str,
-            mode: Mode::Write,
+            mode: Mode::Create,
             data: Some(Vec::new()),
         };
 
-        if (open_flags & libc::O_TRUNC) != 0 {}
+        let create_result =
+            match rt.block_on(async { self.client.write_file(&create_request).await }) {
+                // created meanwhile by someone else: without O_EXCL this is a plain open
+                Err(ClientError::Server { status: 409, .. }) if (flags & libc::O_EXCL) == 0 => {
+                    if (open_flags & libc::O_TRUNC) != 0 {
+                        rt.block_on(async {
+                            let metadata = self.client.get_file_metadata(&full_path).await?;
+                            let now_iso = chrono::Utc::now().to_rfc3339();
+                            let trunc_request = WriteRequest {
+                                offset: None,
+                                path: full_path.clone(),
+                                new_path: None,
+                                size: 0,
+                                atime: metadata.atime,
+                                mtime: now_iso.clone(),
+                                ctime: now_iso,
+                                crtime: metadata.crtime,
+                                kind: metadata.kind,
+                                ref_path: metadata.ref_path,
+                                perm: metadata.perm,
+                                mode: Mode::Truncate,
+                                data: None,
+                            };
+                            self.client.write_file(&trunc_request).await
+                        })
+                    } else {
+                        Ok(())
+                    }
+                }
+                result => result,
+            };
 
-        match rt.block_on(async { self.client.write_file(&create_request).await }) {
+        match create_result {
             Ok(()) => {
                 let new_inode = self.generate_inode();
                 self.register_inode(new_inode, full_path.clone());
@@ -2846,15 +3750,16 @@ impl Filesystem for RemoteFileSystem {
 
                 match metadata_result {
                     Ok(metadata) => {
+                        check_stored_mode("CREATE", &full_path, effective_permissions, &metadata);
                         let attr = attributes::from_metadata(new_inode, &metadata);
-                        let ttl = Duration::from_secs(1);
+                        let ttl = self.kernel_ttl(&full_path);
 
                         reply.created(&ttl, &attr, 0, fh, FOPEN_DIRECT_IO);
                     }
                     Err(e) => {
                         eprintln!("❌ [CREATE] Errore recupero metadati: {}", e);
                         let attr = new_file_attr(new_inode, 0, effective_permissions);
-                        let ttl = Duration::from_secs(1);
+                        let ttl = self.kernel_ttl(&full_path);
                         reply.created(&ttl, &attr, 0, fh, 0);
                     }
                 }
@@ -2863,6 +3768,7 @@ impl Filesystem for RemoteFileSystem {
                 eprintln!("❌ [CREATE] Errore creazione file sul server: {}", e);
                 match e {
                     ClientError::NotFound { .. } => reply.error(libc::ENOENT),
+                    ClientError::Server { status: 409, .. } => reply.error(libc::EEXIST),
                     ClientError::PermissionDenied(_) => reply.error(libc::EPERM),
                     _ => reply.error(libc::EIO),
                 }
@@ -3003,7 +3909,7 @@ impl Filesystem for RemoteFileSystem {
         let metadata = match rt.block_on(async { self.client.get_file_metadata(&path).await }) {
             Ok(metadata) => metadata,
             Err(ClientError::NotFound { .. }) => {
-                eprintln!("❌ [BMAP] File non trovato: {}", path);
+                eprintln!("❌ [BMAP] File non trovato: {}", path.to_string_lossy());
                 reply.error(libc::ENOENT);
                 return;
             }
@@ -3035,7 +3941,7 @@ impl Filesystem for RemoteFileSystem {
 
     fn ioctl(
         &mut self,
-        _req: &Request<'_>,
+        req: &Request<'_>,
         ino: u64,
         fh: u64,
         flags: u32,
@@ -3044,7 +3950,53 @@ impl Filesystem for RemoteFileSystem {
         out_size: u32,
         reply: fuser::ReplyIoctl,
     ) {
-        reply.error(libc::ENOSYS);
+        let path = match self.inode_to_path.get(&ino) {
+            Some(p) => p.clone(),
+            None => {
+                eprintln!("❌ [IOCTL] Inode {} non trovato", ino);
+                reply.error(libc::ENOENT);
+                return;
+            }
+        };
+
+        match cmd {
+            FS_IOC_GETFLAGS | FS_IOC32_GETFLAGS => {
+                let rt = match tokio::runtime::Handle::try_current() {
+                    Ok(handle) => handle,
+                    Err(_) => {
+                        let runtime =
+                            tokio::runtime::Runtime::new().expect("Failed to create runtime");
+                        runtime.handle().clone()
+                    }
+                };
+                match rt.block_on(async { self.client.get_file_metadata(&path).await }) {
+                    Ok(metadata) => reply.ioctl(0, &metadata.flags.to_ne_bytes()),
+                    Err(ClientError::NotFound { .. }) => reply.error(libc::ENOENT),
+                    Err(e) => {
+                        eprintln!(
+                            "❌ [IOCTL] Errore recupero metadati per '{}': {}",
+                            path.to_string_lossy(),
+                            e
+                        );
+                        reply.error(libc::EIO);
+                    }
+                }
+            }
+            FS_IOC_SETFLAGS | FS_IOC32_SETFLAGS => {
+                let new_flags = match in_data.get(..4) {
+                    Some(bytes) => u32::from_ne_bytes(bytes.try_into().unwrap()),
+                    None => {
+                        reply.error(libc::EINVAL);
+                        return;
+                    }
+                };
+                match self.set_file_flags(req, &path, new_flags) {
+                    Ok(()) => reply.ioctl(0, &[]),
+                    Err(errno) => reply.error(errno),
+                }
+            }
+            _ => reply.error(libc::ENOTTY),
+        }
     }
 
     fn fallocate(
@@ -3057,7 +4009,113 @@ impl Filesystem for RemoteFileSystem {
         mode: i32,
         reply: fuser::ReplyEmpty,
     ) {
-        reply.error(libc::ENOSYS);
+        if offset < 0 || length <= 0 {
+            eprintln!(
+                "❌ [FALLOCATE] Intervallo non valido: {} + {}",
+                offset, length
+            );
+            reply.error(libc::EINVAL);
+            return;
+        }
+
+        let keep_size = (mode & libc::FALLOC_FL_KEEP_SIZE) != 0;
+        let operation = match mode & !libc::FALLOC_FL_KEEP_SIZE {
+            0 => AllocateOperation::Allocate,
+            libc::FALLOC_FL_PUNCH_HOLE if keep_size => AllocateOperation::PunchHole,
+            libc::FALLOC_FL_ZERO_RANGE => AllocateOperation::ZeroRange,
+            _ => {
+                log::warn!("⚠️ [FALLOCATE] Modalità non supportata: {:#x}", mode);
+                reply.error(libc::EOPNOTSUPP);
+                return;
+            }
+        };
+
+        let path = match self.open_files.get(&fh) {
+            Some(file) if (file.flags & libc::O_ACCMODE) == libc::O_RDONLY => {
+                eprintln!(
+                    "❌ [FALLOCATE] File non aperto in scrittura: {}",
+                    file.path.to_string_lossy()
+                );
+                reply.error(libc::EBADF);
+                return;
+            }
+            Some(file) => file.path.clone(),
+            None => {
+                eprintln!("❌ [FALLOCATE] File handle {} non trovato", fh);
+                reply.error(libc::EBADF);
+                return;
+            }
+        };
+
+        if let Err(errno) = self.check_not_locked(&path) {
+            reply.error(errno);
+            return;
+        }
+
+        // buffered appends come before the range in the file
+        if let Err(e) = self.flush_write_buffer(fh) {
+            eprintln!("❌ [FALLOCATE] Errore invio buffer di scrittura: {}", e);
+            reply.error(libc::EIO);
+            return;
+        }
+
+        let rt = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => {
+                let runtime = tokio::runtime::Runtime::new().expect("Failed to create runtime");
+                runtime.handle().clone()
+            }
+        };
+
+        let fallocate_request = FallocateRequest {
+            operation,
+            offset: offset as u64,
+            length: length as u64,
+            keep_size,
+        };
+
+        match rt.block_on(async { self.client.fallocate(&path, &fallocate_request).await }) {
+            Ok(()) => reply.ok(),
+            Err(ClientError::NotFound { .. }) => {
+                eprintln!(
+                    "❌ [FALLOCATE] File non trovato sul server: {}",
+                    path.to_string_lossy()
+                );
+                reply.error(libc::ENOENT);
+            }
+            Err(ClientError::PermissionDenied(_)) => {
+                eprintln!(
+                    "❌ [FALLOCATE] Permesso di scrittura negato: {}",
+                    path.to_string_lossy()
+                );
+                reply.error(libc::EACCES);
+            }
+            Err(ClientError::Server { status: 400, .. }) => {
+                eprintln!(
+                    "❌ [FALLOCATE] Richiesta non valida: {}",
+                    path.to_string_lossy()
+                );
+                reply.error(libc::EINVAL);
+            }
+            Err(ClientError::Server { status: 507, .. }) => {
+                eprintln!(
+                    "❌ [FALLOCATE] Spazio insufficiente sul server: {}",
+                    path.to_string_lossy()
+                );
+                reply.error(libc::ENOSPC);
+            }
+            Err(ClientError::Server { status: 501, .. }) => {
+                eprintln!(
+                    "❌ [FALLOCATE] Operazione non supportata dal server: {}",
+                    path.to_string_lossy()
+                );
+                reply.error(libc::EOPNOTSUPP);
+            }
+            Err(e) => {
+                eprintln!("❌ [FALLOCATE] Errore sul server: {}", e);
+                reply.error(libc::EIO);
+            }
+        }
     }
 
     fn lseek(
@@ -3069,7 +4127,72 @@ impl Filesystem for RemoteFileSystem {
         whence: i32,
         reply: fuser::ReplyLseek,
     ) {
-        reply.error(libc::ENOSYS);
+        if offset < 0 {
+            reply.error(libc::EINVAL);
+            return;
+        }
+
+        // SEEK_SET, SEEK_CUR and SEEK_END are resolved by the kernel
+        if whence != libc::SEEK_DATA && whence != libc::SEEK_HOLE {
+            reply.error(libc::EINVAL);
+            return;
+        }
+
+        let path = match self.open_files.get(&fh) {
+            Some(file) => file.path.clone(),
+            None => {
+                eprintln!("❌ [LSEEK] File handle {} non trovato", fh);
+                reply.error(libc::EBADF);
+                return;
+            }
+        };
+
+        // buffered appends are data too
+        if let Err(e) = self.flush_write_buffer(fh) {
+            eprintln!("❌ [LSEEK] Errore invio buffer di scrittura: {}", e);
+            reply.error(libc::EIO);
+            return;
+        }
+
+        let rt = match tokio::runtime::Handle::try_current() {
+            Ok(handle) => handle,
+            Err(_) => {
+                let runtime = tokio::runtime::Runtime::new().expect("Failed to create runtime");
+                runtime.handle().clone()
+            }
+        };
+
+        let extents = match rt.block_on(async { self.client.get_extents(&path).await }) {
+            Ok(extents) => extents,
+            Err(ClientError::NotFound { .. }) => {
+                eprintln!(
+                    "❌ [LSEEK] File non trovato sul server: {}",
+                    path.to_string_lossy()
+                );
+                reply.error(libc::ENOENT);
+                return;
+            }
+            Err(e) => {
+                eprintln!("❌ [LSEEK] Errore lettura extent: {}", e);
+                reply.error(libc::EIO);
+                return;
+            }
+        };
+
+        let offset = offset as u64;
+        if offset >= extents.size {
+            reply.error(libc::ENXIO);
+            return;
+        }
+
+        if whence == libc::SEEK_DATA {
+            match extents.next_data(offset) {
+                Some(data) => reply.offset(data as i64),
+                None => reply.error(libc::ENXIO),
+            }
+        } else {
+            reply.offset(extents.next_hole(offset) as i64);
+        }
     }
 
     fn copy_file_range(
@@ -3135,6 +4258,26 @@ impl Filesystem for RemoteFileSystem {
             return;
         }
 
+        let source_path = source_file.path.clone();
+        let dest_path = dest_file.path.clone();
+
+        if let Err(errno) = self.check_not_locked(&dest_path) {
+            reply.error(errno);
+            return;
+        }
+
+        // appends still buffered on either side must reach the server first
+        for fh in [fh_in, fh_out] {
+            if let Err(e) = self.flush_write_buffer(fh) {
+                eprintln!(
+                    "❌ [COPY_FILE_RANGE] Errore invio buffer di scrittura: {}",
+                    e
+                );
+                reply.error(libc::EIO);
+                return;
+            }
+        }
+
         let rt = match tokio::runtime::Handle::try_current() {
             Ok(handle) => handle,
             Err(_) => {
@@ -3142,62 +4285,63 @@ impl Filesystem for RemoteFileSystem {
                 runtime.handle().clone()
             }
         };
-        let chunk_size = std::cmp::min(len, 1024 * 1024); // Max 1MB per chunk
-
-        let source_data = match rt.block_on(async {
+        // the whole source from the start goes in one request, which the server
+        // can turn into a clone (cp --reflink); otherwise a short count makes the
+        // caller ask again for the rest
+        let whole_file = offset_in == 0
+            && offset_out == 0
+            && rt
+                .block_on(async { self.client.get_file_metadata(&source_path).await })
+                .is_ok_and(|metadata| metadata.size <= len && metadata.size <= u32::MAX as u64);
+        let length = (!whole_file).then(|| std::cmp::min(len, COPY_RANGE_MAX));
+
+        let copy_result = rt.block_on(async {
             self.client
-                .read_file(&source_file.path, Some(offset_in as u64), Some(chunk_size))
+                .copy_range(
+                    &source_path,
+                    &dest_path,
+                    offset_in as u64,
+                    offset_out as u64,
+                    length,
+                )
                 .await
-        }) {
-            Ok(data) => data.data,
-            Err(e) => {
-                eprintln!("❌ [COPY_FILE_RANGE] Errore lettura sorgente: {}", e);
-                reply.error(libc::EIO);
-                return;
-            }
-        };
-
-        let bytes_read = source_data.len() as u64;
-        let bytes_to_copy = std::cmp::min(len, bytes_read);
-
-        if bytes_to_copy == 0 {
-            reply.written(0);
-            return;
-        }
-
-        let dest_metadata =
-            match rt.block_on(async { self.client.get_file_metadata(&dest_file.path).await }) {
-                Ok(metadata) => metadata,
-                Err(e) => {
-                    eprintln!("❌ [COPY_FILE_RANGE] Errore metadati destinazione: {}", e);
-                    reply.error(libc::EIO);
-                    return;
-                }
-            };
-
-        let now_iso = chrono::Utc::now().to_rfc3339();
-        let write_request = WriteRequest {
-            offset: None,
-            path: dest_file.path.clone(),
-            new_path: None,
-            size: std::cmp::max(dest_metadata.size, (offset_out as u64) + bytes_to_copy),
-            atime: dest_metadata.atime.clone(),
-            mtime: now_iso.clone(),
-            ctime: now_iso,
-            crtime: dest_metadata.crtime.clone(),
-            kind: dest_metadata.kind,
-            ref_path: None,
-            perm: dest_metadata.perm.clone(),
-            mode: Mode::Write,
-            data: Some(source_data[..bytes_to_copy as usize].to_vec()),
-        };
+        });
 
-        match rt.block_on(async { self.client.write_file(&write_request).await }) {
-            Ok(()) => {
-                reply.written(bytes_to_copy as u32);
+        match copy_result {
+            Ok(copied) => {
+                reply.written(copied as u32);
+            }
+            Err(ClientError::NotFound { .. }) => {
+                eprintln!(
+                    "❌ [COPY_FILE_RANGE] File non trovato sul server: {} -> {}",
+                    source_path.to_string_lossy(),
+                    dest_path.to_string_lossy()
+                );
+                reply.error(libc::ENOENT);
+            }
+            Err(ClientError::PermissionDenied(_)) => {
+                eprintln!(
+                    "❌ [COPY_FILE_RANGE] Permesso negato: {}",
+                    dest_path.to_string_lossy()
+                );
+                reply.error(libc::EACCES);
+            }
+            Err(ClientError::Server { status: 400, .. }) => {
+                eprintln!(
+                    "❌ [COPY_FILE_RANGE] Copia non valida: {}",
+                    source_path.to_string_lossy()
+                );
+                reply.error(libc::EINVAL);
+            }
+            Err(ClientError::Server { status: 507, .. }) => {
+                eprintln!(
+                    "❌ [COPY_FILE_RANGE] Spazio insufficiente sul server: {}",
+                    dest_path.to_string_lossy()
+                );
+                reply.error(libc::ENOSPC);
             }
             Err(e) => {
-                eprintln!("❌ [COPY_FILE_RANGE] Errore scrittura: {}", e);
+                eprintln!("❌ [COPY_FILE_RANGE] Errore copia sul server: {}", e);
                 reply.error(libc::EIO);
             }
         }
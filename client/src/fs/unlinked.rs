@@ -0,0 +1,161 @@
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::util::path::{decode_path, encode_path};
+
+pub const UNLINKED_PREFIX: &str = ".bifrost-unlinked-";
+// hidden files named and modified longer ago are taken as left behind
+pub const UNLINKED_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 3600);
+
+pub fn is_unlinked_name(name: &OsStr) -> bool {
+    name.as_bytes().starts_with(UNLINKED_PREFIX.as_bytes())
+}
+
+// Hidden name for a file unlinked while open, unique across clients and mounts
+pub fn unlinked_name() -> OsString {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    format!("{}{:x}-{:x}", UNLINKED_PREFIX, std::process::id(), nanos).into()
+}
+
+// Pid of the client that gave a file its hidden name, and how long ago
+fn parse_unlinked_name(name: &OsStr) -> Option<(u32, Duration)> {
+    let rest = name.as_bytes().strip_prefix(UNLINKED_PREFIX.as_bytes())?;
+    let (pid, nanos) = std::str::from_utf8(rest).ok()?.split_once('-')?;
+    let pid = u32::from_str_radix(pid, 16).ok()?;
+    let then = UNIX_EPOCH + Duration::from_nanos(u64::from_str_radix(nanos, 16).ok()?);
+    let age = SystemTime::now().duration_since(then).unwrap_or_default();
+    Some((pid, age))
+}
+
+// How long ago a file got its hidden name, if it is one
+pub fn unlinked_age(name: &OsStr) -> Option<Duration> {
+    parse_unlinked_name(name).map(|(_, age)| age)
+}
+
+fn process_alive(pid: u32) -> bool {
+    if pid == std::process::id() {
+        return false; // a previous process that had the same pid
+    }
+    // signal 0 only checks that the process exists
+    let found = unsafe { libc::kill(pid as libc::pid_t, 0) } == 0;
+    found || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    let ok = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } == 0;
+    let len = if ok {
+        buf.iter().position(|&b| b == 0).unwrap_or(buf.len())
+    } else {
+        0
+    };
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+// Remote paths of the files renamed to a hidden name and not deleted yet, one per
+// line in ~/.bifrost/unlinked: the host and the server of the mount, then the path
+// in its encoded text form, separated by tabs. The file is shared by every mount
+// from the same home, so each one only deals with the lines of its server and host
+pub struct UnlinkedJournal {
+    path: PathBuf,
+    host: String,
+    server: String,
+}
+
+impl UnlinkedJournal {
+    pub fn default_path() -> PathBuf {
+        let mut path = dirs::home_dir().expect("Cannot find home directory");
+        path.push(".bifrost");
+        path.push("unlinked");
+        path
+    }
+
+    pub fn open(server: &str) -> Self {
+        UnlinkedJournal {
+            path: Self::default_path(),
+            host: hostname(),
+            server: server.to_string(),
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        fs::read_to_string(&self.path)
+            .map(|content| {
+                content
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // Path of a line written by a mount of this host and server (lines without
+    // them come from older clients, which kept one journal per home)
+    fn own_path(&self, line: &str) -> Option<OsString> {
+        match line.splitn(3, '\t').collect::<Vec<_>>()[..] {
+            [host, server, path] if host == self.host && server == self.server => {
+                Some(decode_path(path))
+            }
+            [path] => Some(decode_path(path)),
+            _ => None,
+        }
+    }
+
+    // Files left behind by mounts of this server and host that are gone, without
+    // the last release that would have deleted them
+    pub fn orphans(&self) -> Vec<OsString> {
+        self.lines()
+            .iter()
+            .filter_map(|line| self.own_path(line))
+            .filter(|path| {
+                let name = path.as_bytes().rsplit(|&b| b == b'/').next().unwrap_or(&[]);
+                parse_unlinked_name(OsStr::from_bytes(name))
+                    .is_none_or(|(pid, _)| !process_alive(pid))
+            })
+            .collect()
+    }
+
+    pub fn add(&self, path: &OsStr) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(
+            file,
+            "{}\t{}\t{}",
+            self.host,
+            self.server,
+            encode_path(path)
+        )
+    }
+
+    pub fn remove(&self, path: &OsStr) -> io::Result<()> {
+        let remaining: Vec<String> = self
+            .lines()
+            .into_iter()
+            .filter(|line| self.own_path(line).as_deref() != Some(path))
+            .collect();
+        if remaining.is_empty() {
+            return match fs::remove_file(&self.path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            };
+        }
+
+        let tmp_path = self
+            .path
+            .with_extension(format!("tmp-{}", std::process::id()));
+        fs::write(&tmp_path, remaining.join("\n") + "\n")?;
+        fs::rename(&tmp_path, &self.path)
+    }
+}
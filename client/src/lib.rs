@@ -1,4 +1,5 @@
 pub mod api;
 pub mod config;
 pub mod fs;
-pub mod util;
+pub mod pin;
+pub mod util;
\ No newline at end of file
@@ -1,5 +1,6 @@
 use clap::{Parser, Subcommand};
 use daemonize::Daemonize;
+use std::path::PathBuf;
 mod commands;
 
 #[derive(Parser)]
@@ -32,6 +33,14 @@ enum Commands {
         #[arg(long = "disable-autorun", short = 'd')]
         disable_autorun: bool,
     },
+    Pin {
+        path: Option<PathBuf>,
+        #[arg(long = "list", short = 'l', conflicts_with = "path")]
+        list: bool,
+    },
+    Unpin {
+        path: PathBuf,
+    },
 }
 
 fn main() {
@@ -61,15 +70,18 @@ fn main() {
             Commands::Register => {
                 commands::register::run().await;
             }
-            Commands::Start {
-                detached,
-                enable_autorun,
-            } => {
+            Commands::Start { enable_autorun, .. } => {
                 commands::start::run(enable_autorun).await;
             }
             Commands::Stop { disable_autorun } => {
                 commands::stop::run(disable_autorun).await;
             }
+            Commands::Pin { path, list } => {
+                commands::pin::run(path, list).await;
+            }
+            Commands::Unpin { path } => {
+                commands::unpin::run(path).await;
+            }
         }
     });
 }
@@ -0,0 +1,431 @@
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Component, Path, PathBuf};
+
+use crate::api::client::{ClientError, RemoteClient};
+use crate::api::models::{DirectoryListing, FileKind, MetaFile};
+use crate::util::path::{decode_path, encode_path, join_path};
+use sha2::{Digest, Sha256};
+
+const DOWNLOAD_CHUNK: u64 = 4 * 1024 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PinError {
+    #[error("I/O error on the pinned store: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("{0}")]
+    Client(#[from] ClientError),
+
+    #[error("Corrupted pin index: {0}")]
+    Index(String),
+
+    #[error("`{0}` is not pinned")]
+    NotPinned(String),
+
+    #[error("`{0}` does not fit in the pinned store")]
+    InvalidPath(String),
+}
+
+// The index keys paths by their text form (see encode_path), so that any name fits
+// in index.json
+
+// What the store knows about one hydrated remote path
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PinnedEntry {
+    pub metadata: MetaFile,             // `name` is the full remote path
+    pub listing: Option<Vec<MetaFile>>, // children, for directories only
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PinIndex {
+    roots: Vec<String>,
+    entries: HashMap<String, PinnedEntry>,
+}
+
+pub struct PinnedRoot {
+    pub path: OsString,
+    pub files: u64,
+    pub size: u64,
+}
+
+// Local copies of the pinned files and folders, kept under ~/.bifrost/pinned and
+// shared between the `pin` commands and the mount through index.json
+pub struct PinStore {
+    dir: PathBuf,
+    index: Mutex<PinIndex>,
+}
+
+fn is_under(path: &str, root: &str) -> bool {
+    root == "/" || path == root || path.starts_with(&format!("{}/", root))
+}
+
+impl PinStore {
+    pub fn default_path() -> PathBuf {
+        let mut path = dirs::home_dir().expect("Cannot find home directory");
+        path.push(".bifrost");
+        path.push("pinned");
+        path
+    }
+
+    pub fn open() -> Result<Self, PinError> {
+        let store = PinStore {
+            dir: Self::default_path(),
+            index: Mutex::new(PinIndex::default()),
+        };
+        fs::create_dir_all(store.dir.join("data"))?;
+        store.reload()?;
+        Ok(store)
+    }
+
+    // Picks up the pins added or removed by other bifrost processes
+    pub fn reload(&self) -> Result<(), PinError> {
+        let index_path = self.dir.join("index.json");
+        let index = if index_path.exists() {
+            let content = fs::read(&index_path)?;
+            serde_json::from_slice(&content).map_err(|e| PinError::Index(e.to_string()))?
+        } else {
+            PinIndex::default()
+        };
+        *self.index.lock() = index;
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), PinError> {
+        let content = serde_json::to_vec_pretty(&*self.index.lock())
+            .map_err(|e| PinError::Index(e.to_string()))?;
+        let tmp_path = self.dir.join("index.json.tmp");
+        fs::write(&tmp_path, content)?;
+        fs::rename(&tmp_path, self.dir.join("index.json"))?;
+        Ok(())
+    }
+
+    // Local copy of `path`, always under data/: the names come from the server, and
+    // a `..` among them would lead out of the store
+    fn data_path(&self, path: &OsStr) -> Result<PathBuf, PinError> {
+        let mut local = self.dir.join("data");
+        for component in Path::new(path).components() {
+            match component {
+                Component::RootDir | Component::CurDir => {}
+                Component::Normal(name) => local.push(name),
+                Component::ParentDir | Component::Prefix(_) => {
+                    return Err(PinError::InvalidPath(path.to_string_lossy().into_owned()));
+                }
+            }
+        }
+        Ok(local)
+    }
+
+    // Where the download of `path` is written before taking its place, away from the
+    // copies so that it cannot be mistaken for one
+    fn part_path(&self, path: &OsStr) -> PathBuf {
+        let digest = Sha256::digest(path.as_bytes());
+        self.dir.join("partial").join(hex::encode(digest))
+    }
+
+    pub fn pinned(&self) -> Vec<PinnedRoot> {
+        let index = self.index.lock();
+        index
+            .roots
+            .iter()
+            .map(|root| {
+                let files = index
+                    .entries
+                    .iter()
+                    .filter(|(path, entry)| {
+                        is_under(path, root) && entry.metadata.kind != FileKind::Directory
+                    })
+                    .map(|(_, entry)| entry.metadata.size);
+                PinnedRoot {
+                    path: decode_path(root),
+                    files: files.clone().count() as u64,
+                    size: files.sum(),
+                }
+            })
+            .collect()
+    }
+
+    // Hydrates `path` and everything below it, returning the pinned size
+    pub async fn pin(&self, client: &RemoteClient, path: &OsStr) -> Result<u64, PinError> {
+        let path = encode_path(path);
+        self.reload()?;
+        self.hydrate(client, &path).await?;
+        {
+            let mut index = self.index.lock();
+            if !index.roots.contains(&path) {
+                index.roots.push(path.clone());
+            }
+        }
+        self.save()?;
+
+        Ok(self
+            .pinned()
+            .into_iter()
+            .find(|root| encode_path(&root.path) == path)
+            .map_or(0, |root| root.size))
+    }
+
+    pub fn unpin(&self, path: &OsStr) -> Result<(), PinError> {
+        let display = path.to_string_lossy().into_owned();
+        let path = encode_path(path);
+        let path = path.as_str();
+        self.reload()?;
+        let removed: Vec<String> = {
+            let mut index = self.index.lock();
+            let before = index.roots.len();
+            index.roots.retain(|root| root != path);
+            if index.roots.len() == before {
+                return Err(PinError::NotPinned(display));
+            }
+
+            // entries still covered by another pin stay
+            let roots = index.roots.clone();
+            let removed: Vec<String> = index
+                .entries
+                .keys()
+                .filter(|p| is_under(p, path) && !roots.iter().any(|root| is_under(p, root)))
+                .cloned()
+                .collect();
+            for p in &removed {
+                index.entries.remove(p);
+            }
+            removed
+        };
+        self.save()?;
+
+        for p in removed {
+            if let Ok(local) = self.data_path(&decode_path(&p)) {
+                if local.is_file() {
+                    let _ = fs::remove_file(&local);
+                }
+            }
+        }
+        if let Ok(local) = self.data_path(&decode_path(path)) {
+            if local.is_dir() && path != "/" {
+                let _ = fs::remove_dir_all(&local);
+            }
+        }
+        Ok(())
+    }
+
+    // Brings every pin up to date with the server: changed files are downloaded
+    // again and entries deleted remotely are dropped
+    pub async fn refresh(&self, client: &RemoteClient) -> Result<(), PinError> {
+        self.reload()?;
+        let roots = self.index.lock().roots.clone();
+
+        // a root that fails keeps what it had, and the others go on
+        let mut seen = HashSet::new();
+        let mut failed = None;
+        for root in &roots {
+            match self.hydrate(client, root).await {
+                Ok(paths) => seen.extend(paths),
+                Err(PinError::Client(ClientError::NotFound { .. })) => {}
+                Err(e) => {
+                    eprintln!(
+                        "❌ [PIN] Cannot refresh {}: {}",
+                        decode_path(root).to_string_lossy(),
+                        e
+                    );
+                    let index = self.index.lock();
+                    seen.extend(index.entries.keys().filter(|p| is_under(p, root)).cloned());
+                    failed.get_or_insert(e);
+                }
+            }
+        }
+
+        let stale: Vec<String> = {
+            let mut index = self.index.lock();
+            let stale: Vec<String> = index
+                .entries
+                .keys()
+                .filter(|p| !seen.contains(*p))
+                .cloned()
+                .collect();
+            for p in &stale {
+                index.entries.remove(p);
+            }
+            stale
+        };
+        for p in stale {
+            if let Ok(local) = self.data_path(&decode_path(&p)) {
+                if local.is_file() {
+                    let _ = fs::remove_file(&local);
+                }
+            }
+        }
+
+        self.save()?;
+        failed.map_or(Ok(()), Err)
+    }
+
+    // Walks the tree one level at a time, listing the directories of each level in
+    // parallel
+    async fn hydrate(&self, client: &RemoteClient, root: &str) -> Result<Vec<String>, PinError> {
+        let mut seen = Vec::new();
+        let mut level = vec![client.get_file_metadata(&decode_path(root)).await?];
+
+        while !level.is_empty() {
+            let directories: Vec<OsString> = level
+                .iter()
+                .filter(|metadata| metadata.kind == FileKind::Directory)
+                .map(|metadata| metadata.name.clone())
+                .collect();
+            let mut listings = client.fetch_directories(&directories).await.into_iter();
+            let mut next_level = Vec::new();
+
+            for metadata in level {
+                let remote = metadata.name.clone();
+                let path = encode_path(&remote);
+
+                let listing = match metadata.kind {
+                    FileKind::Directory => {
+                        let listing = listings.next().expect("one listing per directory")?;
+                        fs::create_dir_all(self.data_path(&remote)?)?;
+                        for child in &listing.files {
+                            // a single name each, as a listing has to give them
+                            let name = child.name.as_bytes();
+                            if name.is_empty()
+                                || name == b"."
+                                || name == b".."
+                                || name.contains(&b'/')
+                            {
+                                return Err(PinError::InvalidPath(
+                                    child.name.to_string_lossy().into_owned(),
+                                ));
+                            }
+                            let mut child_metadata = child.clone();
+                            child_metadata.name = join_path(&remote, &child.name);
+                            next_level.push(child_metadata);
+                        }
+                        Some(listing.files)
+                    }
+                    FileKind::RegularFile | FileKind::Hardlink => {
+                        if !self.is_current(&metadata) {
+                            self.download(client, &remote, metadata.size).await?;
+                        }
+                        None
+                    }
+                    // no content to keep: links and special files are their metadata
+                    FileKind::Symlink
+                    | FileKind::Fifo
+                    | FileKind::Socket
+                    | FileKind::CharDevice
+                    | FileKind::BlockDevice => None,
+                };
+
+                self.index
+                    .lock()
+                    .entries
+                    .insert(path.clone(), PinnedEntry { metadata, listing });
+                seen.push(path);
+            }
+            level = next_level;
+        }
+
+        Ok(seen)
+    }
+
+    fn is_current(&self, metadata: &MetaFile) -> bool {
+        let index = self.index.lock();
+        let Some(entry) = index.entries.get(&encode_path(&metadata.name)) else {
+            return false;
+        };
+        entry.metadata.size == metadata.size
+            && entry.metadata.mtime == metadata.mtime
+            && self
+                .data_path(&metadata.name)
+                .and_then(|local| Ok(fs::metadata(local)?))
+                .is_ok_and(|local| local.len() == metadata.size)
+    }
+
+    async fn download(
+        &self,
+        client: &RemoteClient,
+        path: &OsStr,
+        size: u64,
+    ) -> Result<(), PinError> {
+        let local = self.data_path(path)?;
+        if let Some(parent) = local.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // written aside and renamed, so the mount never serves a partial copy
+        let part = self.part_path(path);
+        fs::create_dir_all(self.dir.join("partial"))?;
+        let mut file = fs::File::create(&part)?;
+        let mut written = 0u64;
+        while written < size {
+            let chunk = client
+                .http_read_range(path, written, DOWNLOAD_CHUNK)
+                .await?;
+            if chunk.is_empty() {
+                break;
+            }
+            file.write_all(&chunk)?;
+            written += chunk.len() as u64;
+        }
+        file.sync_all()?;
+        fs::rename(&part, &local)?;
+        Ok(())
+    }
+
+    pub fn metadata(&self, path: &OsStr) -> Option<MetaFile> {
+        self.index
+            .lock()
+            .entries
+            .get(&encode_path(path))
+            .map(|entry| entry.metadata.clone())
+    }
+
+    pub fn listing(&self, path: &OsStr) -> Option<DirectoryListing> {
+        self.index
+            .lock()
+            .entries
+            .get(&encode_path(path))
+            .and_then(|entry| entry.listing.clone())
+            .map(|files| DirectoryListing { files })
+    }
+
+    // Serves a read from the local copy, if `path` is pinned and hydrated
+    pub fn read(&self, path: &OsStr, offset: u64, size: u64) -> Option<Vec<u8>> {
+        let file_size = {
+            let index = self.index.lock();
+            let entry = index.entries.get(&encode_path(path))?;
+            if entry.listing.is_some() || entry.metadata.kind == FileKind::Symlink {
+                return None;
+            }
+            entry.metadata.size
+        };
+
+        let mut file = fs::File::open(self.data_path(path).ok()?).ok()?;
+        if file.metadata().ok()?.len() != file_size {
+            return None;
+        }
+        file.seek(SeekFrom::Start(offset)).ok()?;
+        let mut data = Vec::with_capacity(size.min(file_size.saturating_sub(offset)) as usize);
+        file.take(size).read_to_end(&mut data).ok()?;
+        Some(data)
+    }
+
+    // The mount changed `path`: stop serving the local copy until the next refresh
+    pub fn invalidate(&self, path: &OsStr) {
+        // on top of the pins the other processes may have changed meanwhile
+        let _ = self.reload();
+        let removed = self.index.lock().entries.remove(&encode_path(path));
+        if removed.is_some() {
+            if let Ok(local) = self.data_path(path) {
+                let _ = fs::remove_file(local);
+            }
+            // saved at once, or the other processes would still look for the copy
+            if let Err(e) = self.save() {
+                log::warn!("⚠️ [PIN] Cannot save the index: {}", e);
+            }
+        }
+    }
+}
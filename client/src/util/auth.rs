@@ -11,6 +11,10 @@ use crate::util::fs::get_current_user;
 
 type HmacSha256 = Hmac<Sha256>;
 
+// Signed in place of the digest of a body the server streams rather than holds
+// back, as flagged by its `X-Response-Body` header
+pub const STREAMED_BODY: &str = "streamed";
+
 #[derive(Deserialize)]
 pub struct UserKeys {
     pub api_key: String,
@@ -97,6 +101,38 @@ impl UserKeys {
         hex::encode(signature_bytes)
     }
 
+    // Checks the X-Response-Signature of an answer: HMAC over its status, the nonce
+    // of the request, the SHA-256 of its body (STREAMED_BODY for a streamed one, None
+    // here) and the signed headers it carries (X-Next-Cursor, X-Content-Sha256,
+    // X-Written-Sha256), in this order
+    pub fn verify_response(
+        &self,
+        status: u16,
+        nonce: &str,
+        body: Option<&[u8]>,
+        signed_headers: &[Option<&str>],
+        signature: &str,
+    ) -> bool {
+        let Ok(signature) = hex::decode(signature) else {
+            return false;
+        };
+
+        let body_digest = match body {
+            Some(body) => format!("{:x}", Sha256::digest(body)),
+            None => STREAMED_BODY.to_string(),
+        };
+        let mut message = format!("{}\n{}\n{}", status, nonce, body_digest);
+        for value in signed_headers.iter().flatten() {
+            message.push('\n');
+            message.push_str(value);
+        }
+
+        let mut hmac = HmacSha256::new_from_slice(self.secret_key.as_bytes())
+            .expect("HMAC can take key of any size");
+        hmac.update(message.as_bytes());
+        hmac.verify_slice(&signature).is_ok()
+    }
+
     pub fn generate_timestamp() -> i64 {
         Utc::now().timestamp_millis()
     }
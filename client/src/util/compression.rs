@@ -0,0 +1,99 @@
+use std::ffi::OsStr;
+use std::io::{self, Read, Write};
+
+use crate::util::path::get_file_name;
+
+// Value of the Accept-Encoding header of every request, preferred first
+pub const ACCEPT_ENCODING: &str = "zstd, gzip";
+
+// Below this size compressing an upload does not pay for itself
+const MIN_COMPRESSED_SIZE: usize = 1024;
+
+// Formats that are compressed already: compressing them again wastes time
+const COMPRESSED_EXTENSIONS: &[&str] = &[
+    "7z", "aac", "apk", "avi", "avif", "br", "bz2", "docx", "flac", "gif", "gz", "heic", "jar",
+    "jpeg", "jpg", "lz4", "lzma", "m4a", "m4v", "mkv", "mov", "mp3", "mp4", "odt", "ogg", "opus",
+    "png", "pptx", "rar", "tgz", "webm", "webp", "woff2", "xlsx", "xz", "zip", "zst",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Zstd,
+    Gzip,
+}
+
+impl ContentEncoding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ContentEncoding::Zstd => "zstd",
+            ContentEncoding::Gzip => "gzip",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "zstd" => Some(ContentEncoding::Zstd),
+            "gzip" => Some(ContentEncoding::Gzip),
+            _ => None,
+        }
+    }
+
+    // Preferred encoding among those listed by the server in its Accept-Encoding
+    pub fn negotiate(accept_encoding: &str) -> Option<Self> {
+        let listed: Vec<Self> = accept_encoding.split(',').filter_map(Self::parse).collect();
+        [ContentEncoding::Zstd, ContentEncoding::Gzip]
+            .into_iter()
+            .find(|encoding| listed.contains(encoding))
+    }
+}
+
+pub fn is_compressed_media(path: &OsStr) -> bool {
+    let name = get_file_name(path).to_string_lossy().to_ascii_lowercase();
+    match name.rsplit_once('.') {
+        Some((_, extension)) => COMPRESSED_EXTENSIONS.contains(&extension),
+        None => false,
+    }
+}
+
+// The compressed data, or None when it would not be smaller
+pub fn compress(encoding: ContentEncoding, data: &[u8]) -> Option<Vec<u8>> {
+    if data.len() < MIN_COMPRESSED_SIZE {
+        return None;
+    }
+    let compressed = match encoding {
+        ContentEncoding::Zstd => zstd::stream::encode_all(data, 0).ok()?,
+        ContentEncoding::Gzip => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data).ok()?;
+            encoder.finish().ok()?
+        }
+    };
+    (compressed.len() < data.len()).then_some(compressed)
+}
+
+// `max_len` bounds the output, against data that expands without end
+pub fn decompress(encoding: ContentEncoding, data: &[u8], max_len: usize) -> io::Result<Vec<u8>> {
+    let mut decoded = Vec::with_capacity((data.len() * 2).min(max_len));
+    // one byte more than allowed tells an output that is too long
+    let limit = max_len as u64 + 1;
+    match encoding {
+        ContentEncoding::Zstd => {
+            zstd::stream::read::Decoder::new(data)?
+                .take(limit)
+                .read_to_end(&mut decoded)?;
+        }
+        ContentEncoding::Gzip => {
+            flate2::read::GzDecoder::new(data)
+                .take(limit)
+                .read_to_end(&mut decoded)?;
+        }
+    }
+    if decoded.len() > max_len {
+        return Err(io::Error::other(format!(
+            "decoded data larger than {} bytes",
+            max_len
+        )));
+    }
+    Ok(decoded)
+}
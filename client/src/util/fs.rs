@@ -1,7 +1,8 @@
 use users::get_current_username;
 
 pub fn format_permissions(perm: &str) -> String {
-    if perm.len() == 3 && perm.chars().all(|c| c.is_ascii_digit() && c <= '7') {
+    if (perm.len() == 3 || perm.len() == 4) && perm.chars().all(|c| c.is_ascii_digit() && c <= '7')
+    {
         return perm.to_string();
     }
 
@@ -31,7 +32,7 @@ pub fn format_permissions(perm: &str) -> String {
 pub fn symbolic_to_octal(symbolic: &str) -> String {
     let mut octal = 0;
 
-    if symbolic.chars().nth(0) == Some('r') {
+    if symbolic.starts_with('r') {
         octal += 400;
     }
     if symbolic.chars().nth(1) == Some('w') {
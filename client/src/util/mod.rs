@@ -1,4 +1,5 @@
 pub mod auth;
+pub mod compression;
 pub mod date;
 pub mod fs;
 pub mod path;
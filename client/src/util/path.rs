@@ -1,31 +1,182 @@
-pub fn get_parent_path(path: &str) -> String {
-    if path == "/" {
-        return "/".to_string();
+use std::ffi::{OsStr, OsString};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+pub fn get_parent_path(path: &OsStr) -> OsString {
+    let bytes = path.as_bytes();
+    if bytes == b"/" {
+        return OsString::from("/");
+    }
+
+    let clean_path = trim_trailing_slashes(bytes);
+
+    match clean_path.iter().rposition(|&b| b == b'/') {
+        Some(0) | None => OsString::from("/"),
+        Some(last_slash) => OsStr::from_bytes(&clean_path[..last_slash]).to_os_string(),
+    }
+}
+
+pub fn get_file_name(path: &OsStr) -> OsString {
+    let bytes = path.as_bytes();
+    if bytes == b"/" {
+        return OsString::new();
     }
 
-    let clean_path = path.trim_end_matches('/');
+    let clean_path = trim_trailing_slashes(bytes);
+
+    match clean_path.iter().rposition(|&b| b == b'/') {
+        Some(last_slash) => OsStr::from_bytes(&clean_path[last_slash + 1..]).to_os_string(),
+        None => OsStr::from_bytes(clean_path).to_os_string(),
+    }
+}
 
-    if let Some(last_slash) = clean_path.rfind('/') {
-        if last_slash == 0 {
-            "/".to_string()
-        } else {
-            clean_path[..last_slash].to_string()
+pub fn join_path(parent: &OsStr, name: &OsStr) -> OsString {
+    let mut path = parent.as_bytes().to_vec();
+    if path.last() != Some(&b'/') {
+        path.push(b'/');
+    }
+    path.extend_from_slice(name.as_bytes());
+    OsString::from_vec(path)
+}
+
+fn trim_trailing_slashes(bytes: &[u8]) -> &[u8] {
+    let end = bytes.iter().rposition(|&b| b != b'/').map_or(0, |i| i + 1);
+    &bytes[..end]
+}
+
+// Text form of a path, as sent to the server and kept in the local indexes: valid
+// UTF-8 is kept as is, while "%", control characters and every byte that is not
+// valid UTF-8 become %XX. Any name Linux accepts survives the round trip
+pub fn encode_path(path: &OsStr) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for chunk in path.as_bytes().utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if c == '%' || c.is_ascii_control() {
+                encoded.push_str(&format!("%{:02X}", c as u32));
+            } else {
+                encoded.push(c);
+            }
+        }
+        for byte in chunk.invalid() {
+            encoded.push_str(&format!("%{:02X}", byte));
         }
-    } else {
-        "/".to_string()
     }
+    encoded
 }
 
-pub fn get_file_name(path: &str) -> String {
-    if path == "/" {
-        return "".to_string();
+pub fn decode_path(encoded: &str) -> OsString {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
     }
+    OsString::from_vec(decoded)
+}
 
-    let clean_path = path.trim_end_matches('/');
+// Paths and names in JSON, in their encoded text form
+pub mod serde_path {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::ffi::OsString;
+
+    pub fn serialize<S: Serializer>(path: &OsString, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::encode_path(path))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<OsString, D::Error> {
+        Ok(super::decode_path(&String::deserialize(deserializer)?))
+    }
+}
+
+pub mod serde_opt_path {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::ffi::OsString;
+
+    pub fn serialize<S: Serializer>(
+        path: &Option<OsString>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match path {
+            Some(path) => serializer.serialize_some(&super::encode_path(path)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<OsString>, D::Error> {
+        Ok(Option::<String>::deserialize(deserializer)?.map(|path| super::decode_path(&path)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(bytes: &[u8]) -> String {
+        let encoded = encode_path(OsStr::from_bytes(bytes));
+        assert_eq!(decode_path(&encoded).as_bytes(), bytes, "{:?}", encoded);
+        encoded
+    }
+
+    #[test]
+    fn keeps_valid_utf8() {
+        assert_eq!(
+            round_trip("/dir/città €.txt".as_bytes()),
+            "/dir/città €.txt"
+        );
+    }
+
+    #[test]
+    fn escapes_percent() {
+        assert_eq!(round_trip(b"/100%/%41"), "/100%25/%2541");
+    }
+
+    #[test]
+    fn escapes_control_bytes() {
+        assert_eq!(round_trip(b"/a\nb\tc\x7f\x01"), "/a%0Ab%09c%7F%01");
+    }
+
+    #[test]
+    fn escapes_invalid_utf8() {
+        assert_eq!(round_trip(b"/\xff\xfe"), "/%FF%FE");
+        // truncated sequence, lone continuation byte, surrogate
+        assert_eq!(round_trip(b"\xe2\x82"), "%E2%82");
+        assert_eq!(round_trip(b"\x80"), "%80");
+        assert_eq!(round_trip(b"\xed\xa0\x80"), "%ED%A0%80");
+    }
+
+    #[test]
+    fn escapes_overlong_sequences() {
+        // "/" and NUL in their overlong forms must not turn into the real bytes
+        assert_eq!(round_trip(b"\xc0\xaf"), "%C0%AF");
+        assert_eq!(round_trip(b"\xe0\x80\xaf"), "%E0%80%AF");
+        assert_eq!(round_trip(b"\xc0\x80"), "%C0%80");
+    }
+
+    #[test]
+    fn round_trips_every_byte() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        round_trip(&bytes);
+    }
 
-    if let Some(last_slash) = clean_path.rfind('/') {
-        clean_path[last_slash + 1..].to_string()
-    } else {
-        clean_path.to_string()
+    #[test]
+    fn decodes_malformed_escapes_literally() {
+        assert_eq!(decode_path("%zz%4").as_bytes(), b"%zz%4");
+        assert_eq!(decode_path("%").as_bytes(), b"%");
+        assert_eq!(decode_path("%2f%2F").as_bytes(), b"//");
     }
 }
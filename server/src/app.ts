@@ -3,6 +3,7 @@ import morgan from "morgan";
 import { sinkErrorHandler } from "./middleware/error";
 import { filesRouter } from "./router/filesRouter";
 import { usersRouter } from "./router/usersRouter";
+import { timeRouter } from "./router/timeRouter";
 import { checkUsersPath } from "./utils/path";
 const app = express();
 
@@ -18,6 +19,7 @@ app.use((_req, _res, next) => {
 // Routes
 app.use("", filesRouter);
 app.use("/users", usersRouter);
+app.use("/time", timeRouter);
 
 // Error handler middleware. Do not move
 app.use(sinkErrorHandler);
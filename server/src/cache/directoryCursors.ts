@@ -0,0 +1,147 @@
+import NodeCache from "node-cache";
+import { randomBytes } from "crypto";
+import { Dir } from "fs";
+import fs from "fs/promises";
+
+// open listings of a user; past them, the one left unused the longest is closed
+const MAX_LISTINGS = 32;
+
+type Page = { names: Buffer[]; next?: string };
+
+type Listing = {
+  owner: string;
+  dirPath: Buffer;
+  dir?: Dir; // closed once read to the end
+  served: number; // pages served so far
+  last?: Page; // the last of them, for a request repeated after a lost answer
+  turn: Promise<unknown>; // pages are read one at a time
+};
+
+// Directories being listed a page at a time: every page resumes the iteration
+// where the previous one stopped, so a listing reads the directory once however
+// many pages it takes. A cursor names a listing and the page it asks for, and
+// the last page served can be asked for again
+class DirectoryCursors {
+  // listings of each user, the one used last at the end
+  private static owners = new Map<string, string[]>();
+
+  private static cache = (() => {
+    const cache = new NodeCache({
+      stdTTL: 60, // 1 min TTL, a client asks for the next page right away
+      checkperiod: 30,
+      useClones: false,
+    });
+    // expired or evicted: the file descriptor goes with the listing
+    cache.on("del", (id: string, listing: Listing) => {
+      listing.dir?.close().catch(() => undefined);
+      const ids = DirectoryCursors.owners.get(listing.owner);
+      if (!ids) return;
+      const index = ids.indexOf(id);
+      if (index !== -1) ids.splice(index, 1);
+      if (ids.length === 0) DirectoryCursors.owners.delete(listing.owner);
+    });
+    return cache;
+  })();
+
+  // next `limit` names of the directory at dirPath, in the order it returns
+  // them, starting over without a cursor; `next` is the cursor of the rest,
+  // and undefined is returned for a cursor unknown or expired
+  static async page(
+    owner: string,
+    dirPath: Buffer,
+    cursor: string | undefined,
+    limit: number
+  ): Promise<Page | undefined> {
+    if (!cursor) return this.open(owner, dirPath, limit);
+
+    const [id, number] = cursor.split("-");
+    const wanted = Number(number);
+    const listing = this.cache.get<Listing>(id);
+    if (
+      !listing ||
+      !Number.isInteger(wanted) ||
+      listing.owner !== owner ||
+      !listing.dirPath.equals(dirPath)
+    )
+      return undefined;
+
+    // in use again: the TTL starts over and the listing is the one used last
+    this.cache.ttl(id);
+    const ids = this.owners.get(owner) ?? [];
+    const index = ids.indexOf(id);
+    if (index !== -1) ids.push(...ids.splice(index, 1));
+
+    const turn = listing.turn.then(() => {
+      if (wanted === listing.served - 1) return listing.last;
+      if (wanted !== listing.served || !listing.dir) return undefined;
+      return this.read(id, listing, limit);
+    });
+    listing.turn = turn.catch(() => undefined);
+    return turn;
+  }
+
+  private static async open(
+    owner: string,
+    dirPath: Buffer,
+    limit: number
+  ): Promise<Page> {
+    // names are read as bytes: they need not be valid UTF-8
+    const dir = await fs.opendir(dirPath, {
+      encoding: "buffer" as BufferEncoding,
+    });
+    const id = randomBytes(12).toString("hex");
+    const listing: Listing = {
+      owner,
+      dirPath,
+      dir,
+      served: 0,
+      turn: Promise.resolve(),
+    };
+
+    const ids = this.owners.get(owner) ?? [];
+    while (ids.length >= MAX_LISTINGS) this.cache.del(ids.shift()!);
+    ids.push(id);
+    this.owners.set(owner, ids);
+    this.cache.set(id, listing);
+
+    const page = await this.read(id, listing, limit);
+    // read in one page: there is nothing to resume
+    if (!page.next) this.cache.del(id);
+    return page;
+  }
+
+  private static async read(
+    id: string,
+    listing: Listing,
+    limit: number
+  ): Promise<Page> {
+    const names: Buffer[] = [];
+    let done = false;
+    try {
+      while (names.length < limit) {
+        const entry = await listing.dir!.read();
+        if (!entry) {
+          done = true;
+          break;
+        }
+        names.push(entry.name as unknown as Buffer);
+      }
+    } catch (e) {
+      this.cache.del(id);
+      throw e;
+    }
+
+    listing.served += 1;
+    if (done) {
+      await listing.dir!.close().catch(() => undefined);
+      listing.dir = undefined;
+    }
+    listing.last = {
+      names,
+      next: done ? undefined : `${id}-${listing.served}`,
+    };
+    return listing.last;
+  }
+}
+
+export default DirectoryCursors;
@@ -0,0 +1,32 @@
+import NodeCache from "node-cache";
+
+// Outcome of the requests carrying an `Idempotency-Key`, so that a client
+// retrying one after a lost answer does not apply it twice
+class IdempotencyCache {
+  private static cache = new NodeCache({
+    stdTTL: 600, // 10 min TTL, well beyond the retries of a client
+    checkperiod: 60,
+    useClones: false,
+  });
+
+  // runs `operation` once per key: a replay waits for the first run and shares
+  // its outcome, a failed run can be attempted again
+  static async once(
+    key: string,
+    operation: () => Promise<void>
+  ): Promise<void> {
+    const previous = this.cache.get<Promise<void>>(key);
+    if (previous) return previous;
+
+    const run = operation();
+    this.cache.set(key, run);
+    try {
+      await run;
+    } catch (e) {
+      this.cache.del(key);
+      throw e;
+    }
+  }
+}
+
+export default IdempotencyCache;
@@ -8,6 +8,12 @@ export class FileError extends Error {
     this.statusCode = statusCode;
   }
 
+  static CursorExpired(
+    message = "The listing cursor is unknown or has expired"
+  ) {
+    return new FileError(message, StatusCodes.GONE);
+  }
+
   static DirectoryNotEmpty(
     message = "The directory at the provided path is not empty"
   ) {
@@ -34,6 +40,10 @@ export class FileError extends Error {
     return new FileError(message, StatusCodes.BAD_REQUEST);
   }
 
+  static IsADirectory(message = "Expected a file, but found a directory") {
+    return new FileError(message, StatusCodes.BAD_REQUEST);
+  }
+
   static NotADirectory(message = "Expected a directory, but found a file") {
     return new FileError(message, StatusCodes.BAD_REQUEST);
   }
@@ -50,6 +60,18 @@ export class FileError extends Error {
     return new FileError(message, StatusCodes.FORBIDDEN);
   }
 
+  static OperationNotSupported(
+    message = "The file system does not support the operation"
+  ) {
+    return new FileError(message, StatusCodes.NOT_IMPLEMENTED);
+  }
+
+  static OverlappingRanges(
+    message = "The source and destination ranges overlap in the same file"
+  ) {
+    return new FileError(message, StatusCodes.BAD_REQUEST);
+  }
+
   static ParentDirectoryNotFound(message = "Parent directory does not exist") {
     return new FileError(message, StatusCodes.CONFLICT);
   }
@@ -7,6 +7,7 @@ import { UserError } from "../error/userError";
 import NonceCache from "../cache/nonceCache";
 import UserCache from "../cache/userCache";
 import { getUserPath } from "../utils/path";
+import { signResponse } from "./responseSignature";
 
 export async function checkAuth(
   req: Request,
@@ -20,6 +21,8 @@ export async function checkAuth(
     const nonce = req.header("X-Nonce");
     // Range header for GET /files/{path}
     const range = req.header("Range");
+    // Idempotency-Key header for the appends of PUT /files/{path}
+    const idempotencyKey = req.header("Idempotency-Key");
 
     if (!apiKey || !signature || !timestamp || !nonce) {
       return next(AuthError.MissingHeaders());
@@ -29,6 +32,8 @@ export async function checkAuth(
     const now = Date.now();
     const reqTime = parseInt(timestamp, 10);
     if (isNaN(reqTime) || Math.abs(now - reqTime) > 5 * 60 * 1000) {
+      // tells the client to measure its clock again and sign the request again
+      res.setHeader("X-Server-Time", String(now));
       return next(AuthError.InvalidTimestamp());
     }
 
@@ -43,11 +48,17 @@ export async function checkAuth(
     }
 
     const method = req.method.toUpperCase();
-    const path = req.path;
+    // the query string (e.g. the pagination of GET /list/{path}) is signed as well
+    const queryIndex = req.originalUrl.indexOf("?");
+    const path =
+      queryIndex === -1
+        ? req.path
+        : req.path + req.originalUrl.slice(queryIndex);
 
     const messageParts = [method, path, timestamp, nonce];
 
     if (range) messageParts.push(range);
+    if (idempotencyKey) messageParts.push(idempotencyKey);
 
     const extrasHashed = [];
 
@@ -67,6 +78,12 @@ export async function checkAuth(
           .digest("hex")
       );
 
+    // plain JSON bodies (POST /copy/{path}) are signed as a whole
+    if (req.is("application/json") && Object.keys(req.body).length > 0)
+      extrasHashed.push(
+        createHash("sha256").update(JSON.stringify(req.body)).digest("hex")
+      );
+
     if (extrasHashed.length > 0) {
       messageParts.push(extrasHashed.join("\n"));
     }
@@ -82,6 +99,8 @@ export async function checkAuth(
     }
 
     req.userPath = getUserPath(user.username, user.apiKey);
+    // from here on, errors included, the answer is signed for the client
+    signResponse(res, user.secretKey, nonce);
 
     try {
       await fs.stat(req.userPath);
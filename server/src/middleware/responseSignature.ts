@@ -0,0 +1,137 @@
+import { Response } from "express";
+import { createHash, createHmac } from "crypto";
+import {
+  SUPPORTED_ENCODINGS,
+  compress,
+  negotiateEncoding,
+} from "../utils/compression";
+
+type Callback = (error?: Error | null) => void;
+
+// res.write and res.end as they were, taking their arguments as given
+type Passthrough<T> = (
+  chunk?: unknown,
+  encoding?: unknown,
+  callback?: unknown
+) => T;
+
+function toBuffer(chunk: unknown, encoding?: unknown): Buffer {
+  if (Buffer.isBuffer(chunk)) return chunk;
+  if (chunk instanceof Uint8Array) return Buffer.from(chunk);
+  return Buffer.from(
+    String(chunk),
+    typeof encoding === "string" ? (encoding as BufferEncoding) : "utf8"
+  );
+}
+
+// Bodies up to this size are signed whole; larger ones are streamed
+export const SIGNED_BODY_LIMIT = 1024 * 1024;
+
+// signed along with the body, in this order, when present
+const SIGNED_HEADERS = [
+  "X-Next-Cursor",
+  "X-Content-Sha256",
+  "X-Written-Sha256",
+  "Accept-Encoding",
+];
+
+// stands for the digest of a streamed body in the signed message
+const STREAMED_BODY = "streamed";
+
+/**
+ * Signs the response to an authenticated request with the secret of its user,
+ * so that the client can tell it was not tampered with on the way. The body is
+ * held back until the handler ends the response, then sent in one go along
+ * with the X-Response-Signature header: HMAC-SHA256 over the status, the nonce
+ * of the request, the SHA-256 of the body and the X-Next-Cursor,
+ * X-Content-Sha256, X-Written-Sha256 and Accept-Encoding headers, if any. The
+ * body is then compressed, after the signature, when the request accepts an
+ * encoding the server supports and the handler did not set
+ * `res.locals.compressible` to false.
+ *
+ * A body growing past SIGNED_BODY_LIMIT is not held back: it is streamed as it
+ * comes, with backpressure, marked by `X-Response-Body: streamed` and signed
+ * with "streamed" in place of its digest. Its content is covered only by the
+ * checksums of the file it comes from (X-Content-Sha256 or the block hashes),
+ * when the file has them; clients in strict mode refuse such answers
+ */
+export function signResponse(res: Response, secretKey: string, nonce: string) {
+  const chunks: Buffer[] = [];
+  let buffered = 0;
+  let streaming = false;
+  const write = res.write.bind(res) as Passthrough<boolean>;
+  const end = res.end.bind(res) as Passthrough<Response>;
+
+  const sign = (bodyDigest: string) => {
+    // tells the client which encodings it may use for what it uploads
+    res.setHeader("Accept-Encoding", SUPPORTED_ENCODINGS.join(", "));
+
+    const messageParts = [String(res.statusCode), nonce, bodyDigest];
+    for (const header of SIGNED_HEADERS) {
+      const value = res.getHeader(header);
+      if (value !== undefined) messageParts.push(String(value));
+    }
+
+    res.setHeader(
+      "X-Response-Signature",
+      createHmac("sha256", secretKey)
+        .update(messageParts.join("\n"))
+        .digest("hex")
+    );
+  };
+
+  res.write = ((chunk: unknown, encoding?: unknown, callback?: unknown) => {
+    if (streaming) return write(chunk, encoding, callback);
+
+    if (typeof encoding === "function") callback = encoding;
+    const buffer = toBuffer(chunk, encoding);
+    chunks.push(buffer);
+    buffered += buffer.length;
+    if (buffered <= SIGNED_BODY_LIMIT) {
+      if (typeof callback === "function")
+        process.nextTick(callback as Callback);
+      return true;
+    }
+
+    // too large to hold back: the headers go now and the body follows
+    streaming = true;
+    res.setHeader("X-Response-Body", STREAMED_BODY);
+    sign(STREAMED_BODY);
+    return write(Buffer.concat(chunks.splice(0)), callback);
+  }) as Response["write"];
+
+  res.end = ((chunk?: unknown, encoding?: unknown, callback?: unknown) => {
+    if (streaming) return end(chunk, encoding, callback);
+
+    if (typeof chunk === "function") {
+      callback = chunk;
+      chunk = undefined;
+    } else if (typeof encoding === "function") {
+      callback = encoding;
+      encoding = undefined;
+    }
+    if (chunk !== undefined && chunk !== null)
+      chunks.push(toBuffer(chunk, encoding));
+
+    const body = Buffer.concat(chunks);
+    sign(createHash("sha256").update(body).digest("hex"));
+
+    const encoding =
+      res.locals.compressible === false
+        ? undefined
+        : negotiateEncoding(res.req.header("Accept-Encoding"));
+    if (!encoding) return end(body, callback as Callback | undefined);
+
+    compress(body, encoding)
+      .catch(() => undefined) // sent as it is
+      .then((compressed) => {
+        if (compressed) {
+          res.setHeader("Content-Encoding", encoding);
+          res.setHeader("Content-Length", compressed.length);
+          res.vary("Accept-Encoding");
+        }
+        end(compressed ?? body, callback as Callback | undefined);
+      });
+    return res;
+  }) as Response["end"];
+}
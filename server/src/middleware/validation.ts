@@ -2,7 +2,9 @@ import { Request, Response, NextFunction } from "express";
 import { StatusCodes } from "http-status-codes";
 import { ZodSchema } from "zod";
 import multiparty from "multiparty";
+import fs from "fs/promises";
 import { metadataSchema } from "../validation/metadataSchema";
+import { decompress, parseEncoding } from "../utils/compression";
 
 const createValidator =
   (targetName: "body" | "params" | "query") =>
@@ -57,24 +59,47 @@ export const validateMultipartMetadata = (
       });
     }
 
+    const content = files.content ? files.content[0] : undefined;
     req.body = {
       originalMetadata: metadataParsed,
       metadata: validation.data,
-      content: files.content ? files.content[0] : undefined,
+      content,
     };
 
-    next();
+    // a compressed content part is expanded in place before the signature is
+    // checked, the signature being over the original bytes
+    const contentEncoding = content?.headers["content-encoding"];
+    if (!content || contentEncoding === undefined) return next();
+    const encoding = parseEncoding(contentEncoding);
+    if (!encoding) {
+      return res
+        .status(StatusCodes.UNSUPPORTED_MEDIA_TYPE)
+        .json({ error: `Unsupported content encoding: ${contentEncoding}` });
+    }
+    fs.readFile(content.path)
+      .then((data) => decompress(data, encoding, validation.data.size))
+      .then((data) => fs.writeFile(content.path, data))
+      .then(
+        () => next(),
+        () =>
+          res
+            .status(StatusCodes.BAD_REQUEST)
+            .json({ error: "Invalid compressed content" })
+      );
   });
 };
 
 import { FileError } from "../error/fileError";
+import { decodePath } from "../utils/path";
 
 export function validatePathParameter(allowEmpty = false) {
   return (req: Request, res: Response, next: NextFunction) => {
     const path = req.params.path;
+    const bytes = path ? decodePath(path) : undefined;
     if (
       (!path && !allowEmpty) || // error if path not present but empty path not allowed
-      (path && path.includes("..")) // error if path present but path includes the string ".."
+      (bytes && bytes.includes("..")) || // error if path present but path includes the string ".."
+      (bytes && bytes.includes(0)) // error if path present but path includes a NUL byte
     ) {
       return next(FileError.InvalidPath());
     }
@@ -1,4 +1,4 @@
-import { Dirent } from "fs";
+import { Dirent, Stats } from "fs";
 
 // type that is similar to: https://docs.rs/fuse/latest/fuse/struct.FileAttr.html
 export type FileAttr = {
@@ -12,6 +12,10 @@ export type FileAttr = {
   refPath?: string;
   perm: string;
   nlink: number;
+  flags?: number; // FS_IMMUTABLE_FL | FS_APPEND_FL, omitted when none is set
+  rdev?: number; // device number, only for character and block devices
+  sha256?: string; // digest of the content of regular files, when known
+  checksums?: boolean; // block hashes are kept, the digest may be unknown
 };
 
 export enum FileType {
@@ -19,6 +23,10 @@ export enum FileType {
   RegularFile = "regular_file",
   SymLink = "soft_link",
   HardLink = "hard_link",
+  Fifo = "fifo",
+  Socket = "socket",
+  CharDevice = "char_device",
+  BlockDevice = "block_device",
 }
 
 export enum Mode {
@@ -26,11 +34,16 @@ export enum Mode {
   Append = "append",
   WriteAt = "write_at",
   Truncate = "truncate",
+  Create = "create",
 }
 
-export function getNodeType(entry: Dirent): FileType {
+export function getNodeType(entry: Dirent | Stats): FileType {
   if (entry.isSymbolicLink()) return FileType.SymLink;
   if (entry.isDirectory()) return FileType.Directory;
   if (entry.isFile()) return FileType.RegularFile;
+  if (entry.isFIFO()) return FileType.Fifo;
+  if (entry.isSocket()) return FileType.Socket;
+  if (entry.isCharacterDevice()) return FileType.CharDevice;
+  if (entry.isBlockDevice()) return FileType.BlockDevice;
   throw new Error("Unknown node type");
 }
@@ -0,0 +1,307 @@
+import fs from "fs/promises";
+import { PathLike, Stats, createReadStream } from "fs";
+import { createHash, Hash } from "crypto";
+import { Database } from "../database";
+
+// Files are hashed in blocks of this size as well as whole, so that a partial
+// write only hashes again the blocks it touched and a reader can check every
+// block it downloads
+export const CHECKSUM_BLOCK_SIZE = 256 * 1024;
+
+// the largest file read whole to find its holes when its hashes are not known
+const MAX_SCANNED_SIZE = 64 * 1024 * 1024;
+
+export type FileChecksum = {
+  size: number;
+  sha256: string | null; // only when asked for may it be left unknown
+  blockSize: number;
+  blocks: string[];
+};
+
+export type Extent = { offset: number; length: number };
+
+type Inode = { dev: number; ino: number };
+
+type Version = Inode & { size: number; mtimeMs: number };
+
+type StoredChecksum = {
+  size: number;
+  mtimeMs: number;
+  sha256: string | null; // unknown after a partial write, until asked for
+  blocks: string[];
+};
+
+type FileChecksumsDbRow = {
+  dev: string;
+  ino: string;
+  size: string;
+  mtime_ms: number;
+  sha256: string | null;
+  blocks: string[];
+};
+
+const inodeKey = (inode: Inode) => `${inode.dev}:${inode.ino}`;
+
+// A row describes its inode as it was at the size and mtime it records: when
+// either differs the file was changed some other way and the row is ignored
+const isCurrent = (row: StoredChecksum, version: Version) =>
+  row.size === version.size && row.mtimeMs === version.mtimeMs;
+
+const blockCount = (size: number) => Math.ceil(size / CHECKSUM_BLOCK_SIZE);
+
+// hash of a block of zeroes: a full block unless it is the last one of the file
+const fullZeroHash = createHash("sha256")
+  .update(Buffer.alloc(CHECKSUM_BLOCK_SIZE))
+  .digest("hex");
+const zeroHash = (length: number) =>
+  length === CHECKSUM_BLOCK_SIZE
+    ? fullZeroHash
+    : createHash("sha256").update(Buffer.alloc(length)).digest("hex");
+
+// Hashes of the blocks in [first, last) of the file, plus the hash of the whole
+// file when the range covers all of it
+async function hashBlocks(
+  filePath: PathLike,
+  size: number,
+  first: number,
+  last: number
+): Promise<{ blocks: string[]; sha256?: string }> {
+  const start = first * CHECKSUM_BLOCK_SIZE;
+  const end = Math.min(last * CHECKSUM_BLOCK_SIZE, size);
+  const whole = first === 0 && end === size ? createHash("sha256") : undefined;
+  const blocks: string[] = [];
+  if (end <= start) return { blocks, sha256: whole?.digest("hex") };
+
+  let block: Hash = createHash("sha256");
+  let filled = 0;
+  const stream = createReadStream(filePath, { start, end: end - 1 });
+  for await (const chunk of stream as AsyncIterable<Buffer>) {
+    whole?.update(chunk);
+    let pos = 0;
+    while (pos < chunk.length) {
+      const take = Math.min(chunk.length - pos, CHECKSUM_BLOCK_SIZE - filled);
+      block.update(chunk.subarray(pos, pos + take));
+      filled += take;
+      pos += take;
+      if (filled === CHECKSUM_BLOCK_SIZE) {
+        blocks.push(block.digest("hex"));
+        block = createHash("sha256");
+        filled = 0;
+      }
+    }
+  }
+  if (filled > 0) blocks.push(block.digest("hex"));
+
+  return { blocks, sha256: whole?.digest("hex") };
+}
+
+// SHA-256 of the bytes in [start, end) of the file
+export async function hashRange(
+  filePath: PathLike,
+  start: number,
+  end: number
+): Promise<string> {
+  const hash = createHash("sha256");
+  if (end > start) {
+    const stream = createReadStream(filePath, { start, end: end - 1 });
+    for await (const chunk of stream as AsyncIterable<Buffer>)
+      hash.update(chunk);
+  }
+  return hash.digest("hex");
+}
+
+// SHA-256 of the content of regular files, kept by inode like the file flags
+class FileChecksums {
+  // rows of many inodes with a single query, by `dev:ino`; outdated rows are
+  // left out
+  static async getMany(
+    versions: Version[]
+  ): Promise<Map<string, StoredChecksum>> {
+    const checksums = new Map<string, StoredChecksum>();
+    if (versions.length === 0) return checksums;
+
+    const result = await Database.query(
+      `SELECT dev, ino, size, mtime_ms, sha256, blocks FROM file_checksums WHERE ino = ANY($1)`,
+      [versions.map((version) => version.ino.toString())]
+    );
+    const wanted = new Map(versions.map((v) => [inodeKey(v), v]));
+    for (const row of result.rows as FileChecksumsDbRow[]) {
+      const key = `${row.dev}:${row.ino}`;
+      const stored: StoredChecksum = {
+        size: Number(row.size),
+        mtimeMs: row.mtime_ms,
+        sha256: row.sha256,
+        blocks: row.blocks,
+      };
+      const version = wanted.get(key);
+      if (version && isCurrent(stored, version)) checksums.set(key, stored);
+    }
+    return checksums;
+  }
+
+  // digest of the whole file, when known
+  static lookup(
+    checksums: Map<string, StoredChecksum>,
+    inode: Inode
+  ): string | undefined {
+    return checksums.get(inodeKey(inode))?.sha256 ?? undefined;
+  }
+
+  // whether block hashes are kept for the inode
+  static has(checksums: Map<string, StoredChecksum>, inode: Inode): boolean {
+    return checksums.has(inodeKey(inode));
+  }
+
+  // checksums of a regular file, computed now for whatever is not known yet;
+  // with `blocksOnly` the digest of the whole file is not computed for this
+  static async get(
+    filePath: PathLike,
+    blocksOnly = false
+  ): Promise<FileChecksum> {
+    const stat = await fs.stat(filePath);
+    const stored = (await FileChecksums.getMany([stat])).get(inodeKey(stat));
+    if (stored && (stored.sha256 || blocksOnly))
+      return {
+        size: stat.size,
+        sha256: stored.sha256,
+        blockSize: CHECKSUM_BLOCK_SIZE,
+        blocks: stored.blocks,
+      };
+
+    const { blocks, sha256 } = await hashBlocks(
+      filePath,
+      stat.size,
+      0,
+      blockCount(stat.size)
+    );
+    await FileChecksums.store(stat, blocks, sha256 as string);
+    return {
+      size: stat.size,
+      sha256: sha256 as string,
+      blockSize: CHECKSUM_BLOCK_SIZE,
+      blocks,
+    };
+  }
+
+  // Ranges of the file holding data, at the granularity of the blocks: those
+  // hashing as zeroes are holes. The hashes are kept up to date by the writes,
+  // so the file is only read when they are not known, and only up to
+  // MAX_SCANNED_SIZE: a larger file is reported as data from end to end
+  static async dataExtents(
+    filePath: PathLike
+  ): Promise<{ size: number; extents: Extent[] }> {
+    const stat = await fs.stat(filePath);
+    const stored = (await FileChecksums.getMany([stat])).get(inodeKey(stat));
+    if (!stored && stat.size > MAX_SCANNED_SIZE) {
+      const extents = stat.size > 0 ? [{ offset: 0, length: stat.size }] : [];
+      return { size: stat.size, extents };
+    }
+    const blocks =
+      stored?.blocks ?? (await FileChecksums.get(filePath, true)).blocks;
+
+    const extents: Extent[] = [];
+    blocks.forEach((hash, index) => {
+      const offset = index * CHECKSUM_BLOCK_SIZE;
+      const length = Math.min(CHECKSUM_BLOCK_SIZE, stat.size - offset);
+      if (hash === zeroHash(length)) return;
+
+      const last = extents[extents.length - 1];
+      if (last && last.offset + last.length === offset) {
+        last.length += length;
+      } else {
+        extents.push({ offset, length });
+      }
+    });
+    return { size: stat.size, extents };
+  }
+
+  // The content of the file changed in [start, end) (`end` may go past the end
+  // of the file), `before` being its state beforehand: only the blocks touched
+  // are hashed again when the previous checksums are still valid. Returns the
+  // digest of the whole file when the whole file was hashed
+  static async update(
+    filePath: PathLike,
+    before: Stats | undefined,
+    start: number,
+    end: number
+  ): Promise<string | undefined> {
+    const stat = await fs.lstat(filePath);
+    if (!stat.isFile()) return undefined;
+
+    const stored =
+      before && (await FileChecksums.getMany([before])).get(inodeKey(before));
+    if (!stored || (start === 0 && end >= stat.size)) {
+      const { blocks, sha256 } = await hashBlocks(
+        filePath,
+        stat.size,
+        0,
+        blockCount(stat.size)
+      );
+      await FileChecksums.store(stat, blocks, sha256 as string);
+      return sha256;
+    }
+
+    // a change of size also changes the block holding the old or the new end
+    const resized = stat.size !== stored.size;
+    const first = Math.floor(
+      (resized ? Math.min(start, stored.size, stat.size) : start) /
+        CHECKSUM_BLOCK_SIZE
+    );
+    const last = blockCount(
+      Math.min(stat.size, resized ? Math.max(end, stat.size) : end)
+    );
+
+    const { blocks } = await hashBlocks(filePath, stat.size, first, last);
+    const merged = stored.blocks.slice(0, blockCount(stat.size));
+    merged.splice(first, blocks.length, ...blocks);
+    await FileChecksums.store(stat, merged, null);
+    return undefined;
+  }
+
+  // After a change of attributes only (times, name): the content is the same
+  static async carry(before: Stats, filePath: PathLike): Promise<void> {
+    const stat = await fs.lstat(filePath);
+    await Database.query(
+      `UPDATE file_checksums SET size = $3, mtime_ms = $4
+       WHERE dev = $1 AND ino = $2 AND size = $5 AND mtime_ms = $6`,
+      [
+        stat.dev.toString(),
+        stat.ino.toString(),
+        stat.size,
+        stat.mtimeMs,
+        before.size,
+        before.mtimeMs,
+      ]
+    );
+  }
+
+  static async clear(inode: Inode): Promise<void> {
+    await Database.query(
+      `DELETE FROM file_checksums WHERE dev = $1 AND ino = $2`,
+      [inode.dev.toString(), inode.ino.toString()]
+    );
+  }
+
+  private static async store(
+    version: Version,
+    blocks: string[],
+    sha256: string | null
+  ): Promise<void> {
+    await Database.query(
+      `INSERT INTO file_checksums (dev, ino, size, mtime_ms, sha256, blocks)
+       VALUES ($1, $2, $3, $4, $5, $6)
+       ON CONFLICT (dev, ino) DO UPDATE SET size = EXCLUDED.size,
+         mtime_ms = EXCLUDED.mtime_ms, sha256 = EXCLUDED.sha256, blocks = EXCLUDED.blocks`,
+      [
+        version.dev.toString(),
+        version.ino.toString(),
+        version.size,
+        version.mtimeMs,
+        sha256,
+        blocks,
+      ]
+    );
+  }
+}
+
+export default FileChecksums;
@@ -0,0 +1,80 @@
+import fs from "fs/promises";
+import { PathLike } from "fs";
+import { Database } from "../database";
+
+// Linux inode flags (see FS_IOC_GETFLAGS) stored and enforced by the server
+export enum FileFlag {
+  Immutable = 0x10, // FS_IMMUTABLE_FL: the file cannot be changed, renamed or deleted
+  Append = 0x20, // FS_APPEND_FL: data can only be appended
+}
+
+export const SUPPORTED_FLAGS = FileFlag.Immutable | FileFlag.Append;
+
+type Inode = { dev: number; ino: number };
+
+type FileFlagsDbRow = {
+  dev: string;
+  ino: string;
+  flags: number;
+};
+
+const inodeKey = (inode: Inode) => `${inode.dev}:${inode.ino}`;
+
+// Flags are kept by inode, so they follow renames and are shared by hard links
+class FileFlags {
+  static async get(filePath: PathLike): Promise<number> {
+    const stat = await fs.lstat(filePath);
+    const result = await Database.query(
+      `SELECT flags FROM file_flags WHERE dev = $1 AND ino = $2`,
+      [stat.dev.toString(), stat.ino.toString()]
+    );
+    return result.rows[0]?.flags ?? 0;
+  }
+
+  // flags of many inodes with a single query, by `dev:ino`; inodes without flags are missing
+  static async getMany(inodes: Inode[]): Promise<Map<string, number>> {
+    const flags = new Map<string, number>();
+    if (inodes.length === 0) return flags;
+
+    const result = await Database.query(
+      `SELECT dev, ino, flags FROM file_flags WHERE ino = ANY($1)`,
+      [inodes.map((inode) => inode.ino.toString())]
+    );
+    for (const row of result.rows as FileFlagsDbRow[]) {
+      flags.set(`${row.dev}:${row.ino}`, row.flags);
+    }
+    return flags;
+  }
+
+  static lookup(flags: Map<string, number>, inode: Inode): number {
+    return flags.get(inodeKey(inode)) ?? 0;
+  }
+
+  static async set(filePath: PathLike, flags: number): Promise<void> {
+    const stat = await fs.lstat(filePath);
+    const values = [stat.dev.toString(), stat.ino.toString()];
+
+    if (flags === 0) {
+      await Database.query(
+        `DELETE FROM file_flags WHERE dev = $1 AND ino = $2`,
+        values
+      );
+    } else {
+      await Database.query(
+        `INSERT INTO file_flags (dev, ino, flags) VALUES ($1, $2, $3)
+         ON CONFLICT (dev, ino) DO UPDATE SET flags = EXCLUDED.flags`,
+        [...values, flags]
+      );
+    }
+  }
+
+  // the flags of a deleted inode must not be inherited by the next file reusing its number
+  static async clear(inode: Inode): Promise<void> {
+    await Database.query(`DELETE FROM file_flags WHERE dev = $1 AND ino = $2`, [
+      inode.dev.toString(),
+      inode.ino.toString(),
+    ]);
+  }
+}
+
+export default FileFlags;
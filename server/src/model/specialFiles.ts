@@ -0,0 +1,74 @@
+import fs from "fs/promises";
+import { PathLike } from "fs";
+import { Database } from "../database";
+import { FileType } from "./file";
+
+// Kinds that only exist as a record on the server: node has no mknod and device
+// nodes would need root, so each one is an empty regular file plus its row
+export type SpecialFileType =
+  | FileType.Fifo
+  | FileType.Socket
+  | FileType.CharDevice
+  | FileType.BlockDevice;
+
+export type SpecialFile = { kind: SpecialFileType; rdev: number };
+
+type Inode = { dev: number; ino: number };
+
+type SpecialFilesDbRow = {
+  dev: string;
+  ino: string;
+  kind: SpecialFileType;
+  rdev: number;
+};
+
+const inodeKey = (inode: Inode) => `${inode.dev}:${inode.ino}`;
+
+// Kept by inode like the file flags, so they follow renames and hard links
+class SpecialFiles {
+  static async get(filePath: PathLike): Promise<SpecialFile | undefined> {
+    const stat = await fs.lstat(filePath);
+    return (await SpecialFiles.getMany([stat])).get(inodeKey(stat));
+  }
+
+  // special files among many inodes with a single query, by `dev:ino`
+  static async getMany(inodes: Inode[]): Promise<Map<string, SpecialFile>> {
+    const special = new Map<string, SpecialFile>();
+    if (inodes.length === 0) return special;
+
+    const result = await Database.query(
+      `SELECT dev, ino, kind, rdev FROM special_files WHERE ino = ANY($1)`,
+      [inodes.map((inode) => inode.ino.toString())]
+    );
+    for (const row of result.rows as SpecialFilesDbRow[]) {
+      special.set(`${row.dev}:${row.ino}`, { kind: row.kind, rdev: row.rdev });
+    }
+    return special;
+  }
+
+  static lookup(
+    special: Map<string, SpecialFile>,
+    inode: Inode
+  ): SpecialFile | undefined {
+    return special.get(inodeKey(inode));
+  }
+
+  static async set(filePath: PathLike, file: SpecialFile): Promise<void> {
+    const stat = await fs.lstat(filePath);
+    await Database.query(
+      `INSERT INTO special_files (dev, ino, kind, rdev) VALUES ($1, $2, $3, $4)
+       ON CONFLICT (dev, ino) DO UPDATE SET kind = EXCLUDED.kind, rdev = EXCLUDED.rdev`,
+      [stat.dev.toString(), stat.ino.toString(), file.kind, file.rdev]
+    );
+  }
+
+  // the next file reusing the number of a deleted inode must be a regular one
+  static async clear(inode: Inode): Promise<void> {
+    await Database.query(
+      `DELETE FROM special_files WHERE dev = $1 AND ino = $2`,
+      [inode.dev.toString(), inode.ino.toString()]
+    );
+  }
+}
+
+export default SpecialFiles;
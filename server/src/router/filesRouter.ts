@@ -1,20 +1,115 @@
 import { Router, Request, Response, NextFunction } from "express";
 import { StatusCodes } from "http-status-codes";
 import fs from "fs/promises";
-import path from "path";
 import { FileAttr, FileType, getNodeType, Mode } from "../model/file";
+import FileFlags, { FileFlag } from "../model/fileFlags";
+import SpecialFiles, { SpecialFile } from "../model/specialFiles";
+import FileChecksums, { hashRange } from "../model/fileChecksums";
 import { FileError } from "../error/fileError";
 import {
+  validateBody,
   validateMultipartMetadata,
   validatePathParameter,
+  validateQueryParameters,
 } from "../middleware/validation";
 import { MetadataPut } from "../validation/metadataSchema";
-import { getPath } from "../utils/path";
+import { listQuerySchema } from "../validation/listSchema";
+import { checksumsQuerySchema } from "../validation/checksumsSchema";
+import { copySchema } from "../validation/copySchema";
+import { mkdirSchema } from "../validation/mkdirSchema";
+import { mknodSchema } from "../validation/mknodSchema";
+import { flagsSchema } from "../validation/flagsSchema";
+import {
+  AllocateOperation,
+  fallocateSchema,
+} from "../validation/fallocateSchema";
+import {
+  decodePath,
+  encodePath,
+  getFsBaseName,
+  getFsPath,
+  joinFsPath,
+  startsWithPath,
+} from "../utils/path";
 import { checkAuth } from "../middleware/authentication";
-import { createReadStream } from "fs";
+import IdempotencyCache from "../cache/idempotencyCache";
+import DirectoryCursors from "../cache/directoryCursors";
+import { constants, createReadStream, PathLike, Stats } from "fs";
+import { isCompressedMedia } from "../utils/compression";
+import { fallocate } from "../utils/fallocate";
 
 export const filesRouter: Router = Router();
 
+const IO_CHUNK = 1024 * 1024; // buffer size of the copies done here
+
+const FILE_LOCKED = "The file is immutable or append-only";
+
+// link target as listed: inside the namespace it is relative to the user's root
+function toRefPath(userPath: string, refPathAbs: Buffer): string {
+  if (refPathAbs.equals(Buffer.from(userPath))) return "/";
+  if (startsWithPath(refPathAbs, userPath))
+    return encodePath(refPathAbs.subarray(Buffer.byteLength(userPath)));
+  return encodePath(refPathAbs); // outside the namespace
+}
+
+// kind and device number of an entry, the special files recorded by the server included
+function describeNode(
+  stats: Stats,
+  special: SpecialFile | undefined
+): { kind: FileType; rdev?: number } {
+  if (special && stats.isFile())
+    return { kind: special.kind, rdev: special.rdev || undefined };
+  return { kind: getNodeType(stats), rdev: stats.rdev || undefined };
+}
+
+// flags of the entry at filePath, none when there is nothing there
+async function flagsOf(filePath: PathLike): Promise<number> {
+  try {
+    return await FileFlags.get(filePath);
+  } catch (e) {
+    if ((e as NodeJS.ErrnoException).code === "ENOENT") return 0;
+    throw e;
+  }
+}
+
+// metadata of the entry at entryPath, as listed
+async function describeEntry(
+  userPath: string,
+  entryPath: Buffer,
+  stats: Stats
+): Promise<FileAttr> {
+  const special = stats.isFile()
+    ? await SpecialFiles.get(entryPath)
+    : undefined;
+  const { kind, rdev } = describeNode(stats, special);
+  let refPath;
+
+  if (kind === FileType.SymLink) {
+    const refPathAbs = await fs.readlink(entryPath, { encoding: "buffer" });
+    refPath = toRefPath(userPath, refPathAbs);
+  }
+  const checksums =
+    kind === FileType.RegularFile
+      ? await FileChecksums.getMany([stats])
+      : undefined;
+  return {
+    name: encodePath(getFsBaseName(entryPath)),
+    size: stats.size,
+    atime: stats.atime.toISOString(),
+    mtime: stats.mtime.toISOString(),
+    ctime: stats.ctime.toISOString(),
+    crtime: stats.birthtime.toISOString(),
+    kind,
+    refPath,
+    perm: (stats.mode & 0o7777).toString(8), // octal mask to isolate permissions bits (special bits included)
+    nlink: stats.nlink,
+    flags: (await flagsOf(entryPath)) || undefined,
+    rdev,
+    sha256: checksums && FileChecksums.lookup(checksums, stats),
+    checksums: (checksums && FileChecksums.has(checksums, stats)) || undefined,
+  };
+}
+
 // GET /files/:path
 filesRouter.get(
   "/files/:path?",
@@ -22,8 +117,10 @@ filesRouter.get(
   checkAuth,
   async (req: Request, res: Response, next: NextFunction) => {
     try {
-      const filePath = getPath(req.userPath, req.params.path);
+      const filePath = getFsPath(req.userPath, req.params.path);
       const stat = await fs.stat(filePath);
+      // images, archives and the like are not worth compressing again
+      res.locals.compressible = !isCompressedMedia(filePath);
 
       const range = req.header("Range");
 
@@ -41,11 +138,12 @@ filesRouter.get(
           return next(FileError.RequestedRangeNotSatisfiable());
 
         const chunkSize = end - start + 1;
-        res.writeHead(StatusCodes.PARTIAL_CONTENT, {
-          "Content-Length": chunkSize,
-          "Content-Range": `bytes ${start}-${end}/${stat.size}`,
-          "Accept-Ranges": "bytes",
-        });
+        // headers are set rather than written: the response signature is
+        // added once the body is complete, or too large to hold back
+        res.status(StatusCodes.PARTIAL_CONTENT);
+        res.setHeader("Content-Length", chunkSize);
+        res.setHeader("Content-Range", `bytes ${start}-${end}/${stat.size}`);
+        res.setHeader("Accept-Ranges", "bytes");
 
         const stream = createReadStream(filePath, { start, end });
         stream.pipe(res);
@@ -79,20 +177,38 @@ filesRouter.put(
       content?: { path: string };
     };
 
-    const finalPath = getPath(req.userPath, currentPath);
+    const finalPath = getFsPath(req.userPath, currentPath);
 
     try {
       if (metadata.newPath && metadata.newPath !== currentPath) {
         const oldPath = finalPath;
-        const newPath = getPath(req.userPath, metadata.newPath);
+        const newPath = getFsPath(req.userPath, metadata.newPath);
+
+        // neither the renamed file nor the one it would replace may be flagged
+        if ((await flagsOf(oldPath)) || (await flagsOf(newPath)))
+          return next(FileError.OperationNotPermitted(FILE_LOCKED));
 
+        const before = await fs.lstat(oldPath);
+        // the entry replaced loses its rows with its last link, as on DELETE
+        const replaced = await fs.lstat(newPath).catch(() => undefined);
         await fs.rename(oldPath, newPath);
+        if (
+          replaced &&
+          !replaced.isDirectory() &&
+          replaced.ino !== before.ino &&
+          replaced.nlink <= 1
+        ) {
+          await FileFlags.clear(replaced);
+          await SpecialFiles.clear(replaced);
+          await FileChecksums.clear(replaced);
+        }
         await fs.chmod(newPath, parseInt(metadata.perm, 8));
         await fs.utimes(
           newPath,
           new Date(metadata.atime),
           new Date(metadata.mtime)
         );
+        await FileChecksums.carry(before, newPath);
         return res.status(StatusCodes.NO_CONTENT).send();
       }
 
@@ -103,22 +219,22 @@ filesRouter.put(
         metadata.refPath
       ) {
         if (metadata.kind === FileType.SymLink) {
-          await fs.symlink(metadata.refPath, finalPath);
+          await fs.symlink(decodePath(metadata.refPath), finalPath);
         } else {
           let targetPath;
 
           // relative hard link
           if (metadata.refPath.includes("..")) {
-            const parentPath = getPath(finalPath, "..");
-            targetPath = getPath(parentPath, metadata.refPath);
+            const parentPath = joinFsPath(finalPath, Buffer.from(".."));
+            targetPath = joinFsPath(parentPath, decodePath(metadata.refPath));
 
             // check if targetPath refers to another file system (outside req.userPath)
-            if (!targetPath.startsWith(req.userPath)) {
+            if (!startsWithPath(targetPath, req.userPath)) {
               return next(FileError.InvalidHardLink());
             }
           } else {
             // absolute hard link
-            targetPath = getPath(req.userPath, metadata.refPath);
+            targetPath = getFsPath(req.userPath, metadata.refPath);
           }
 
           try {
@@ -128,12 +244,23 @@ filesRouter.put(
             return next(FileError.NotFound());
           }
 
+          if (await flagsOf(targetPath))
+            return next(FileError.OperationNotPermitted(FILE_LOCKED));
+
           await fs.link(targetPath, finalPath);
         }
 
         return res.status(StatusCodes.CREATED).send();
       }
 
+      // immutable: nothing can change; append-only: only appends, attributes included
+      const flags = await flagsOf(finalPath);
+      if (
+        (flags & FileFlag.Immutable) !== 0 ||
+        ((flags & FileFlag.Append) !== 0 && metadata.mode !== Mode.Append)
+      )
+        return next(FileError.OperationNotPermitted(FILE_LOCKED));
+
       let contentBuffer: Buffer | undefined = undefined;
       if (content?.path && metadata.mode !== Mode.Truncate) {
         contentBuffer = await fs.readFile(content.path);
@@ -147,17 +274,35 @@ filesRouter.put(
         .access(finalPath)
         .then(() => true)
         .catch(() => false);
+      const before = fileExists ? await fs.lstat(finalPath) : undefined;
+      // part of the content changed by the request, none for attributes only
+      let changed: { start: number; end: number } | undefined;
 
       switch (metadata.mode) {
         case Mode.Write:
           if (!contentBuffer && fileExists) break;
           await fs.writeFile(finalPath, contentBuffer ?? Buffer.alloc(0));
+          changed = { start: 0, end: Infinity };
           break;
 
-        case Mode.Append:
+        case Mode.Append: {
           if (!contentBuffer && fileExists) break;
-          await fs.appendFile(finalPath, contentBuffer ?? Buffer.alloc(0));
+          const append = () =>
+            fs.appendFile(finalPath, contentBuffer ?? Buffer.alloc(0));
+          // a replayed append (same key) is applied only once
+          const idempotencyKey = req.header("Idempotency-Key");
+          if (idempotencyKey) {
+            await IdempotencyCache.once(
+              `${req.userPath}:${idempotencyKey}`,
+              append
+            );
+          } else {
+            await append();
+          }
+          const start = before?.size ?? 0;
+          changed = { start, end: Infinity };
           break;
+        }
 
         case Mode.WriteAt: {
           if (!contentBuffer && !fileExists) break;
@@ -165,6 +310,8 @@ filesRouter.put(
           try {
             const buffer = contentBuffer ?? Buffer.alloc(0);
             await fd.write(buffer, 0, buffer.length, metadata.offset);
+            const start = metadata.offset ?? 0;
+            changed = { start, end: start + buffer.length };
           } finally {
             await fd.close();
           }
@@ -174,20 +321,62 @@ filesRouter.put(
         case Mode.Truncate:
           if (!fileExists) return next(FileError.NotFound());
           await fs.truncate(finalPath, metadata.size);
+          changed = { start: metadata.size, end: metadata.size };
+          break;
+
+        case Mode.Create:
+          // "wx" fails with EEXIST if anything is there, atomically
+          await fs.writeFile(finalPath, contentBuffer ?? Buffer.alloc(0), {
+            flag: "wx",
+          });
+          changed = { start: 0, end: Infinity };
           break;
 
         default:
         // this section cannot be accessed because zod intercept the error
       }
 
-      await fs.chmod(finalPath, parseInt(metadata.perm, 8));
-      await fs.utimes(
-        finalPath,
-        new Date(metadata.atime),
-        new Date(metadata.mtime)
-      );
+      if (!flags) {
+        await fs.chmod(finalPath, parseInt(metadata.perm, 8));
+        await fs.utimes(
+          finalPath,
+          new Date(metadata.atime),
+          new Date(metadata.mtime)
+        );
+      }
       // NOTE: ctime and crtime are not manually settable. They are controlled by the file system
 
+      // the digest of the stored content, when the whole file was hashed, lets
+      // the client confirm that what it wrote is what the server now holds; for
+      // a write in place or an append, that of the bytes where it wrote
+      if (changed) {
+        const sha256 = await FileChecksums.update(
+          finalPath,
+          before,
+          changed.start,
+          changed.end
+        );
+        if (sha256) res.setHeader("X-Content-Sha256", sha256);
+
+        const written = contentBuffer?.length ?? 0;
+        if (metadata.mode === Mode.WriteAt) {
+          const start = metadata.offset ?? 0;
+          res.setHeader(
+            "X-Written-Sha256",
+            await hashRange(finalPath, start, start + written)
+          );
+        } else if (metadata.mode === Mode.Append) {
+          // the tail of the file, which holds the append even when replayed
+          const { size } = await fs.stat(finalPath);
+          res.setHeader(
+            "X-Written-Sha256",
+            await hashRange(finalPath, size - written, size)
+          );
+        }
+      } else if (before) {
+        await FileChecksums.carry(before, finalPath);
+      }
+
       const status = fileExists ? StatusCodes.NO_CONTENT : StatusCodes.CREATED;
       res.status(status).send();
     } catch (e) {
@@ -220,13 +409,21 @@ filesRouter.delete(
   checkAuth,
   async (req: Request, res: Response, next: NextFunction) => {
     try {
-      const filePath = getPath(req.userPath, req.params.path);
+      const filePath = getFsPath(req.userPath, req.params.path);
       const stat = await fs.lstat(filePath);
 
+      if (await flagsOf(filePath))
+        return next(FileError.OperationNotPermitted(FILE_LOCKED));
+
       if (stat.isDirectory()) {
         await fs.rmdir(filePath);
       } else {
         await fs.unlink(filePath);
+        if (stat.nlink <= 1) {
+          await FileFlags.clear(stat);
+          await SpecialFiles.clear(stat);
+          await FileChecksums.clear(stat);
+        }
       }
 
       res.status(StatusCodes.NO_CONTENT).send();
@@ -243,79 +440,320 @@ filesRouter.delete(
   }
 );
 
+// POST /copy/:path
+filesRouter.post(
+  "/copy/:path?",
+  validatePathParameter(false),
+  validateBody(copySchema),
+  checkAuth,
+  async (req: Request, res: Response, next: NextFunction) => {
+    try {
+      const destPath = getFsPath(req.userPath, req.params.path);
+      const { source, sourceOffset, destOffset, length } = copySchema.parse(
+        req.body
+      );
+      const sourcePath = getFsPath(req.userPath, source);
+
+      const stat = await fs.stat(sourcePath);
+      if (stat.isDirectory()) return next(FileError.IsADirectory());
+
+      if (await flagsOf(destPath))
+        return next(FileError.OperationNotPermitted(FILE_LOCKED));
+
+      const before = await fs.lstat(destPath).catch(() => undefined);
+      const end = Math.min(
+        stat.size,
+        length === undefined ? stat.size : sourceOffset + length
+      );
+
+      // within one file the ranges may not overlap (EINVAL, as on Linux): the
+      // copy would read back what it has just written
+      const dest = await fs.stat(destPath).catch(() => undefined);
+      if (
+        dest &&
+        dest.dev === stat.dev &&
+        dest.ino === stat.ino &&
+        sourceOffset < destOffset + (end - sourceOffset) &&
+        destOffset < end
+      )
+        return next(FileError.OverlappingRanges());
+
+      // the whole source over a destination no longer than it: let the file
+      // system clone it when it can (reflink)
+      if (
+        sourceOffset === 0 &&
+        destOffset === 0 &&
+        end === stat.size &&
+        (dest === undefined || dest.size <= stat.size)
+      ) {
+        await fs.copyFile(sourcePath, destPath, constants.COPYFILE_FICLONE);
+        await FileChecksums.update(destPath, before, 0, Infinity);
+        return res.status(StatusCodes.OK).json({ copied: stat.size });
+      }
+
+      const destExists = before !== undefined;
+
+      let copied = 0;
+      const input = await fs.open(sourcePath, "r");
+      try {
+        const output = await fs.open(destPath, destExists ? "r+" : "w+");
+        try {
+          const buffer = Buffer.alloc(IO_CHUNK);
+          while (sourceOffset + copied < end) {
+            const toRead = Math.min(IO_CHUNK, end - sourceOffset - copied);
+            const { bytesRead } = await input.read(
+              buffer,
+              0,
+              toRead,
+              sourceOffset + copied
+            );
+            if (bytesRead === 0) break;
+            await output.write(buffer, 0, bytesRead, destOffset + copied);
+            copied += bytesRead;
+          }
+        } finally {
+          await output.close();
+        }
+      } finally {
+        await input.close();
+      }
+
+      await FileChecksums.update(
+        destPath,
+        before,
+        destOffset,
+        destOffset + copied
+      );
+      res.status(StatusCodes.OK).json({ copied });
+    } catch (e) {
+      const code = (e as NodeJS.ErrnoException).code;
+      if (code === "ENOENT") {
+        next(FileError.NotFound());
+      } else if (code === "EISDIR") {
+        next(FileError.IsADirectory());
+      } else if (code === "ENOSPC") {
+        next(FileError.NoSpaceLeft());
+      } else {
+        next(e);
+      }
+    }
+  }
+);
+
+// POST /fallocate/:path
+filesRouter.post(
+  "/fallocate/:path?",
+  validatePathParameter(false),
+  validateBody(fallocateSchema),
+  checkAuth,
+  async (req: Request, res: Response, next: NextFunction) => {
+    try {
+      const filePath = getFsPath(req.userPath, req.params.path);
+      const { operation, offset, length, keepSize } = fallocateSchema.parse(
+        req.body
+      );
+
+      const stat = await fs.stat(filePath);
+      if (stat.isDirectory()) return next(FileError.IsADirectory());
+
+      if (await flagsOf(filePath))
+        return next(FileError.OperationNotPermitted(FILE_LOCKED));
+
+      const end = offset + length;
+
+      // quota preflight: the blocks the range may need must be available
+      if (operation !== AllocateOperation.PunchHole) {
+        const needed = Math.max(0, end - stat.blocks * 512);
+        const space = await fs.statfs(filePath);
+        if (needed > space.bavail * space.bsize)
+          return next(FileError.NoSpaceLeft());
+      }
+
+      await fallocate(filePath, operation, offset, length, keepSize);
+      await FileChecksums.update(filePath, stat, offset, end);
+
+      res.status(StatusCodes.NO_CONTENT).send();
+    } catch (e) {
+      const code = (e as NodeJS.ErrnoException).code;
+      if (code === "ENOENT") {
+        next(FileError.NotFound());
+      } else if (code === "EISDIR") {
+        next(FileError.IsADirectory());
+      } else if (code === "ENOSPC") {
+        next(FileError.NoSpaceLeft());
+      } else if (code === "EOPNOTSUPP") {
+        next(FileError.OperationNotSupported());
+      } else {
+        next(e);
+      }
+    }
+  }
+);
+
+// GET /extents/:path
+filesRouter.get(
+  "/extents/:path?",
+  validatePathParameter(false),
+  checkAuth,
+  async (req: Request, res: Response, next: NextFunction) => {
+    try {
+      const filePath = getFsPath(req.userPath, req.params.path);
+      const stat = await fs.stat(filePath);
+      if (stat.isDirectory()) return next(FileError.IsADirectory());
+
+      // node has no SEEK_DATA/SEEK_HOLE: the block hashes kept for the checksums
+      // tell the blocks that are entirely zero, which are reported as holes
+      res
+        .status(StatusCodes.OK)
+        .json(await FileChecksums.dataExtents(filePath));
+    } catch (e) {
+      const code = (e as NodeJS.ErrnoException).code;
+      if (code === "ENOENT") {
+        next(FileError.NotFound());
+      } else if (code === "EISDIR") {
+        next(FileError.IsADirectory());
+      } else {
+        next(e);
+      }
+    }
+  }
+);
+
+// GET /checksums/:path
+filesRouter.get(
+  "/checksums/:path?",
+  validatePathParameter(false),
+  validateQueryParameters(checksumsQuerySchema),
+  checkAuth,
+  async (req: Request, res: Response, next: NextFunction) => {
+    try {
+      const filePath = getFsPath(req.userPath, req.params.path);
+      const { blocksOnly } = checksumsQuerySchema.parse(req.query);
+      const stat = await fs.stat(filePath);
+      if (stat.isDirectory()) return next(FileError.IsADirectory());
+
+      res
+        .status(StatusCodes.OK)
+        .json(await FileChecksums.get(filePath, blocksOnly));
+    } catch (e) {
+      const code = (e as NodeJS.ErrnoException).code;
+      if (code === "ENOENT") {
+        next(FileError.NotFound());
+      } else {
+        next(e);
+      }
+    }
+  }
+);
+
+// PUT /flags/:path
+filesRouter.put(
+  "/flags/:path?",
+  validatePathParameter(false),
+  validateBody(flagsSchema),
+  checkAuth,
+  async (req: Request, res: Response, next: NextFunction) => {
+    try {
+      const filePath = getFsPath(req.userPath, req.params.path);
+      const { flags, clear } = flagsSchema.parse(req.body);
+
+      const stat = await fs.lstat(filePath);
+      if (stat.isDirectory()) return next(FileError.IsADirectory());
+      if (!stat.isFile())
+        return next(
+          FileError.OperationNotPermitted(
+            "Flags can only be set on regular files"
+          )
+        );
+
+      // a request meant to add a flag never lifts the protection of another
+      const current = await FileFlags.get(filePath);
+      if ((current & ~flags) !== 0 && !clear)
+        return next(
+          FileError.OperationNotPermitted(
+            "Clearing flags has to be asked for with `clear`"
+          )
+        );
+
+      await FileFlags.set(filePath, flags);
+      res.status(StatusCodes.NO_CONTENT).send();
+    } catch (e) {
+      const code = (e as NodeJS.ErrnoException).code;
+      if (code === "ENOENT") {
+        next(FileError.NotFound());
+      } else {
+        next(e);
+      }
+    }
+  }
+);
+
 // GET /list/:path
 filesRouter.get(
   "/list/:path?",
   validatePathParameter(true),
+  validateQueryParameters(listQuerySchema),
   checkAuth,
   async (req: Request, res: Response, next: NextFunction) => {
     try {
-      const entryPath = getPath(req.userPath, req.params.path ?? "");
+      const entryPath = getFsPath(req.userPath, req.params.path ?? "");
+      const { limit, cursor } = listQuerySchema.parse(req.query);
 
       const stats = await fs.lstat(entryPath);
 
       // if the entry is a file, the output will be an array with a single object containing its metadata
       if (!stats.isDirectory()) {
-        const parentDirPath = path.dirname(entryPath);
-        const entries = await fs.readdir(parentDirPath, {
-          withFileTypes: true,
-        });
-        const entry = entries.find((e) => e.name === path.basename(entryPath))!; // cannot be undefined
-
-        const kind = getNodeType(entry);
-        let refPath;
-
-        if (kind === FileType.SymLink) {
-          const refPathAbs = await fs.readlink(entryPath);
-
-          if (refPathAbs === req.userPath) {
-            refPath = "/";
-          } else if (refPathAbs.startsWith(req.userPath)) {
-            refPath = refPathAbs.slice(req.userPath.length);
-          } else {
-            refPath = refPathAbs; // outside the namespace
-          }
-        }
-        const fsEntry: FileAttr = {
-          name: entry.name,
-          size: stats.size,
-          atime: stats.atime.toISOString(),
-          mtime: stats.mtime.toISOString(),
-          ctime: stats.ctime.toISOString(),
-          crtime: stats.birthtime.toISOString(),
-          kind,
-          refPath,
-          perm: (stats.mode & 0o777).toString(8), // octal mask to isolate permissions bits
-          nlink: stats.nlink,
-        };
-
+        const fsEntry = await describeEntry(req.userPath, entryPath, stats);
         return res.status(StatusCodes.OK).json([fsEntry]);
       }
 
       const dirPath = entryPath; // the entry is now assumed to be a directory
-      const entries = await fs.readdir(dirPath, { withFileTypes: true });
+      let entries: Buffer[];
+      if (limit === undefined) {
+        // names are read as bytes: they need not be valid UTF-8
+        entries = await fs.readdir(dirPath, { encoding: "buffer" });
+      } else {
+        // pagination: each page goes on reading the directory where the
+        // previous one stopped, the cursor naming the open listing and the page
+        const page = await DirectoryCursors.page(
+          req.userPath,
+          dirPath,
+          cursor,
+          limit
+        );
+        if (!page) return next(FileError.CursorExpired());
+        entries = page.names;
+        if (page.next) res.setHeader("X-Next-Cursor", page.next);
+      }
 
-      const result = await Promise.all(
-        entries.map(async (entry) => {
-          const entryPath = getPath(dirPath, entry.name);
-          const stats = await fs.lstat(entryPath);
+      const entryStats = await Promise.all(
+        entries.map((name) => fs.lstat(joinFsPath(dirPath, name)))
+      );
+      // one query each for the flags, the special files and the checksums of
+      // the whole page
+      const flags = await FileFlags.getMany(entryStats);
+      const special = await SpecialFiles.getMany(entryStats);
+      const checksums = await FileChecksums.getMany(entryStats);
 
-          const kind = getNodeType(entry);
+      const result = await Promise.all(
+        entries.map(async (name, i) => {
+          const entryPath = joinFsPath(dirPath, name);
+          const stats = entryStats[i];
+
+          const { kind, rdev } = describeNode(
+            stats,
+            SpecialFiles.lookup(special, stats)
+          );
           let refPath;
           if (kind === FileType.SymLink) {
-            const refPathAbs = await fs.readlink(entryPath);
-
-            if (refPathAbs === req.userPath) {
-              refPath = "/";
-            } else if (refPathAbs.startsWith(req.userPath)) {
-              refPath = refPathAbs.slice(req.userPath.length);
-            } else {
-              refPath = refPathAbs; // outside the namespace
-            }
+            const refPathAbs = await fs.readlink(entryPath, {
+              encoding: "buffer",
+            });
+            refPath = toRefPath(req.userPath, refPathAbs);
           }
 
           const fsEntry: FileAttr = {
-            name: entry.name,
+            name: encodePath(name),
             size: stats.size,
             atime: stats.atime.toISOString(),
             mtime: stats.mtime.toISOString(),
@@ -323,8 +761,18 @@ filesRouter.get(
             crtime: stats.birthtime.toISOString(),
             kind,
             refPath,
-            perm: (stats.mode & 0o777).toString(8),
+            perm: (stats.mode & 0o7777).toString(8),
             nlink: stats.nlink,
+            flags: FileFlags.lookup(flags, stats) || undefined,
+            rdev,
+            sha256:
+              kind === FileType.RegularFile
+                ? FileChecksums.lookup(checksums, stats)
+                : undefined,
+            checksums:
+              (kind === FileType.RegularFile &&
+                FileChecksums.has(checksums, stats)) ||
+              undefined,
           };
 
           return fsEntry;
@@ -343,16 +791,45 @@ filesRouter.get(
   }
 );
 
+// GET /stat/:path
+filesRouter.get(
+  "/stat/:path?",
+  validatePathParameter(false),
+  checkAuth,
+  async (req: Request, res: Response, next: NextFunction) => {
+    try {
+      const entryPath = getFsPath(req.userPath, req.params.path);
+
+      // the entry itself, a directory included, without reading its parent
+      const stats = await fs.lstat(entryPath);
+      const fsEntry = await describeEntry(req.userPath, entryPath, stats);
+      res.status(StatusCodes.OK).json(fsEntry);
+    } catch (e) {
+      const code = (e as NodeJS.ErrnoException).code;
+      if (code === "ENOENT") {
+        next(FileError.NotFound());
+      } else {
+        next(e);
+      }
+    }
+  }
+);
+
 // POST /mkdir/:path
 filesRouter.post(
   "/mkdir/:path?",
   validatePathParameter(false),
+  validateBody(mkdirSchema),
   checkAuth,
   async (req: Request, res: Response, next: NextFunction) => {
     try {
-      const dirPath = getPath(req.userPath, req.params.path);
+      const dirPath = getFsPath(req.userPath, req.params.path);
+      const { permissionsOctal } = mkdirSchema.parse(req.body);
 
       await fs.mkdir(dirPath);
+      // mkdir applies the umask of the server and drops the special bits
+      if (permissionsOctal)
+        await fs.chmod(dirPath, parseInt(permissionsOctal, 8));
       res.status(StatusCodes.CREATED).send();
     } catch (e) {
       const code = (e as NodeJS.ErrnoException).code;
@@ -368,3 +845,39 @@ filesRouter.post(
     }
   }
 );
+
+// POST /mknod/:path
+filesRouter.post(
+  "/mknod/:path?",
+  validatePathParameter(false),
+  validateBody(mknodSchema),
+  checkAuth,
+  async (req: Request, res: Response, next: NextFunction) => {
+    try {
+      const nodePath = getFsPath(req.userPath, req.params.path);
+      const { kind, rdev, permissionsOctal } = mknodSchema.parse(req.body);
+
+      // the node is an empty placeholder ("wx": only if nothing is there)
+      await fs.writeFile(nodePath, Buffer.alloc(0), { flag: "wx" });
+      try {
+        await fs.chmod(nodePath, parseInt(permissionsOctal, 8));
+        await SpecialFiles.set(nodePath, { kind, rdev });
+      } catch (e) {
+        await fs.unlink(nodePath); // never left as a plain regular file
+        throw e;
+      }
+      res.status(StatusCodes.CREATED).send();
+    } catch (e) {
+      const code = (e as NodeJS.ErrnoException).code;
+      if (code === "ENOENT") {
+        next(FileError.ParentDirectoryNotFound());
+      } else if (code === "EEXIST") {
+        next(FileError.FileAlreadyExists());
+      } else if (code === "ENOSPC") {
+        next(FileError.NoSpaceLeft());
+      } else {
+        next(e);
+      }
+    }
+  }
+);
@@ -0,0 +1,9 @@
+import { Router, Request, Response } from "express";
+
+export const timeRouter: Router = Router();
+
+// Clock of the server, in milliseconds since the epoch, for the clients to
+// compensate the skew of their own when signing requests
+timeRouter.get("/", (_req: Request, res: Response) => {
+  res.send({ time: Date.now() });
+});
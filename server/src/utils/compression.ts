@@ -0,0 +1,94 @@
+import zlib from "zlib";
+import { promisify } from "util";
+
+export type ContentEncoding = "zstd" | "gzip";
+
+type Codec = (data: Buffer, options?: zlib.ZlibOptions) => Promise<Buffer>;
+
+type ZstdZlib = {
+  zstdCompress?: (data: Buffer, callback: zlib.CompressCallback) => void;
+  zstdDecompress?: (
+    data: Buffer,
+    options: zlib.ZlibOptions,
+    callback: zlib.CompressCallback
+  ) => void;
+};
+
+// zstd is part of node's zlib from v22.15 on: older versions only offer gzip
+const zstd = zlib as unknown as ZstdZlib;
+
+const compressors: Partial<Record<ContentEncoding, Codec>> = {
+  gzip: promisify(zlib.gzip),
+  ...(zstd.zstdCompress && { zstd: promisify(zstd.zstdCompress) as Codec }),
+};
+
+const decompressors: Partial<Record<ContentEncoding, Codec>> = {
+  gzip: promisify(zlib.gunzip),
+  ...(zstd.zstdDecompress && {
+    zstd: promisify(zstd.zstdDecompress) as Codec,
+  }),
+};
+
+// in order of preference
+export const SUPPORTED_ENCODINGS = (["zstd", "gzip"] as const).filter(
+  (encoding) => compressors[encoding] !== undefined
+);
+
+// below this size compression does not pay for itself
+const MIN_COMPRESSED_SIZE = 1024;
+
+// formats that are compressed already: compressing them again wastes time
+const COMPRESSED_EXTENSIONS = new Set([
+  "7z", "aac", "apk", "avi", "avif", "br", "bz2", "docx", "flac", "gif", "gz",
+  "heic", "jar", "jpeg", "jpg", "lz4", "lzma", "m4a", "m4v", "mkv", "mov",
+  "mp3", "mp4", "odt", "ogg", "opus", "png", "pptx", "rar", "tgz", "webm",
+  "webp", "woff2", "xlsx", "xz", "zip", "zst",
+]); // prettier-ignore
+
+export function isCompressedMedia(filePath: Buffer | string): boolean {
+  const name = filePath.toString("latin1").toLowerCase();
+  const dot = name.lastIndexOf(".");
+  return dot !== -1 && COMPRESSED_EXTENSIONS.has(name.slice(dot + 1));
+}
+
+export function parseEncoding(
+  value: string | undefined
+): ContentEncoding | undefined {
+  const encoding = value?.trim().toLowerCase();
+  return SUPPORTED_ENCODINGS.find((supported) => supported === encoding);
+}
+
+// preferred encoding among those listed in an Accept-Encoding header
+export function negotiateEncoding(
+  acceptEncoding: string | undefined
+): ContentEncoding | undefined {
+  if (!acceptEncoding) return undefined;
+
+  const accepted = acceptEncoding.split(",").flatMap((item) => {
+    const [name, ...params] = item.split(";").map((part) => part.trim());
+    const q = params.find((param) => param.startsWith("q="));
+    return q && parseFloat(q.slice(2)) === 0 ? [] : [name.toLowerCase()];
+  });
+  return SUPPORTED_ENCODINGS.find((encoding) => accepted.includes(encoding));
+}
+
+// the compressed body, or nothing when it would not be smaller
+export async function compress(
+  data: Buffer,
+  encoding: ContentEncoding
+): Promise<Buffer | undefined> {
+  if (data.length < MIN_COMPRESSED_SIZE) return undefined;
+  const compressed = await (compressors[encoding] as Codec)(data);
+  return compressed.length < data.length ? compressed : undefined;
+}
+
+// `maxLength` bounds the output, against data that expands without end
+export async function decompress(
+  data: Buffer,
+  encoding: ContentEncoding,
+  maxLength: number
+): Promise<Buffer> {
+  return (decompressors[encoding] as Codec)(data, {
+    maxOutputLength: Math.max(maxLength, 1),
+  });
+}
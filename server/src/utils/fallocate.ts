@@ -0,0 +1,65 @@
+import { spawn } from "child_process";
+import fs from "fs/promises";
+import { AllocateOperation } from "../validation/fallocateSchema";
+
+// node has no binding for fallocate(2): util-linux's fallocate runs it on the
+// file, handed over already open as fd 3 so the path is resolved only once
+const FALLOCATE = "fallocate";
+
+const FLAGS: Record<AllocateOperation, string[]> = {
+  [AllocateOperation.Allocate]: [],
+  [AllocateOperation.PunchHole]: ["--punch-hole"],
+  [AllocateOperation.ZeroRange]: ["--zero-range"],
+};
+
+function errnoError(message: string, code: string) {
+  return Object.assign(new Error(message), { code });
+}
+
+// Runs fallocate(2) on the range of the file; fails with EOPNOTSUPP when the
+// file system (or the host, lacking the tool) cannot do the operation, and
+// with ENOSPC when the blocks are not there
+export async function fallocate(
+  filePath: string,
+  operation: AllocateOperation,
+  offset: number,
+  length: number,
+  keepSize: boolean
+): Promise<void> {
+  const file = await fs.open(filePath, "r+");
+  try {
+    const args = [
+      "--offset",
+      String(offset),
+      "--length",
+      String(length),
+      ...FLAGS[operation],
+      ...(keepSize ? ["--keep-size"] : []),
+      "/dev/fd/3",
+    ];
+    await new Promise<void>((resolve, reject) => {
+      const child = spawn(FALLOCATE, args, {
+        stdio: ["ignore", "ignore", "pipe", file.fd],
+      });
+      let stderr = "";
+      child.stderr.on("data", (chunk) => (stderr += chunk));
+      child.on("error", (e: NodeJS.ErrnoException) =>
+        reject(
+          e.code === "ENOENT"
+            ? errnoError(`${FALLOCATE} is not installed`, "EOPNOTSUPP")
+            : e
+        )
+      );
+      child.on("close", (status) => {
+        if (status === 0) return resolve();
+        if (/not supported/i.test(stderr))
+          return reject(errnoError(stderr.trim(), "EOPNOTSUPP"));
+        if (/no space left/i.test(stderr))
+          return reject(errnoError(stderr.trim(), "ENOSPC"));
+        reject(new Error(stderr.trim() || `${FALLOCATE} exited ${status}`));
+      });
+    });
+  } finally {
+    await file.close();
+  }
+}
@@ -7,6 +7,73 @@ export function getPath(a: string, b: string): string {
   return path.join(a, b);
 }
 
+// Paths travel as text: valid UTF-8 as is, "%XX" for "%", control characters and
+// every byte that is not valid UTF-8. The file system is always given the raw bytes
+export function decodePath(wire: string): Buffer {
+  const text = Buffer.from(wire, "utf8");
+  const bytes: number[] = [];
+  for (let i = 0; i < text.length; i++) {
+    const hex = text.subarray(i + 1, i + 3).toString("latin1");
+    if (text[i] === 0x25 && /^[0-9a-fA-F]{2}$/.test(hex)) {
+      bytes.push(parseInt(hex, 16));
+      i += 2;
+    } else {
+      bytes.push(text[i]);
+    }
+  }
+  return Buffer.from(bytes);
+}
+
+export function encodePath(bytes: Buffer): string {
+  let wire = "";
+  for (let i = 0; i < bytes.length; ) {
+    const length = utf8SequenceLength(bytes, i);
+    const byte = bytes[i];
+    if (length === 0 || byte === 0x25 || byte < 0x20 || byte === 0x7f) {
+      wire += `%${byte.toString(16).toUpperCase().padStart(2, "0")}`;
+      i++;
+    } else {
+      wire += bytes.subarray(i, i + length).toString("utf8");
+      i += length;
+    }
+  }
+  return wire;
+}
+
+// length of the UTF-8 character starting at `i`, 0 if the bytes there are not one
+function utf8SequenceLength(bytes: Buffer, i: number): number {
+  const lead = bytes[i];
+  const length =
+    lead < 0x80 ? 1 : lead < 0xc2 ? 0 : lead < 0xe0 ? 2 : lead < 0xf0 ? 3 : 4;
+  if (length === 0 || lead > 0xf4 || i + length > bytes.length) return 0;
+
+  // decoding replaces overlong forms, surrogates and bad continuations
+  const sequence = bytes.subarray(i, i + length);
+  return Buffer.from(sequence.toString("utf8")).equals(sequence) ? length : 0;
+}
+
+// latin1 maps each byte to one character, so path.join works on the raw bytes
+export function joinFsPath(a: Buffer, b: Buffer): Buffer {
+  return Buffer.from(
+    path.join(a.toString("latin1"), b.toString("latin1")),
+    "latin1"
+  );
+}
+
+export function getFsPath(root: string, wire: string): Buffer {
+  return joinFsPath(Buffer.from(root), decodePath(wire));
+}
+
+export function getFsBaseName(filePath: Buffer): Buffer {
+  return Buffer.from(path.basename(filePath.toString("latin1")), "latin1");
+}
+
+// byte prefix check, as String.startsWith on the text form
+export function startsWithPath(filePath: Buffer, prefix: string): boolean {
+  const prefixBytes = Buffer.from(prefix);
+  return filePath.subarray(0, prefixBytes.length).equals(prefixBytes);
+}
+
 export function getUserPath(username: string, apiKey: string): string {
   return getPath(env.USERS_PATH, `${username}_${apiKey}`);
 }
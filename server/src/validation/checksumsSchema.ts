@@ -0,0 +1,11 @@
+import { z } from "zod";
+
+export const checksumsQuerySchema = z
+  .object({
+    // the block hashes are enough: the digest of the whole file may be null
+    blocksOnly: z.enum(["true", "false"]).transform((v) => v === "true"),
+  })
+  .partial()
+  .strict();
+
+export type ChecksumsQuery = z.infer<typeof checksumsQuerySchema>;
@@ -0,0 +1,25 @@
+import { z } from "zod";
+import { decodePath } from "../utils/path";
+
+// any name Linux accepts, in the escaped text form (see utils/path)
+const filePathPattern = /^\/?([^\/\0]+\/)*([^\/\0]+)$/;
+
+export const copySchema = z
+  .object({
+    source: z
+      .string()
+      .regex(filePathPattern, {
+        message: "source must be a valid path like /folder/file.txt",
+      })
+      .refine((source) => !decodePath(source).includes(".."), {
+        message: "source cannot contain '..'",
+      }),
+
+    sourceOffset: z.number().int().nonnegative().default(0),
+    destOffset: z.number().int().nonnegative().default(0),
+    // omitted: everything up to the end of the source
+    length: z.number().int().nonnegative().optional(),
+  })
+  .strict();
+
+export type CopyRequest = z.infer<typeof copySchema>;
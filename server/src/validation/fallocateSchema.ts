@@ -0,0 +1,27 @@
+import { z } from "zod";
+
+export enum AllocateOperation {
+  Allocate = "allocate",
+  PunchHole = "punch_hole",
+  ZeroRange = "zero_range",
+}
+
+export const fallocateSchema = z
+  .object({
+    operation: z.nativeEnum(AllocateOperation),
+    offset: z.number().int().nonnegative(),
+    length: z.number().int().positive(),
+    // the file size never changes, even if the range goes past the end
+    keepSize: z.boolean(),
+  })
+  .strict()
+  .refine(
+    (request) =>
+      request.operation !== AllocateOperation.PunchHole || request.keepSize,
+    {
+      path: ["keepSize"],
+      message: "`punch_hole` requires keepSize to be true",
+    }
+  );
+
+export type FallocateRequest = z.infer<typeof fallocateSchema>;
@@ -0,0 +1,18 @@
+import { z } from "zod";
+import { SUPPORTED_FLAGS } from "../model/fileFlags";
+
+export const flagsSchema = z
+  .object({
+    flags: z
+      .number()
+      .int()
+      .nonnegative()
+      .refine((flags) => (flags & ~SUPPORTED_FLAGS) === 0, {
+        message: "only the immutable (0x10) and append (0x20) flags are supported",
+      }),
+    // flags already set are removed only when this is true
+    clear: z.boolean().optional(),
+  })
+  .strict();
+
+export type FlagsRequest = z.infer<typeof flagsSchema>;
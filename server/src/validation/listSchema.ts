@@ -0,0 +1,20 @@
+import { z } from "zod";
+
+export const listQuerySchema = z
+  .object({
+    limit: z
+      .string()
+      .regex(/^\d+$/, "Must be a number")
+      .transform(Number)
+      .refine((limit) => limit > 0 && limit <= 10000, {
+        message: "limit must be between 1 and 10000",
+      })
+      .optional(),
+    cursor: z
+      .string()
+      .regex(/^[A-Za-z0-9_-]+$/, "Must be a cursor returned by the server")
+      .optional(),
+  })
+  .strict();
+
+export type ListQuery = z.infer<typeof listQuerySchema>;
@@ -1,7 +1,8 @@
 import { z } from "zod";
 import { FileType, Mode } from "../model/file";
 
-const filePathPattern = /^\/?([^\/:*?"<>|\r\n]+\/)*([^\/:*?"<>|\r\n]+)$/;
+// any name Linux accepts, in the escaped text form (see utils/path)
+const filePathPattern = /^\/?([^\/\0]+\/)*([^\/\0]+)$/;
 
 const baseMetadataSchema = z.object({
   newPath: z
@@ -19,7 +20,7 @@ const baseMetadataSchema = z.object({
   kind: z.nativeEnum(FileType),
   refPath: z.string().optional(),
 
-  perm: z.string().regex(/^[0-7]{3}$/),
+  perm: z.string().regex(/^[0-7]{3,4}$/), // 4 digits with setuid/setgid/sticky
 
   mode: z.nativeEnum(Mode),
   offset: z.number().int().nonnegative().optional(),
@@ -35,6 +36,19 @@ export const metadataSchema = baseMetadataSchema.superRefine(
       });
     }
 
+    if (
+      metadata.kind === FileType.Fifo ||
+      metadata.kind === FileType.Socket ||
+      metadata.kind === FileType.CharDevice ||
+      metadata.kind === FileType.BlockDevice
+    ) {
+      ctx.addIssue({
+        path: ["kind"],
+        code: z.ZodIssueCode.custom,
+        message: "special files are created through /mknod",
+      });
+    }
+
     if (
       (metadata.kind === FileType.SymLink ||
         metadata.kind === FileType.HardLink) &&
@@ -0,0 +1,13 @@
+import { z } from "zod";
+
+export const mkdirSchema = z
+  .object({
+    // omitted: the server default (0777 minus the umask of the server)
+    permissionsOctal: z
+      .string()
+      .regex(/^[0-7]{3,4}$/)
+      .optional(),
+  })
+  .strict();
+
+export type CreateDirectoryRequest = z.infer<typeof mkdirSchema>;
@@ -0,0 +1,17 @@
+import { z } from "zod";
+import { FileType } from "../model/file";
+
+export const mknodSchema = z
+  .object({
+    kind: z.enum([
+      FileType.Fifo,
+      FileType.Socket,
+      FileType.CharDevice,
+      FileType.BlockDevice,
+    ]),
+    rdev: z.number().int().nonnegative().default(0), // device number, as st_rdev
+    permissionsOctal: z.string().regex(/^[0-7]{3,4}$/),
+  })
+  .strict();
+
+export type CreateNodeRequest = z.infer<typeof mknodSchema>;
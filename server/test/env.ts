@@ -0,0 +1,8 @@
+// The modules under test read the environment when they are loaded: any valid
+// one will do, nothing here connects to the database or touches USERS_PATH
+process.env.DB_HOST ??= "localhost";
+process.env.DB_NAME ??= "bifrost";
+process.env.DB_USER ??= "bifrost";
+process.env.DB_PASSWORD ??= "bifrost";
+process.env.MASTER_KEY ??= "0".repeat(64);
+process.env.USERS_PATH ??= "/tmp";
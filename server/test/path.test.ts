@@ -0,0 +1,54 @@
+import { describe, it } from "node:test";
+import assert from "node:assert/strict";
+import { decodePath, encodePath } from "../src/utils/path";
+
+function roundTrip(bytes: Buffer): string {
+  const wire = encodePath(bytes);
+  assert.deepEqual(decodePath(wire), bytes, wire);
+  return wire;
+}
+
+describe("encodePath and decodePath", () => {
+  it("keep valid UTF-8", () => {
+    assert.equal(
+      roundTrip(Buffer.from("/dir/città €.txt")),
+      "/dir/città €.txt"
+    );
+  });
+
+  it("escape %", () => {
+    assert.equal(roundTrip(Buffer.from("/100%/%41")), "/100%25/%2541");
+  });
+
+  it("escape control bytes", () => {
+    assert.equal(
+      roundTrip(Buffer.from("/a\nb\tc\x7f\x01", "latin1")),
+      "/a%0Ab%09c%7F%01"
+    );
+  });
+
+  it("escape invalid UTF-8", () => {
+    assert.equal(roundTrip(Buffer.from([0x2f, 0xff, 0xfe])), "/%FF%FE");
+    // truncated sequence, lone continuation byte, surrogate
+    assert.equal(roundTrip(Buffer.from([0xe2, 0x82])), "%E2%82");
+    assert.equal(roundTrip(Buffer.from([0x80])), "%80");
+    assert.equal(roundTrip(Buffer.from([0xed, 0xa0, 0x80])), "%ED%A0%80");
+  });
+
+  it("escape overlong sequences", () => {
+    // "/" and NUL in their overlong forms must not turn into the real bytes
+    assert.equal(roundTrip(Buffer.from([0xc0, 0xaf])), "%C0%AF");
+    assert.equal(roundTrip(Buffer.from([0xe0, 0x80, 0xaf])), "%E0%80%AF");
+    assert.equal(roundTrip(Buffer.from([0xc0, 0x80])), "%C0%80");
+  });
+
+  it("round-trip every byte", () => {
+    roundTrip(Buffer.from(Array.from({ length: 256 }, (_, i) => i)));
+  });
+
+  it("decode malformed escapes literally", () => {
+    assert.deepEqual(decodePath("%zz%4"), Buffer.from("%zz%4"));
+    assert.deepEqual(decodePath("%"), Buffer.from("%"));
+    assert.deepEqual(decodePath("%2f%2F"), Buffer.from("//"));
+  });
+});